@@ -1,34 +1,86 @@
 use crate::instance::Instance;
 use bytemuck::{bytes_of, cast_slice, Pod};
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 use wgpu::util::DeviceExt;
 
-pub struct UniformBlock(Vec<u8>);
+pub struct UniformBlock {
+    data: Vec<u8>,
+    // size of the matching struct in the shader, if known
+    size: Option<usize>,
+}
 
 impl UniformBlock {
     #[inline]
     pub fn new() -> Self {
-        Self(Vec::new())
+        Self {
+            data: Vec::new(),
+            size: None,
+        }
+    }
+
+    #[inline]
+    pub fn with_size(size: usize) -> Self {
+        Self {
+            data: Vec::with_capacity(size),
+            size: Some(size),
+        }
     }
 
     #[inline]
     pub fn write<T: Pod>(&mut self, uniform: &T) {
-        self.0.append(&mut Vec::from(bytes_of(uniform)));
+        self.data.extend_from_slice(bytes_of(uniform));
     }
 
     #[inline]
     pub fn write_slice<T: Pod>(&mut self, slice: &[T]) {
-        self.0.append(&mut Vec::from(cast_slice(slice)));
+        self.data.extend_from_slice(cast_slice(slice));
     }
 
     #[inline]
     pub fn pad(&mut self, amount: usize) {
-        self.0.append(&mut vec![0u8; amount]);
+        self.data.resize(self.data.len() + amount, 0);
+    }
+
+    // pads with zeros up to `len` bytes
+    #[inline]
+    pub fn pad_to(&mut self, len: usize) {
+        if self.data.len() < len {
+            self.data.resize(len, 0);
+        }
+    }
+
+    // uniform buffers are read in 16 byte chunks, so a block has to be a multiple of 16 and
+    // match the shader's struct exactly
+    pub fn check(&self) -> anyhow::Result<()> {
+        if !self.data.len().is_multiple_of(16) {
+            anyhow::bail!(
+                "uniform block is {} bytes, which isn't a multiple of 16",
+                self.data.len()
+            );
+        }
+
+        if let Some(size) = self.size {
+            if self.data.len() != size {
+                anyhow::bail!(
+                    "uniform block is {} bytes, but the shader expects {}",
+                    self.data.len(),
+                    size
+                );
+            }
+        }
+
+        Ok(())
     }
 
     #[inline]
     pub fn finish(self) -> Vec<u8> {
-        self.0
+        self.data
     }
 }
 
@@ -43,7 +95,11 @@ pub enum Binding {
         view: Arc<wgpu::TextureView>,
         sample_type: wgpu::TextureSampleType,
     },
+    // a view with all six faces of a cube texture, for `texture_cube` in shaders
+    CubeTexture(Arc<wgpu::TextureView>),
     Sampler(Arc<wgpu::Sampler>),
+    // a sampler created with a `compare` function, for `sampler_comparison` in shaders
+    ComparisonSampler(Arc<wgpu::Sampler>),
     Uniform {
         buffer: wgpu::Buffer,
         data_len: usize,
@@ -59,10 +115,19 @@ impl Binding {
                 view_dimension: wgpu::TextureViewDimension::D2,
                 multisampled: false,
             },
+            Self::CubeTexture(_) => wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::Cube,
+                multisampled: false,
+            },
             Self::Sampler(_) => wgpu::BindingType::Sampler {
                 filtering: true,
                 comparison: false,
             },
+            Self::ComparisonSampler(_) => wgpu::BindingType::Sampler {
+                filtering: true,
+                comparison: true,
+            },
             Self::Uniform { .. } => wgpu::BindingType::Buffer {
                 ty: wgpu::BufferBindingType::Uniform,
                 has_dynamic_offset: false,
@@ -74,8 +139,12 @@ impl Binding {
     #[inline]
     pub fn resource(&self) -> wgpu::BindingResource {
         match self {
-            Self::Texture { view, .. } => wgpu::BindingResource::TextureView(view),
-            Self::Sampler(sampler) => wgpu::BindingResource::Sampler(sampler),
+            Self::Texture { view, .. } | Self::CubeTexture(view) => {
+                wgpu::BindingResource::TextureView(view)
+            }
+            Self::Sampler(sampler) | Self::ComparisonSampler(sampler) => {
+                wgpu::BindingResource::Sampler(sampler)
+            }
             Self::Uniform { buffer, .. } => buffer.as_entire_binding(),
         }
     }
@@ -108,6 +177,14 @@ impl Binding {
     }
 }
 
+static GENERATED_BIND_GROUPS: AtomicUsize = AtomicUsize::new(0);
+
+// bind groups created by `BindGroup::generate` so far, for measuring churn
+#[inline]
+pub fn generated_bind_groups() -> usize {
+    GENERATED_BIND_GROUPS.load(Ordering::Relaxed)
+}
+
 pub struct BindGroup {
     instance: Instance,
     bindings: HashMap<u32, Binding>,
@@ -155,6 +232,19 @@ impl BindGroup {
         }
     }
 
+    pub fn bind_cube_texture(&mut self, index: u32, texture: &Arc<wgpu::TextureView>) {
+        if let Some(Binding::CubeTexture(view)) = self.bindings.get(&index) {
+            if Arc::ptr_eq(texture, view) {
+                return;
+            }
+        }
+
+        self.bindings
+            .insert(index, Binding::CubeTexture(texture.clone()));
+
+        self.bind_group = None;
+    }
+
     pub fn bind_sampler(&mut self, index: u32, sampler: &Arc<wgpu::Sampler>) {
         if let Some(binding) = self.bindings.get_mut(&index) {
             match binding {
@@ -177,6 +267,19 @@ impl BindGroup {
         }
     }
 
+    pub fn bind_comparison_sampler(&mut self, index: u32, sampler: &Arc<wgpu::Sampler>) {
+        if let Some(Binding::ComparisonSampler(this_sampler)) = self.bindings.get(&index) {
+            if Arc::ptr_eq(sampler, this_sampler) {
+                return;
+            }
+        }
+
+        self.bindings
+            .insert(index, Binding::ComparisonSampler(sampler.clone()));
+
+        self.bind_group = None;
+    }
+
     pub fn bind_uniform<T: Pod>(&mut self, index: u32, uniform: &T) {
         let data = bytes_of(uniform);
 
@@ -204,6 +307,10 @@ impl BindGroup {
     }
 
     pub fn bind_uniform_block(&mut self, index: u32, block: UniformBlock) {
+        if let Err(err) = block.check() {
+            log::error!("invalid uniform block at binding {}: {}", index, err);
+        }
+
         let data = block.finish();
 
         if let Some(binding) = self.bindings.get_mut(&index) {
@@ -275,8 +382,42 @@ impl BindGroup {
                 });
 
             self.bind_group = Some(bind_group);
+            GENERATED_BIND_GROUPS.fetch_add(1, Ordering::Relaxed);
 
             self.bind_group.as_ref().unwrap()
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mis_sized_uniform_blocks_are_rejected() {
+        // a light count padded out to 16 bytes followed by one vec4, like `light.wgsl` expects
+        let mut block = UniformBlock::with_size(32);
+        block.write(&1u32);
+        block.pad(12);
+        block.write(&[0.0f32; 4]);
+        assert!(block.check().is_ok());
+
+        // padding forgotten, so the slice starts 12 bytes early
+        let mut unpadded = UniformBlock::with_size(32);
+        unpadded.write(&1u32);
+        unpadded.write(&[0.0f32; 4]);
+        assert!(unpadded.check().is_err());
+
+        // aligned, but not what the shader expects
+        let mut short = UniformBlock::with_size(32);
+        short.write(&[0.0f32; 4]);
+        assert!(short.check().is_err());
+
+        // without an expected size only the alignment is checked
+        let mut unsized_block = UniformBlock::new();
+        unsized_block.write(&[0.0f32; 4]);
+        assert!(unsized_block.check().is_ok());
+        unsized_block.write(&0.0f32);
+        assert!(unsized_block.check().is_err());
+    }
+}
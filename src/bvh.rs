@@ -0,0 +1,328 @@
+use crate::mesh::{Aabb, Vertex};
+use glam::*;
+
+// triangles per leaf, below this splitting costs more than testing them
+const MAX_LEAF_TRIANGLES: usize = 4;
+
+struct BvhNode {
+    bounds: Aabb,
+    // leaves hold `count` triangles from `start` in `Bvh::triangles`, inner nodes have a count
+    // of 0 and their children at `start` and `start + 1`
+    start: u32,
+    count: u32,
+}
+
+// bounding volume hierarchy over a mesh's triangles, split at the median along the longest
+// axis of the triangle centroids
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    triangles: Vec<u32>,
+}
+
+impl Bvh {
+    pub fn new(vertices: &[Vertex], indices: &[u32]) -> Self {
+        let triangle_count = indices.len() / 3;
+
+        let mut bounds = Vec::with_capacity(triangle_count);
+        let mut centroids = Vec::with_capacity(triangle_count);
+
+        for triangle in indices.chunks_exact(3) {
+            let a = vertices[triangle[0] as usize].position;
+            let b = vertices[triangle[1] as usize].position;
+            let c = vertices[triangle[2] as usize].position;
+
+            bounds.push(Aabb {
+                min: a.min(b).min(c),
+                max: a.max(b).max(c),
+            });
+            centroids.push((a + b + c) / 3.0);
+        }
+
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(triangle_count * 2 / MAX_LEAF_TRIANGLES + 1),
+            triangles: (0..triangle_count as u32).collect(),
+        };
+
+        if triangle_count > 0 {
+            bvh.nodes.push(BvhNode {
+                bounds: bounds[0],
+                start: 0,
+                count: triangle_count as u32,
+            });
+
+            bvh.split(0, &bounds, &centroids);
+        }
+
+        bvh
+    }
+
+    fn split(&mut self, node: usize, bounds: &[Aabb], centroids: &[Vec3]) {
+        let start = self.nodes[node].start as usize;
+        let count = self.nodes[node].count as usize;
+        let triangles = &mut self.triangles[start..start + count];
+
+        let first = triangles[0] as usize;
+        let mut node_bounds = bounds[first];
+        let mut centroid_min = centroids[first];
+        let mut centroid_max = centroids[first];
+
+        for &triangle in triangles.iter() {
+            node_bounds = node_bounds.union(&bounds[triangle as usize]);
+            centroid_min = centroid_min.min(centroids[triangle as usize]);
+            centroid_max = centroid_max.max(centroids[triangle as usize]);
+        }
+
+        self.nodes[node].bounds = node_bounds;
+
+        let extent = centroid_max - centroid_min;
+
+        // all centroids in one spot can't be split any further
+        if count <= MAX_LEAF_TRIANGLES || extent.max_element() <= 0.0 {
+            return;
+        }
+
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+
+        let mid = count / 2;
+        triangles.select_nth_unstable_by(mid, |a, b| {
+            centroids[*a as usize][axis].total_cmp(&centroids[*b as usize][axis])
+        });
+
+        let left = self.nodes.len();
+
+        self.nodes.push(BvhNode {
+            bounds: node_bounds,
+            start: start as u32,
+            count: mid as u32,
+        });
+        self.nodes.push(BvhNode {
+            bounds: node_bounds,
+            start: (start + mid) as u32,
+            count: (count - mid) as u32,
+        });
+
+        self.nodes[node].start = left as u32;
+        self.nodes[node].count = 0;
+
+        self.split(left, bounds, centroids);
+        self.split(left + 1, bounds, centroids);
+    }
+
+    // calls `hit` for every triangle in a box the ray passes through closer than the nearest
+    // hit so far, `hit` returns the distance along the ray if the triangle was hit
+    pub fn raycast<T>(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        mut hit: impl FnMut(usize) -> Option<(f32, T)>,
+    ) -> Option<T> {
+        let inv_direction = direction.recip();
+
+        let mut nearest: Option<(f32, T)> = None;
+        let mut stack = Vec::with_capacity(64);
+
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+
+            let max_t = nearest.as_ref().map_or(f32::INFINITY, |(t, _)| *t);
+
+            if !ray_aabb(origin, inv_direction, &node.bounds, max_t) {
+                continue;
+            }
+
+            if node.count == 0 {
+                stack.push(node.start as usize);
+                stack.push(node.start as usize + 1);
+                continue;
+            }
+
+            let start = node.start as usize;
+
+            for &triangle in &self.triangles[start..start + node.count as usize] {
+                if let Some((t, value)) = hit(triangle as usize) {
+                    if nearest.as_ref().is_none_or(|(nearest, _)| t < *nearest) {
+                        nearest = Some((t, value));
+                    }
+                }
+            }
+        }
+
+        nearest.map(|(_, value)| value)
+    }
+}
+
+// slab test, true if the ray enters `bounds` before `max_t`
+#[inline]
+fn ray_aabb(origin: Vec3, inv_direction: Vec3, bounds: &Aabb, max_t: f32) -> bool {
+    let t0 = (bounds.min - origin) * inv_direction;
+    let t1 = (bounds.max - origin) * inv_direction;
+
+    let mut near = 0.0;
+    let mut far = max_t;
+
+    for axis in 0..3 {
+        let (enter, exit) = if t0[axis] > t1[axis] {
+            (t1[axis], t0[axis])
+        } else {
+            (t0[axis], t1[axis])
+        };
+
+        // a ray parallel to an axis starting on the slab's edge gives nan, comparisons with it
+        // are false so it doesn't narrow the range
+        if enter > near {
+            near = enter;
+        }
+
+        if exit < far {
+            far = exit;
+        }
+    }
+
+    near <= far
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::ray_triangle;
+    use std::time::Instant;
+
+    // a bumpy `size` x `size` grid of quads in the xz plane, two triangles each
+    fn terrain(size: u32) -> (Vec<Vertex>, Vec<u32>) {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+
+        for z in 0..=size {
+            for x in 0..=size {
+                let (x, z) = (x as f32, z as f32);
+
+                vertices.push(Vertex {
+                    position: Vec3::new(x, (x * 0.3).sin() * (z * 0.2).cos() * 2.0, z),
+                    ..Default::default()
+                });
+            }
+        }
+
+        for z in 0..size {
+            for x in 0..size {
+                let i = z * (size + 1) + x;
+                let below = i + size + 1;
+
+                indices.extend_from_slice(&[i, below, i + 1, i + 1, below, below + 1]);
+            }
+        }
+
+        (vertices, indices)
+    }
+
+    // rays from above the grid, aimed down at spots spread over it
+    fn rays(size: u32, count: u32) -> Vec<(Vec3, Vec3)> {
+        let inside = |t: f32| 0.5 + t * (size as f32 - 1.0);
+
+        (0..count)
+            .map(|i| {
+                let t = i as f32 / count as f32;
+                let target = Vec3::new(inside(t), 0.0, inside((t * 37.0).fract()));
+                let origin = target + Vec3::new(3.0, 20.0, -2.0);
+
+                (origin, (target - origin).normalize())
+            })
+            .collect()
+    }
+
+    fn linear_raycast(
+        vertices: &[Vertex],
+        indices: &[u32],
+        origin: Vec3,
+        direction: Vec3,
+    ) -> Option<f32> {
+        indices
+            .chunks_exact(3)
+            .filter_map(|triangle| {
+                let [a, b, c] = [0, 1, 2].map(|i| vertices[triangle[i] as usize].position);
+                ray_triangle(origin, direction, a, b, c).map(|tuv| tuv.x)
+            })
+            .min_by(f32::total_cmp)
+    }
+
+    fn bvh_raycast(
+        bvh: &Bvh,
+        vertices: &[Vertex],
+        indices: &[u32],
+        origin: Vec3,
+        direction: Vec3,
+    ) -> Option<f32> {
+        bvh.raycast(origin, direction, |triangle| {
+            let [a, b, c] =
+                [0, 1, 2].map(|i| vertices[indices[triangle * 3 + i] as usize].position);
+            ray_triangle(origin, direction, a, b, c).map(|tuv| (tuv.x, tuv.x))
+        })
+    }
+
+    #[test]
+    fn bvh_hits_what_a_linear_scan_hits() {
+        let (vertices, indices) = terrain(24);
+        let bvh = Bvh::new(&vertices, &indices);
+
+        for (origin, direction) in rays(24, 200) {
+            let expected = linear_raycast(&vertices, &indices, origin, direction);
+            assert_eq!(
+                bvh_raycast(&bvh, &vertices, &indices, origin, direction),
+                expected
+            );
+        }
+
+        // rays pointing away miss both ways
+        let up = (Vec3::new(5.0, 10.0, 5.0), Vec3::Y);
+        assert_eq!(bvh_raycast(&bvh, &vertices, &indices, up.0, up.1), None);
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with `cargo test --release -- --ignored --nocapture`"]
+    fn bvh_raycast_benchmark() {
+        // 224 x 224 quads, 100352 triangles
+        let (vertices, indices) = terrain(224);
+        let rays = rays(224, 1000);
+
+        let start = Instant::now();
+        let bvh = Bvh::new(&vertices, &indices);
+        let build = start.elapsed();
+
+        let start = Instant::now();
+        let bvh_hits = rays
+            .iter()
+            .map(|&(origin, direction)| bvh_raycast(&bvh, &vertices, &indices, origin, direction))
+            .collect::<Vec<_>>();
+        let with_bvh = start.elapsed();
+
+        let start = Instant::now();
+        let linear_hits = rays
+            .iter()
+            .map(|&(origin, direction)| linear_raycast(&vertices, &indices, origin, direction))
+            .collect::<Vec<_>>();
+        let linear = start.elapsed();
+
+        assert_eq!(bvh_hits, linear_hits);
+
+        println!(
+            "{} triangles, {} rays: build {:?}, bvh {:?} ({:?} per ray), linear {:?} ({:?} per ray)",
+            indices.len() / 3,
+            rays.len(),
+            build,
+            with_bvh,
+            with_bvh / rays.len() as u32,
+            linear,
+            linear / rays.len() as u32,
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use egui::{Align, Color32, ComboBox, CtxRef, Key, ScrollArea, Window};
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+// records kept for the console, older ones are dropped
+const CAPACITY: usize = 1000;
+
+pub struct LogRecord {
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+#[derive(Default)]
+pub struct LogRecords {
+    pub records: VecDeque<LogRecord>,
+    // records ever logged, including dropped ones
+    pub total: usize,
+}
+
+// passes records on to `inner` and keeps the ones it logs for the console
+struct ConsoleLogger<L> {
+    inner: L,
+    records: Arc<Mutex<LogRecords>>,
+}
+
+impl<L: Log> Log for ConsoleLogger<L> {
+    #[inline]
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        self.inner.log(record);
+
+        let mut log = self.records.lock().unwrap();
+
+        if log.records.len() == CAPACITY {
+            log.records.pop_front();
+        }
+
+        log.records.push_back(LogRecord {
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+        });
+        log.total += 1;
+    }
+
+    #[inline]
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+// installs `inner` as the global logger, records it logs also go to the returned buffer
+pub fn init(
+    inner: impl Log + 'static,
+    max_level: LevelFilter,
+) -> Result<Arc<Mutex<LogRecords>>, SetLoggerError> {
+    let records = Arc::new(Mutex::new(LogRecords::default()));
+
+    log::set_boxed_logger(Box::new(ConsoleLogger {
+        inner,
+        records: records.clone(),
+    }))?;
+    log::set_max_level(max_level);
+
+    Ok(records)
+}
+
+pub struct Console {
+    pub open: bool,
+    records: Arc<Mutex<LogRecords>>,
+    // least severe level shown
+    filter: Level,
+    input: String,
+    // `LogRecords::total` when last drawn, new records scroll to the bottom
+    seen: usize,
+}
+
+impl Console {
+    pub const LEVELS: [Level; 5] = [
+        Level::Error,
+        Level::Warn,
+        Level::Info,
+        Level::Debug,
+        Level::Trace,
+    ];
+
+    pub fn new(records: Arc<Mutex<LogRecords>>) -> Self {
+        Self {
+            open: false,
+            records,
+            filter: Level::Info,
+            input: String::new(),
+            seen: 0,
+        }
+    }
+
+    #[inline]
+    fn color(level: Level) -> Color32 {
+        match level {
+            Level::Error => Color32::from_rgb(230, 80, 80),
+            Level::Warn => Color32::from_rgb(230, 190, 80),
+            Level::Info => Color32::LIGHT_GRAY,
+            Level::Debug | Level::Trace => Color32::GRAY,
+        }
+    }
+
+    // returns the command entered this frame, if any
+    pub fn ui(&mut self, ctx: &CtxRef) -> Option<String> {
+        let records = &self.records;
+        let filter = &mut self.filter;
+        let input = &mut self.input;
+        let seen = &mut self.seen;
+
+        let mut command = None;
+
+        Window::new("Console")
+            .open(&mut self.open)
+            .default_width(600.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ComboBox::from_label("Level")
+                        .selected_text(filter.as_str())
+                        .show_ui(ui, |ui| {
+                            for level in Self::LEVELS.iter() {
+                                ui.selectable_value(filter, *level, level.as_str());
+                            }
+                        });
+
+                    if ui.button("Clear").clicked() {
+                        records.lock().unwrap().records.clear();
+                    }
+                });
+
+                ui.separator();
+
+                ScrollArea::from_max_height(300.0).show(ui, |ui| {
+                    // nothing may log while the records are locked
+                    let log = records.lock().unwrap();
+
+                    for record in log.records.iter().filter(|r| r.level <= *filter) {
+                        ui.colored_label(
+                            Self::color(record.level),
+                            format!("[{}] {}: {}", record.level, record.target, record.message),
+                        );
+                    }
+
+                    if log.total != *seen {
+                        *seen = log.total;
+                        ui.scroll_to_cursor(Align::BOTTOM);
+                    }
+                });
+
+                ui.separator();
+
+                let response = ui.text_edit_singleline(input);
+
+                if response.lost_focus() && ui.input().key_pressed(Key::Enter) {
+                    let entered = input.trim().to_string();
+                    input.clear();
+
+                    if !entered.is_empty() {
+                        command = Some(entered);
+                    }
+
+                    response.request_focus();
+                }
+            });
+
+        command
+    }
+}
@@ -0,0 +1,69 @@
+use glam::*;
+use winit::{dpi::PhysicalPosition, window::Window};
+
+// relative mouse mode, the cursor is hidden and locked while grabbed and
+// motion is accumulated from raw device events instead
+pub struct CursorGrab {
+    grabbed: bool,
+    restore_position: Vec2,
+    delta: Vec2,
+}
+
+impl CursorGrab {
+    pub fn new() -> Self {
+        Self {
+            grabbed: false,
+            restore_position: Vec2::ZERO,
+            delta: Vec2::ZERO,
+        }
+    }
+
+    pub fn grab(&mut self, window: &Window, cursor_position: Vec2) {
+        if self.grabbed {
+            return;
+        }
+
+        if let Err(err) = window.set_cursor_grab(true) {
+            log::warn!("failed to grab cursor: {}", err);
+            return;
+        }
+
+        window.set_cursor_visible(false);
+
+        self.grabbed = true;
+        self.restore_position = cursor_position;
+        self.delta = Vec2::ZERO;
+    }
+
+    pub fn release(&mut self, window: &Window) {
+        if !self.grabbed {
+            return;
+        }
+
+        if let Err(err) = window.set_cursor_grab(false) {
+            log::warn!("failed to release cursor: {}", err);
+        }
+
+        window.set_cursor_visible(true);
+
+        let position = PhysicalPosition::new(self.restore_position.x, self.restore_position.y);
+        if let Err(err) = window.set_cursor_position(position) {
+            log::warn!("failed to restore cursor position: {}", err);
+        }
+
+        self.grabbed = false;
+        self.delta = Vec2::ZERO;
+    }
+
+    #[inline]
+    pub fn mouse_motion(&mut self, delta: (f64, f64)) {
+        if self.grabbed {
+            self.delta += Vec2::new(delta.0 as f32, delta.1 as f32);
+        }
+    }
+
+    #[inline]
+    pub fn take_delta(&mut self) -> Vec2 {
+        std::mem::replace(&mut self.delta, Vec2::ZERO)
+    }
+}
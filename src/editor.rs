@@ -1,39 +1,546 @@
 use crate::{
+    console::{Console, LogRecords},
+    gizmo::{Gizmo, GizmoMode},
     instance::Instance,
     labled,
-    node::{drag3, drag_vec3, Node},
-    transform::Transform,
-    world::World,
+    mesh::{Mesh, Vertex},
+    node::{drag_vec3, transform_ui, Component, Node, NodeId},
+    pacing::{self, FramePacing},
+    renderer::{
+        DirectionalLight, Frame, Grid, GridPlane, RenderPasses, RenderStats, Renderer, Tonemap,
+    },
+    transform::{GlobalTransform, Transform},
+    world::{OutOfBounds, RenderSettings, Resources, World},
 };
 use egui::*;
-use glam::Quat;
-use std::sync::Arc;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{Arc, Mutex},
+};
 use wgpu::util::DeviceExt;
 
+// editor files are optional, a missing or broken one falls back to the defaults
+fn load_ron<T: Default + DeserializeOwned>(path: &str) -> T {
+    match std::fs::read_to_string(path) {
+        Ok(ron) => ron::de::from_str(&ron).unwrap_or_else(|err| {
+            log::warn!("failed to parse '{}': {}", path, err);
+            T::default()
+        }),
+        Err(_) => T::default(),
+    }
+}
+
+fn save_ron<T: Serialize>(path: &str, value: &T) {
+    let ron = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default()).unwrap();
+
+    if let Err(err) = std::fs::write(path, ron) {
+        log::warn!("failed to save '{}': {}", path, err);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preferences {
+    pub dark: bool,
+    pub ui_scale: f32,
+    pub spacing: f32,
+    pub gizmo: bool,
+    pub gizmo_size: f32,
+    pub grid: bool,
+    pub grid_plane: GridPlane,
+    pub grid_origin: glam::Vec3,
+    pub grid_spacing: f32,
+}
+
+impl Default for Preferences {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            dark: true,
+            ui_scale: 1.0,
+            spacing: 1.0,
+            gizmo: true,
+            gizmo_size: 80.0,
+            grid: true,
+            grid_plane: GridPlane::XZ,
+            grid_origin: glam::Vec3::ZERO,
+            grid_spacing: 1.0,
+        }
+    }
+}
+
+impl Preferences {
+    pub const PATH: &'static str = "editor.ron";
+
+    #[inline]
+    pub fn load() -> Self {
+        load_ron(Self::PATH)
+    }
+
+    #[inline]
+    pub fn save(&self) {
+        save_ron(Self::PATH, self);
+    }
+
+    pub fn style(&self) -> Style {
+        let mut style = Style {
+            visuals: if self.dark {
+                Visuals::dark()
+            } else {
+                Visuals::light()
+            },
+            ..Default::default()
+        };
+
+        style.spacing.item_spacing *= self.spacing;
+        style.spacing.window_padding *= self.spacing;
+        style.spacing.button_padding *= self.spacing;
+        style.spacing.indent *= self.spacing;
+
+        style
+    }
+}
+
+// named render settings shared between worlds
+#[derive(Default, Serialize, Deserialize)]
+pub struct RenderPresets {
+    pub presets: BTreeMap<String, RenderSettings>,
+    #[serde(skip)]
+    selected: Option<String>,
+    #[serde(skip)]
+    new_name: String,
+    // settings before the last preset was applied
+    #[serde(skip)]
+    undo: Option<RenderSettings>,
+}
+
+impl RenderPresets {
+    pub const PATH: &'static str = "render_presets.ron";
+
+    #[inline]
+    pub fn load() -> Self {
+        load_ron(Self::PATH)
+    }
+
+    #[inline]
+    pub fn save(&self) {
+        save_ron(Self::PATH, self);
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui, settings: &mut RenderSettings) {
+        let selected = &mut self.selected;
+        let presets = &self.presets;
+
+        ComboBox::from_label("Preset")
+            .selected_text(selected.as_deref().unwrap_or("None"))
+            .show_ui(ui, |ui| {
+                for name in presets.keys() {
+                    ui.selectable_value(selected, Some(name.clone()), name);
+                }
+            });
+
+        ui.horizontal(|ui| {
+            let preset = self
+                .selected
+                .as_ref()
+                .and_then(|name| self.presets.get(name));
+
+            if let Some(preset) = preset {
+                if ui.button("Apply").clicked() {
+                    self.undo = Some(std::mem::replace(settings, preset.clone()));
+                }
+
+                if ui.button("Delete").clicked() {
+                    let name = self.selected.take().unwrap();
+                    self.presets.remove(&name);
+                    self.save();
+                }
+            }
+
+            if let Some(undo) = &self.undo {
+                if ui.button("Undo Apply").clicked() {
+                    *settings = undo.clone();
+                    self.undo = None;
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.new_name);
+
+            if ui.button("Save Current As").clicked() && !self.new_name.is_empty() {
+                let name = std::mem::take(&mut self.new_name);
+
+                self.presets.insert(name.clone(), settings.clone());
+                self.selected = Some(name);
+                self.save();
+            }
+        });
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TransformSpace {
+    Local,
+    World,
+}
+
+// edits the node's transform as it is in world space, the result is brought back into the
+// parent's space, only when changed so decomposing the matrices can't make it drift
+fn world_transform_ui(ui: &mut Ui, node: &mut Node) {
+    // the global matrix is as of the last update, which is what's on screen
+    let parent = node.global.matrix * node.transform.matrix().inverse();
+
+    // a zero scale somewhere up the hierarchy leaves no world space to edit in
+    if !parent.is_finite() || parent.determinant().abs() <= f32::EPSILON {
+        transform_ui(ui, &mut node.transform);
+        return;
+    }
+
+    let before = Transform::from_matrix(node.global.matrix);
+    let mut world = before.clone();
+
+    transform_ui(ui, &mut world);
+
+    if world != before {
+        node.transform = Transform::from_matrix(parent.inverse() * world.matrix());
+    }
+}
+
+// case-insensitive match against the node's name, id or component names
+fn node_matches(id: &NodeId, node: &Node, filter: &str) -> bool {
+    filter.is_empty()
+        || node.name.to_lowercase().contains(filter)
+        || id.0.to_string().contains(filter)
+        || node
+            .components
+            .iter()
+            .any(|component| component.name().to_lowercase().contains(filter))
+}
+
+// details of a mesh and its textures, for finding out why an asset looks or performs as it does
+fn mesh_stats_ui(ui: &mut Ui, resources: &Resources, mesh: &str, textures: &[&String]) {
+    ui.label(format!("Mesh: '{}'", mesh));
+
+    let mesh = match resources.get_mesh(mesh) {
+        Some(mesh) => mesh,
+        None => {
+            ui.label("Not loaded");
+            return;
+        }
+    };
+
+    ui.label(format!("Vertices: {}", mesh.vertices.len()));
+    ui.label(format!("Triangles: {}", mesh.indices.len() / 3));
+
+    if let Some(bounds) = mesh.bounds() {
+        let size = bounds.size();
+        ui.label(format!(
+            "Size: {:.3} x {:.3} x {:.3}",
+            size.x, size.y, size.z
+        ));
+    }
+
+    // attributes a file doesn't have keep their `Vertex::default` value
+    let default = Vertex::default();
+    let normals = mesh.vertices.iter().any(|v| v.normal != default.normal);
+    let uvs = mesh.vertices.iter().any(|v| v.uv != default.uv);
+    let colors = mesh.vertices.iter().any(|v| v.color != default.color);
+    let tangents = mesh.vertices.iter().any(|v| v.tangent != default.tangent);
+
+    ui.label(format!("Normals: {}", normals));
+    ui.label(format!("UVs: {}", uvs));
+    ui.label(format!("Colors: {}", colors));
+    ui.label(format!("Tangents: {}", tangents));
+
+    for texture in textures.iter().filter(|texture| !texture.is_empty()) {
+        match resources.texture_size(texture.as_str()) {
+            Some((width, height)) => ui.label(format!("'{}': {}x{}", texture, width, height)),
+            None => ui.label(format!("'{}': not loaded", texture)),
+        };
+    }
+}
+
 pub struct Editor {
     pub input: RawInput,
     pub ctx: CtxRef,
     pub open: bool,
+    pub preferences_open: bool,
+    pub preferences: Preferences,
+    // window scale factor, ui is scaled by this times `preferences.ui_scale`
+    pub scale_factor: f32,
+    // physical size of the window
+    pub screen_size: Vec2,
+    pub import_dir: String,
+    pub export_path: String,
+    pub screenshot_path: String,
+    // set by the screenshot button, main captures the next frame and clears it
+    pub screenshot_requested: bool,
+    pub node_filter: String,
+    // space the inspector edits node transforms in
+    pub transform_space: TransformSpace,
+    pub selected: Option<NodeId>,
+    pub stats: RenderStats,
+    pub frames_in_flight: usize,
+    pub passes: RenderPasses,
+    pub paused: bool,
+    pub step: bool,
+    pub render_presets: RenderPresets,
+    pub pacing: FramePacing,
+    pub pacing_open: bool,
+    pub present_mode: wgpu::PresentMode,
+    pub console: Console,
+    // dragging over the scene places the selected node on the surface under the cursor
+    pub drop_to_surface: bool,
+    // set by the revert button, the world is only reverted once confirmed
+    pub confirm_revert: bool,
+    // copied by right clicking a component, pasted onto any node
+    pub component_clipboard: Option<Component>,
+    // node picked in the viewport, its header is opened and scrolled to next frame
+    pub reveal: Option<NodeId>,
+    // part of every node header's id, bumped to reset which headers are open
+    node_list_generation: u64,
+    // translate, rotate and scale handles drawn on the selected node
+    pub gizmo: Gizmo,
 }
 
 impl Editor {
-    pub fn new() -> Self {
+    pub fn new(
+        scale_factor: f32,
+        width: u32,
+        height: u32,
+        log_records: Arc<Mutex<LogRecords>>,
+    ) -> Self {
+        let preferences = Preferences::load();
+
+        let ctx = CtxRef::default();
+        ctx.set_style(preferences.style());
+
         Self {
             input: RawInput::default(),
-            ctx: CtxRef::default(),
+            ctx,
             open: false,
+            preferences_open: false,
+            preferences,
+            scale_factor,
+            screen_size: Vec2::new(width as f32, height as f32),
+            import_dir: String::from("assets"),
+            export_path: String::from("export.gltf"),
+            screenshot_path: String::from("screenshot.png"),
+            screenshot_requested: false,
+            node_filter: String::new(),
+            transform_space: TransformSpace::Local,
+            selected: None,
+            stats: RenderStats::default(),
+            frames_in_flight: Renderer::DEFAULT_FRAMES_IN_FLIGHT,
+            passes: RenderPasses::default(),
+            paused: false,
+            step: false,
+            render_presets: RenderPresets::load(),
+            pacing: FramePacing::new(),
+            pacing_open: false,
+            present_mode: wgpu::PresentMode::Fifo,
+            console: Console::new(log_records),
+            drop_to_surface: false,
+            confirm_revert: false,
+            component_clipboard: None,
+            reveal: None,
+            node_list_generation: 0,
+            gizmo: Gizmo::new(),
+        }
+    }
+
+    #[inline]
+    pub fn pixels_per_point(&self) -> f32 {
+        self.scale_factor * self.preferences.ui_scale
+    }
+
+    // converts a position in physical pixels to egui points
+    #[inline]
+    pub fn to_points(&self, x: f32, y: f32) -> Pos2 {
+        let pixels_per_point = self.pixels_per_point();
+        Pos2::new(x / pixels_per_point, y / pixels_per_point)
+    }
+
+    #[inline]
+    pub fn begin_frame(&mut self) {
+        let pixels_per_point = self.pixels_per_point();
+
+        self.input.pixels_per_point = Some(pixels_per_point);
+        self.input.screen_rect = Some(Rect::from_min_size(
+            Default::default(),
+            self.screen_size / pixels_per_point,
+        ));
+
+        self.ctx.begin_frame(self.input.take());
+    }
+
+    pub const STEP_DELTA_TIME: f32 = 1.0 / 60.0;
+
+    // returns the delta time the world should be updated with, or `None` while paused
+    pub fn update_delta_time(&mut self, delta_time: f32) -> Option<f32> {
+        if !self.paused {
+            Some(delta_time)
+        } else if std::mem::take(&mut self.step) {
+            Some(Self::STEP_DELTA_TIME)
+        } else {
+            None
         }
     }
 
-    pub fn ui(&mut self, world: &mut World, loaded_world: &str) {
+    pub fn ui(
+        &mut self,
+        world: &mut World,
+        resources: &mut Resources,
+        renderer: &Renderer,
+        loaded_world: &mut String,
+    ) {
         if self.ctx.input().key_pressed(Key::Home) {
             self.open ^= true;
         }
 
+        if let Some(command) = self.console.ui(&self.ctx) {
+            log::info!("> {}", command);
+
+            if let Err(err) = self.run_command(&command, world, resources, loaded_world) {
+                log::warn!("{}", err);
+            }
+        }
+
+        let loaded_world = loaded_world.as_str();
+
+        // ctrl+r resets the selected node
+        let input = self.ctx.input();
+        let reset_selected = input.modifiers.ctrl && input.key_pressed(Key::R);
+
+        if reset_selected && !self.ctx.wants_keyboard_input() {
+            if let Some(node) = self.selected.and_then(|id| world.node_mut(&id)) {
+                node.transform = Transform::IDENTITY;
+            }
+        }
+
+        self.gizmo.shortcuts(&self.ctx);
+
+        if let Some((done, queued)) = resources.loading_progress() {
+            Window::new("Loading")
+                .collapsible(false)
+                .resizable(false)
+                .show(&self.ctx, |ui| {
+                    ui.label(format!("Loading assets: {}/{}", done, queued));
+                });
+        }
+
+        let import_dir = &mut self.import_dir;
+        let export_path = &mut self.export_path;
+        let screenshot_path = &mut self.screenshot_path;
+        let screenshot_requested = &mut self.screenshot_requested;
+        let node_filter = &mut self.node_filter;
+        let transform_space = &mut self.transform_space;
+        let selected = &mut self.selected;
+        let stats = self.stats;
+        let frames_in_flight = &mut self.frames_in_flight;
+        let passes = &mut self.passes;
+        let paused = &mut self.paused;
+        let step = &mut self.step;
+        let preferences_open = &mut self.preferences_open;
+        let render_presets = &mut self.render_presets;
+        let pacing_open = &mut self.pacing_open;
+        let console_open = &mut self.console.open;
+        let drop_to_surface = &mut self.drop_to_surface;
+        let confirm_revert = &mut self.confirm_revert;
+        let component_clipboard = &mut self.component_clipboard;
+        let reveal = &mut self.reveal;
+        let node_list_generation = self.node_list_generation;
+        let gizmo_mode = &mut self.gizmo.mode;
+
         Window::new("Debug")
             .open(&mut self.open)
             .show(&self.ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Preferences").clicked() {
+                        *preferences_open ^= true;
+                    }
+
+                    if ui.button("Console").clicked() {
+                        *console_open ^= true;
+                    }
+
+                    ui.checkbox(drop_to_surface, "Drop to Surface")
+                        .on_hover_text("Drag to place the selected node, shift aligns it");
+
+                    for mode in [GizmoMode::Translate, GizmoMode::Rotate, GizmoMode::Scale] {
+                        ui.selectable_value(gizmo_mode, mode, mode.name());
+                    }
+
+                    if *paused {
+                        if ui.button("Resume").clicked() {
+                            *paused = false;
+                        }
+
+                        if ui.button("Step").clicked() {
+                            *step = true;
+                        }
+                    } else if ui.button("Pause").clicked() {
+                        *paused = true;
+                    }
+                });
+
+                ui.collapsing("Statistics", |ui| {
+                    ui.label(format!("Draw calls: {}", stats.draw_calls));
+                    ui.label(format!("Triangles: {}", stats.triangles));
+                    ui.label(format!("Culled: {}", stats.culled));
+                    ui.label(format!("Ui draws: {}", stats.ui_draws));
+                    ui.label(format!("Bind groups created: {}", stats.bind_groups));
+
+                    ui.add(Slider::new(frames_in_flight, 1..=4).text("Frames in flight"));
+
+                    ui.checkbox(pacing_open, "Frame Pacing");
+
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut passes.shadow, "Shadow");
+                        ui.checkbox(&mut passes.light, "Light");
+                        ui.checkbox(&mut passes.bloom, "Bloom");
+                        ui.checkbox(&mut passes.combine, "Combine");
+                    });
+                });
+
+                ui.collapsing("Selected Mesh", |ui| {
+                    let node = match selected.and_then(|id| world.nodes.get(&id)) {
+                        Some(node) => node,
+                        None => {
+                            ui.label("No node selected");
+                            return;
+                        }
+                    };
+
+                    let mut any_mesh = false;
+
+                    for component in &node.components {
+                        if let Component::Mesh {
+                            mesh,
+                            albedo,
+                            emission,
+                            normal,
+                            ..
+                        } = component
+                        {
+                            any_mesh = true;
+                            mesh_stats_ui(ui, resources, mesh, &[albedo, emission, normal]);
+                        }
+                    }
+
+                    if !any_mesh {
+                        ui.label(format!("'{}' has no mesh", node.name));
+                    }
+                });
+
                 ui.collapsing("Render Settings", |ui| {
+                    render_presets.ui(ui, &mut world.data.render_settings);
+
+                    ui.separator();
+
                     ui.add(
                         Slider::new(&mut world.data.render_settings.bloom, 0.0..=1.0).text("Bloom"),
                     );
@@ -54,6 +561,110 @@ impl Editor {
                                 .speed(0.1)
                         )
                     );
+
+                    labled!(
+                        ui,
+                        "Environment",
+                        ui.text_edit_singleline(&mut world.data.render_settings.environment)
+                    );
+
+                    labled!(
+                        ui,
+                        "Environment Strength",
+                        ui.add(
+                            DragValue::new(&mut world.data.render_settings.environment_strength)
+                                .speed(0.1)
+                        )
+                    );
+
+                    let mut sun = world.data.sun.is_some();
+                    ui.checkbox(&mut sun, "Fallback Sun");
+
+                    if sun != world.data.sun.is_some() {
+                        world.data.sun = sun.then(DirectionalLight::default);
+                    }
+
+                    if let Some(sun) = &mut world.data.sun {
+                        ui.label("Sun Direction");
+                        drag_vec3(ui, &mut sun.direction);
+
+                        let mut sun_color = sun.color.into();
+                        labled!(ui, "Sun Color", ui.color_edit_button_rgb(&mut sun_color));
+                        sun.color = sun_color.into();
+
+                        labled!(
+                            ui,
+                            "Sun Strength",
+                            ui.add(DragValue::new(&mut sun.strength).speed(0.1))
+                        );
+
+                        ui.checkbox(&mut sun.casts_shadow, "Sun Casts Shadow");
+                    }
+
+                    let tonemap = &mut world.data.render_settings.tonemap;
+                    ComboBox::from_label("Tonemap")
+                        .selected_text(tonemap.name())
+                        .show_ui(ui, |ui| {
+                            for operator in Tonemap::ALL.iter() {
+                                ui.selectable_value(tonemap, *operator, operator.name());
+                            }
+                        });
+
+                    labled!(
+                        ui,
+                        "Exposure",
+                        ui.add(
+                            DragValue::new(&mut world.data.render_settings.exposure)
+                                .speed(0.05)
+                                .clamp_range(0.0..=f32::MAX)
+                        )
+                    );
+
+                    ui.checkbox(
+                        &mut world.data.render_settings.baked_lighting,
+                        "Baked Lighting",
+                    );
+
+                    ui.checkbox(&mut world.data.render_settings.pcss, "Soft Shadows (PCSS)");
+                    ui.add(
+                        Slider::new(&mut world.data.render_settings.shadow_softness, 0.0..=4.0)
+                            .text("Shadow Softness"),
+                    );
+                    ui.add(
+                        Slider::new(&mut world.data.render_settings.light_size, 0.0..=4.0)
+                            .text("Light Size"),
+                    );
+
+                    ui.checkbox(
+                        &mut world.data.render_settings.ssr,
+                        "Screen Space Reflections",
+                    );
+                    ui.add(
+                        Slider::new(&mut world.data.render_settings.ssr_steps, 1..=128)
+                            .text("SSR Steps"),
+                    );
+                    ui.add(
+                        Slider::new(
+                            &mut world.data.render_settings.ssr_max_distance,
+                            0.1..=100.0,
+                        )
+                        .text("SSR Max Distance"),
+                    );
+
+                    ui.checkbox(&mut world.data.render_settings.dof, "Depth of Field");
+                    labled!(
+                        ui,
+                        "Focus Distance",
+                        ui.add(
+                            DragValue::new(&mut world.data.render_settings.focus_distance)
+                                .speed(0.1)
+                                .clamp_range(0.0..=f32::MAX)
+                        )
+                    );
+                    ui.add(
+                        Slider::new(&mut world.data.render_settings.aperture, 0.0..=4.0)
+                            .text("Aperture"),
+                    );
                 });
 
                 ui.collapsing("World", |ui| {
@@ -65,36 +676,244 @@ impl Editor {
                             )
                             .unwrap();
 
-                            std::fs::write(loaded_world, world_ron).unwrap();
+                            // save back to the file the world came from, wherever its root is
+                            let path = resources
+                                .file_path(loaded_world)
+                                .cloned()
+                                .unwrap_or_else(|| loaded_world.into());
+
+                            if let Some(parent) = path.parent() {
+                                std::fs::create_dir_all(parent).unwrap();
+                            }
+
+                            std::fs::write(path, world_ron).unwrap();
+                        }
+
+                        if ui.button("Revert").clicked() {
+                            *confirm_revert = true;
                         }
 
                         if ui.button("Add").clicked() {
                             world.spawn(Node {
                                 name: String::from("New Node"),
                                 transform: Transform::IDENTITY,
+                                parent: None,
+                                global: GlobalTransform::default(),
                                 components: Vec::new(),
+                                version: 0,
                             });
                         }
 
                         if ui.button("Validate").clicked() {
                             world.validate_next_node();
                         }
+
+                        if ui.button("Bake Lighting").clicked() {
+                            let baked = world.bake_lighting(resources);
+
+                            log::debug!("baked lighting into {} meshes", baked);
+                        }
+
+                        if ui.button("Refresh Shadows").clicked() {
+                            world.refresh_shadows = true;
+                        }
+
+                        if ui.button("Bake LODs").clicked() {
+                            let baked = world.bake_lods(resources);
+
+                            log::debug!("baked {} lod meshes", baked);
+                        }
+                    });
+
+                    if *confirm_revert {
+                        ui.horizontal(|ui| {
+                            ui.label(format!("Discard unsaved changes to '{}'?", loaded_world));
+
+                            if ui.button("Revert").clicked() {
+                                *confirm_revert = false;
+
+                                match Self::revert_world(world, resources, loaded_world) {
+                                    Ok(()) => {
+                                        *selected = None;
+
+                                        log::info!("reverted world '{}'", loaded_world);
+                                    }
+                                    Err(err) => {
+                                        log::error!("failed to revert '{}': {}", loaded_world, err)
+                                    }
+                                }
+                            }
+
+                            if ui.button("Cancel").clicked() {
+                                *confirm_revert = false;
+                            }
+                        });
+                    }
+
+                    ui.horizontal(|ui| {
+                        let seed = ui.add(DragValue::new(&mut world.data.seed));
+                        ui.label("Seed");
+
+                        if seed.changed() || ui.button("Restart").clicked() {
+                            world.reseed();
+                        }
+                    });
+
+                    let mut bounded = world.data.bounds.is_some();
+                    ui.checkbox(&mut bounded, "World Bounds");
+
+                    if bounded != world.data.bounds.is_some() {
+                        world.data.bounds = bounded
+                            .then_some((glam::Vec3::splat(-100.0), glam::Vec3::splat(100.0)));
+                    }
+
+                    if let Some((min, max)) = &mut world.data.bounds {
+                        ui.label("Bounds Min");
+                        drag_vec3(ui, min);
+                        ui.label("Bounds Max");
+                        drag_vec3(ui, max);
+
+                        let out_of_bounds = &mut world.data.out_of_bounds;
+                        ComboBox::from_label("Out of Bounds")
+                            .selected_text(out_of_bounds.name())
+                            .show_ui(ui, |ui| {
+                                for policy in OutOfBounds::ALL.iter() {
+                                    ui.selectable_value(out_of_bounds, *policy, policy.name());
+                                }
+                            });
+                    }
+
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(import_dir);
+
+                        if ui.button("Import Meshes").clicked() {
+                            let ids = world.spawn_meshes(resources, import_dir.as_str(), 1.0);
+
+                            log::debug!("imported {} meshes from '{}'", ids.len(), import_dir);
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(export_path);
+
+                        if ui.button("Export glTF").clicked() {
+                            match world.export_gltf(resources, Path::new(export_path.as_str())) {
+                                Ok(count) => {
+                                    log::info!("exported {} meshes to '{}'", count, export_path)
+                                }
+                                Err(err) => {
+                                    log::error!("failed to export '{}': {}", export_path, err)
+                                }
+                            }
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(screenshot_path);
+
+                        if ui.button("Screenshot").clicked() {
+                            *screenshot_requested = true;
+                        }
+                    });
+
+                    ui.collapsing("Meshes", |ui| {
+                        let mut paths = resources.meshes.keys().cloned().collect::<Vec<_>>();
+                        paths.sort();
+
+                        for path in paths {
+                            ui.horizontal(|ui| {
+                                ui.label(path.to_string_lossy().into_owned());
+
+                                if ui.button("Flip Normals").clicked() {
+                                    resources.meshes.get_mut(&path).unwrap().flip_normals();
+                                    world.refresh_shadows = true;
+                                }
+
+                                if ui.button("Flip Winding").clicked() {
+                                    resources.meshes.get_mut(&path).unwrap().flip_winding();
+                                    world.refresh_shadows = true;
+                                }
+
+                                if ui.button("Tangents").clicked() {
+                                    resources
+                                        .meshes
+                                        .get_mut(&path)
+                                        .unwrap()
+                                        .calculate_tangents();
+                                }
+
+                                if ui.button("Weld").clicked() {
+                                    let mesh = resources.meshes.get_mut(&path).unwrap();
+                                    let removed = mesh.weld_vertices(0.0001);
+                                    world.refresh_shadows = true;
+
+                                    log::info!(
+                                        "welded {} vertices of '{}'",
+                                        removed,
+                                        path.display()
+                                    );
+                                }
+
+                                // only files, not meshes derived from them
+                                if resources.files.contains_key(&path)
+                                    && ui.button("Spawn Hierarchy").clicked()
+                                {
+                                    let key = path.to_string_lossy();
+
+                                    match world.spawn_gltf(resources, &key) {
+                                        Ok(_) => log::debug!("spawned hierarchy of '{}'", key),
+                                        Err(err) => {
+                                            log::error!("failed to spawn '{}': {}", key, err)
+                                        }
+                                    }
+                                }
+                            });
+                        }
                     });
 
                     ui.separator();
 
                     ui.label(format!("Next node id: '[{}]'", world.next_node_id.0));
-                    ui.label(format!("Next node validated: '{}'", world.next_node_validated));
+                    ui.label(format!(
+                        "Next node validated: '{}'",
+                        world.next_node_validated
+                    ));
 
                     ui.separator();
 
+                    labled!(ui, "Filter", ui.text_edit_singleline(node_filter));
+
+                    ui.horizontal(|ui| {
+                        ui.label("Transform Space");
+                        ui.selectable_value(transform_space, TransformSpace::Local, "Local");
+                        ui.selectable_value(transform_space, TransformSpace::World, "World");
+                    });
+
+                    let filter = node_filter.to_lowercase();
+
                     let mut despawn = Vec::new();
 
                     for (id, node) in &mut world.nodes {
-                        CollapsingHeader::new(format!("[{}]: {}", id.0, node.name))
-                            .id_source(id)
+                        if !node_matches(id, node, &filter) {
+                            continue;
+                        }
+
+                        let response = CollapsingHeader::new(format!("[{}]: {}", id.0, node.name))
+                            .id_source((id, node_list_generation))
+                            .default_open(*reveal == Some(*id))
                             .show(ui, |ui| {
+                                // inspector widgets don't report edits, so compare what would
+                                // be saved instead
+                                let before = ron::to_string(node).ok();
+
                                 ui.horizontal(|ui| {
+                                    if ui
+                                        .selectable_label(*selected == Some(*id), "Select")
+                                        .clicked()
+                                    {
+                                        *selected = Some(*id);
+                                    }
+
                                     if ui.button("Remove").clicked() {
                                         despawn.push(*id);
                                     }
@@ -102,40 +921,702 @@ impl Editor {
 
                                 ui.text_edit_singleline(&mut node.name);
 
-                                drag_vec3(ui, &mut node.transform.translation);
+                                match node.parent {
+                                    Some(parent) => ui.label(format!("Parent: [{}]", parent.0)),
+                                    None => ui.label("Parent: none"),
+                                };
 
-                                let (mut y, mut x, mut z) =
-                                    node.transform.rotation.to_euler(glam::EulerRot::YXZ);
-
-                                x = x / std::f32::consts::PI * 180.0;
-                                y = y / std::f32::consts::PI * 180.0;
-                                z = z / std::f32::consts::PI * 180.0;
+                                match transform_space {
+                                    TransformSpace::Local => transform_ui(ui, &mut node.transform),
+                                    TransformSpace::World => world_transform_ui(ui, node),
+                                }
 
-                                drag3(ui, &mut x, &mut y, &mut z);
+                                ui.horizontal(|ui| {
+                                    if ui.button("Reset").clicked() {
+                                        node.transform = Transform::IDENTITY;
+                                    }
 
-                                x = x / 180.0 * std::f32::consts::PI;
-                                y = y / 180.0 * std::f32::consts::PI;
-                                z = z / 180.0 * std::f32::consts::PI;
+                                    if ui.button("Reset Translation").clicked() {
+                                        node.transform.translation =
+                                            Transform::IDENTITY.translation;
+                                    }
 
-                                node.transform.rotation =
-                                    Quat::from_euler(glam::EulerRot::YXZ, y, x, z);
+                                    if ui.button("Reset Rotation").clicked() {
+                                        node.transform.rotation = Transform::IDENTITY.rotation;
+                                    }
 
-                                drag_vec3(ui, &mut node.transform.scale);
+                                    if ui.button("Reset Scale").clicked() {
+                                        node.transform.scale = Transform::IDENTITY.scale;
+                                    }
+                                });
 
                                 ui.separator();
                                 for (i, component) in node.components.iter_mut().enumerate() {
-                                    ui.collapsing(component.name(), |ui| {
+                                    let response = ui.collapsing(component.name(), |ui| {
                                         component.ui(ui);
                                     });
+
+                                    let header = response.header_response;
+                                    let menu = ui.make_persistent_id(("component menu", id.0, i));
+
+                                    if header.secondary_clicked() {
+                                        ui.memory().toggle_popup(menu);
+                                    }
+
+                                    egui::popup::popup_below_widget(ui, menu, &header, |ui| {
+                                        if ui.button("Copy Component").clicked() {
+                                            *component_clipboard = Some(component.clone());
+                                            ui.memory().close_popup();
+                                        }
+                                    });
+                                }
+
+                                if let Some(copied) = component_clipboard {
+                                    let existing = node
+                                        .components
+                                        .iter()
+                                        .position(|component| component.name() == copied.name());
+
+                                    let paste = ui
+                                        .button(format!("Paste {}", copied.name()))
+                                        .on_hover_text("Hold shift to replace an existing one");
+
+                                    if paste.clicked() {
+                                        match existing {
+                                            Some(i) if ui.input().modifiers.shift => {
+                                                node.components[i] = copied.clone()
+                                            }
+                                            _ => node.components.push(copied.clone()),
+                                        }
+                                    }
+                                }
+
+                                if ron::to_string(node).ok() != before {
+                                    node.changed();
                                 }
                             });
+
+                        if *reveal == Some(*id) {
+                            response.header_response.scroll_to_me(Align::Center);
+                        }
                     }
 
+                    // nodes hidden by the filter aren't revealed later
+                    *reveal = None;
+
                     for id in despawn {
+                        if *selected == Some(id) {
+                            *selected = None;
+                        }
+
                         world.despawn(&id);
                     }
                 });
             });
+
+        self.preferences_ui();
+        self.pacing_ui();
+
+        if self.preferences.gizmo {
+            self.gizmo_ui(world);
+        }
+
+        self.labels_ui(world);
+
+        if self.open && self.drop_to_surface {
+            self.drop_selected_to_surface(world, resources);
+        } else if self.open {
+            if let Some(id) = self.selected {
+                let screen_size = glam::Vec2::new(self.screen_size.x, self.screen_size.y);
+                let pixels_per_point = self.pixels_per_point();
+
+                self.gizmo
+                    .ui(&self.ctx, world, id, screen_size, pixels_per_point);
+            }
+
+            // a press on a handle shouldn't also select what's behind it
+            if !self.gizmo.is_dragging() {
+                self.click_select(world, resources, renderer);
+            }
+        }
+    }
+
+    // the node drawn under `cursor` in the last frame, in pixels of the window, read from the
+    // renderer's id buffer
+    pub fn pick(
+        &self,
+        instance: &Instance,
+        renderer: &Renderer,
+        cursor: glam::Vec2,
+    ) -> Option<NodeId> {
+        if cursor.x < 0.0 || cursor.y < 0.0 {
+            return None;
+        }
+
+        let id = renderer.read_id(instance, cursor.as_u32())?;
+
+        NodeId::from_pick_id(id)
+    }
+
+    // selects the node clicked in the scene and opens it in the node list, clicks on egui
+    // windows are left to them
+    fn click_select(&mut self, world: &World, resources: &Resources, renderer: &Renderer) {
+        if self.ctx.wants_pointer_input() || self.ctx.is_pointer_over_area() {
+            return;
+        }
+
+        let input = self.ctx.input();
+
+        let primary_released = input.events.iter().any(|event| {
+            matches!(
+                event,
+                Event::PointerButton {
+                    button: PointerButton::Primary,
+                    pressed: false,
+                    ..
+                }
+            )
+        });
+
+        // a release after dragging isn't a click
+        if !primary_released || !input.pointer.any_click() {
+            return;
+        }
+
+        let pointer = match input.pointer.interact_pos() {
+            Some(pointer) => pointer,
+            None => return,
+        };
+
+        let cursor = glam::Vec2::new(pointer.x, pointer.y) * self.pixels_per_point();
+
+        let picked = self.pick(&resources.instance, renderer, cursor);
+
+        // the node may have been removed since the frame was drawn
+        if let Some(id) = picked.filter(|id| world.node(id).is_some()) {
+            self.selected = Some(id);
+            self.reveal = Some(id);
+            self.node_list_generation += 1;
+        }
+    }
+
+    // while the primary button is held over the scene, moves the selected node to the surface
+    // under the cursor, holding shift also turns the node's up towards the surface normal
+    fn drop_selected_to_surface(&mut self, world: &mut World, resources: &Resources) {
+        let selected = match self.selected {
+            Some(selected) => selected,
+            None => return,
+        };
+
+        if self.ctx.is_using_pointer() || self.ctx.is_pointer_over_area() {
+            return;
+        }
+
+        let input = self.ctx.input();
+
+        let pointer = match input.pointer.interact_pos() {
+            Some(pointer) if input.pointer.button_down(PointerButton::Primary) => pointer,
+            _ => return,
+        };
+
+        let align = input.modifiers.shift;
+        let cursor = glam::Vec2::new(pointer.x, pointer.y) * self.pixels_per_point();
+        let screen_size = glam::Vec2::new(self.screen_size.x, self.screen_size.y);
+
+        let (origin, direction) = match world.cursor_ray(cursor, screen_size) {
+            Some(ray) => ray,
+            None => return,
+        };
+
+        // the node's own meshes would otherwise be hit first
+        let hit = world.raycast(resources, origin, direction, |id| {
+            world.descends_from(id, selected)
+        });
+
+        let hit = match hit {
+            Some((_, hit)) => hit,
+            None => return,
+        };
+
+        let parent = world.parent_transform(&selected).matrix();
+
+        let node = match world.node_mut(&selected) {
+            Some(node) => node,
+            None => return,
+        };
+
+        node.transform.translation = parent.inverse().transform_point3(hit.position);
+
+        if align && hit.normal != glam::Vec3::ZERO {
+            let parent_rotation = Transform::from_matrix(parent).rotation;
+            let rotation = parent_rotation * node.transform.rotation;
+
+            // the smallest turn taking the current up to the normal, keeping the heading
+            let up = (rotation * glam::Vec3::Y).normalize();
+            let aligned = glam::Quat::from_rotation_arc(up, hit.normal) * rotation;
+
+            node.transform.rotation = (parent_rotation.inverse() * aligned).normalize();
+        }
+    }
+
+    // runs a console command, its results are logged
+    fn run_command(
+        &mut self,
+        command: &str,
+        world: &mut World,
+        resources: &mut Resources,
+        loaded_world: &mut String,
+    ) -> anyhow::Result<()> {
+        let (verb, argument) = match command.split_once(' ') {
+            Some((verb, argument)) => (verb, argument.trim()),
+            None => (command, ""),
+        };
+
+        match (verb, argument) {
+            ("help", _) => {
+                log::info!(
+                    "commands: spawn <mesh>, quad [subdivisions], nearby <radius>, load <world>, \
+                     unload <asset>, unload unused, reload assets, help"
+                );
+            }
+            // an already loaded mesh, see `spawn_mesh`
+            ("spawn", mesh) if !mesh.is_empty() => {
+                if resources.get_mesh(mesh).is_none() {
+                    anyhow::bail!("no mesh '{}' is loaded", mesh);
+                }
+
+                self.spawn_mesh(world, mesh);
+            }
+            // a unit quad for terrain and water experiments, kept as mesh '<quad n>' for reuse
+            ("quad", subdivisions) => {
+                let subdivisions = match subdivisions {
+                    "" => 0,
+                    subdivisions => subdivisions.parse::<u32>()?,
+                };
+
+                // every level quadruples the triangles
+                if subdivisions > 8 {
+                    anyhow::bail!("{} subdivisions is too many, at most 8", subdivisions);
+                }
+
+                let key = format!("<quad {}>", subdivisions);
+
+                if resources.get_mesh(&key).is_none() {
+                    let mut quad = Mesh::quad(&resources.instance, glam::Vec2::ONE, subdivisions);
+                    quad.generate_buffers();
+
+                    resources.meshes.insert(key.clone().into(), quad);
+                }
+
+                self.spawn_mesh(world, &key);
+            }
+            // lists the nodes around the selected one
+            ("nearby", radius) if !radius.is_empty() => {
+                let radius = radius.parse::<f32>()?;
+
+                let selected = self
+                    .selected
+                    .and_then(|id| world.node(&id))
+                    .ok_or_else(|| anyhow::anyhow!("no node is selected"))?;
+
+                let point = selected.global.translation();
+
+                for id in world.nearby(point, radius) {
+                    if Some(id) != self.selected {
+                        log::info!("{} '{}'", id.0, world.node(&id).unwrap().name);
+                    }
+                }
+            }
+            // replaces the edited world, saving then writes to the loaded one
+            ("load", key) if !key.is_empty() => {
+                let loaded = resources
+                    .get_world(key)
+                    .ok_or_else(|| anyhow::anyhow!("no world '{}' is loaded", key))?;
+
+                *world = loaded.clone();
+                *loaded_world = key.to_string();
+                self.selected = None;
+
+                log::info!("loaded world '{}'", key);
+            }
+            // frees the gpu memory of assets the edited world doesn't use
+            ("unload", "unused") => {
+                let unloaded = resources.unload_unused(world);
+
+                log::info!("unloaded {} unused meshes and textures", unloaded);
+            }
+            ("unload", key) if !key.is_empty() => {
+                let unloaded = resources.cancel_load(key)
+                    | resources.unload_world(key)
+                    | resources.unload_mesh(key)
+                    | resources.unload_texture(key);
+
+                if !unloaded {
+                    anyhow::bail!("no asset '{}' is loaded", key);
+                }
+
+                log::info!("unloaded '{}'", key);
+            }
+            ("reload", "assets") => {
+                resources.reload_assets()?;
+
+                log::info!("reloading assets from {} roots", resources.roots.len());
+            }
+            _ => anyhow::bail!("unknown command '{}', 'help' lists them", command),
+        }
+
+        Ok(())
+    }
+
+    // a node drawing `mesh` at the origin, selected so it can be moved right away
+    fn spawn_mesh(&mut self, world: &mut World, mesh: &str) {
+        let name = Path::new(mesh)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| String::from("Mesh"));
+
+        let id = world.spawn(Node {
+            name,
+            transform: Transform::IDENTITY,
+            parent: None,
+            global: GlobalTransform::default(),
+            components: vec![Component::mesh(mesh.to_string())],
+            version: 0,
+        });
+
+        self.selected = Some(id);
+
+        log::info!("spawned '{}' as node {}", mesh, id.0);
+    }
+
+    // reloads the world from the file it came from, replacing `world` and its unsaved changes
+    fn revert_world(
+        world: &mut World,
+        resources: &mut Resources,
+        loaded_world: &str,
+    ) -> anyhow::Result<()> {
+        let path = resources
+            .file_path(loaded_world)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("'{}' wasn't loaded from a file", loaded_world))?;
+
+        resources.load_world(path)?;
+
+        *world = resources
+            .get_world(loaded_world)
+            .ok_or_else(|| anyhow::anyhow!("no world '{}' is loaded", loaded_world))?
+            .clone();
+
+        Ok(())
+    }
+
+    // the grid to draw over the scene while the editor is open
+    #[inline]
+    pub fn grid(&self) -> Option<Grid> {
+        (self.open && self.preferences.grid).then_some(Grid {
+            plane: self.preferences.grid_plane,
+            origin: self.preferences.grid_origin,
+            spacing: self.preferences.grid_spacing,
+        })
+    }
+
+    // draws every `Component::Label` into egui's background layer, below all windows
+    fn labels_ui(&self, world: &World) {
+        let pixels_per_point = self.pixels_per_point();
+        let screen_size = glam::Vec2::new(self.screen_size.x, self.screen_size.y);
+        let screen = Rect::from_min_size(Pos2::ZERO, self.screen_size / pixels_per_point);
+
+        let painter = self.ctx.layer_painter(LayerId::background());
+
+        for (id, component) in world.iter_components(Component::LABEL) {
+            if let Component::Label {
+                text,
+                color,
+                offset,
+                clamp_to_screen,
+            } = component
+            {
+                let position = world.node(&id).unwrap().global.translation() + *offset;
+
+                let pixel = match world.world_to_screen(position, screen_size) {
+                    Some(pixel) => pixel / pixels_per_point,
+                    None => continue,
+                };
+
+                let galley = painter.layout_no_wrap(TextStyle::Body, text.clone());
+                let mut rect = Align2::CENTER_BOTTOM
+                    .anchor_rect(Rect::from_min_size(pos2(pixel.x, pixel.y), galley.size));
+
+                if *clamp_to_screen {
+                    let shift =
+                        rect.min.max(screen.min) - rect.min + (rect.max.min(screen.max) - rect.max);
+                    rect = rect.translate(shift);
+                } else if !screen.intersects(rect) {
+                    continue;
+                }
+
+                let color = Rgba::from_rgb(color.x, color.y, color.z);
+                painter.galley(rect.min, galley, color.into());
+            }
+        }
+    }
+
+    // outlines the world bounds while the editor is open
+    pub fn draw_world_bounds(&self, world: &World, frame: &mut Frame) {
+        let (min, max) = match world.data.bounds {
+            Some(bounds) if self.open => bounds,
+            _ => return,
+        };
+
+        let corner = |i: usize| {
+            glam::Vec3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            )
+        };
+
+        let color = glam::Vec4::new(1.0, 0.6, 0.1, 1.0);
+
+        // corners one bit apart share an edge
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    frame.draw_line(corner(i), corner(i | bit), color, 2.0);
+                }
+            }
+        }
+    }
+
+    // marks the control points of the selected node's splines
+    pub fn draw_selected_splines(&self, world: &World, frame: &mut Frame) {
+        let node = match self.selected.and_then(|id| world.node(&id)) {
+            Some(node) if self.open => node,
+            _ => return,
+        };
+
+        let color = glam::Vec4::new(0.2, 0.8, 1.0, 1.0);
+        let line_color = glam::Vec4::new(0.1, 0.5, 0.6, 1.0);
+
+        for component in &node.components {
+            if let Component::Spline { points, closed } = component {
+                let points = points
+                    .iter()
+                    .map(|&point| node.global.matrix.transform_point3(point))
+                    .collect::<Vec<_>>();
+
+                for (i, &point) in points.iter().enumerate() {
+                    frame.draw_point(point, color, 8.0);
+
+                    if let Some(&next) = points.get(i + 1).or(closed.then(|| &points[0])) {
+                        frame.draw_line(point, next, line_color, 1.0);
+                    }
+                }
+            }
+        }
+    }
+
+    // axis gizmo in the top right corner, clicking an axis points the camera down it
+    fn gizmo_ui(&mut self, world: &mut World) {
+        let camera = match world.camera() {
+            Some(camera) => camera,
+            None => return,
+        };
+
+        let size = self.preferences.gizmo_size;
+        let view = world.node(&camera).unwrap().global.matrix.inverse();
+
+        let axes = [
+            (glam::Vec3::X, "X", Color32::from_rgb(230, 70, 70)),
+            (glam::Vec3::Y, "Y", Color32::from_rgb(70, 200, 70)),
+            (glam::Vec3::Z, "Z", Color32::from_rgb(70, 110, 230)),
+        ];
+
+        let mut clicked = None;
+
+        Area::new("gizmo")
+            .anchor(Align2::RIGHT_TOP, Vec2::new(-10.0, 10.0))
+            .show(&self.ctx, |ui| {
+                let (response, painter) = ui.allocate_painter(Vec2::splat(size), Sense::click());
+
+                let center = response.rect.center();
+                let radius = size / 2.0 - 8.0;
+
+                let mut handles = Vec::new();
+
+                for (axis, label, color) in axes.iter() {
+                    for sign in [1.0, -1.0].iter() {
+                        let axis = *axis * *sign;
+                        let view_axis = view.transform_vector3(axis).normalize_or_zero();
+                        let pos = center + Vec2::new(view_axis.x, -view_axis.y) * radius;
+
+                        handles.push((view_axis.z, axis, pos, *label, *color, *sign > 0.0));
+                    }
+                }
+
+                // draw back to front, the camera looks down -z
+                handles.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+                for (_, _, pos, label, color, positive) in &handles {
+                    if *positive {
+                        painter.line_segment([center, *pos], Stroke::new(2.0, *color));
+                        painter.circle_filled(*pos, 7.0, *color);
+                        painter.text(
+                            *pos,
+                            Align2::CENTER_CENTER,
+                            label,
+                            TextStyle::Small,
+                            Color32::BLACK,
+                        );
+                    } else {
+                        painter.circle_stroke(*pos, 5.0, Stroke::new(1.5, *color));
+                    }
+                }
+
+                if response.clicked() {
+                    if let Some(pointer) = response.interact_pointer_pos() {
+                        // pick the front-most handle under the pointer
+                        clicked = handles
+                            .iter()
+                            .rev()
+                            .find(|(_, _, pos, ..)| pos.distance(pointer) < 8.0)
+                            .map(|(_, axis, ..)| *axis);
+                    }
+                }
+            });
+
+        if let Some(axis) = clicked {
+            let parent_rotation = world.parent_transform(&camera).rotation;
+
+            let up = if axis.y.abs() > 0.5 {
+                -glam::Vec3::Z * axis.y
+            } else {
+                glam::Vec3::Y
+            };
+
+            // look from the clicked side of the axis towards the origin
+            let mut view = Transform::from_translation(axis);
+            view.look_at(glam::Vec3::ZERO, up);
+
+            let node = world.node_mut(&camera).unwrap();
+            node.transform.rotation = parent_rotation.inverse() * view.rotation;
+        }
+    }
+
+    fn pacing_ui(&mut self) {
+        let pacing = &self.pacing;
+        let present_mode = self.present_mode;
+        let frames_in_flight = self.frames_in_flight;
+
+        Window::new("Frame Pacing")
+            .open(&mut self.pacing_open)
+            .show(&self.ctx, |ui| {
+                let millis = |value: Option<f32>| match value {
+                    Some(value) => format!("{:.2} ms", value),
+                    None => String::from("-"),
+                };
+
+                ui.label(format!("Frame time: {}", millis(pacing.mean_frame_time())));
+                ui.label(format!(
+                    "Frame time deviation: {}",
+                    millis(pacing.frame_time_deviation())
+                ));
+
+                ui.separator();
+
+                ui.label(format!("Present mode: {:?}", present_mode));
+                // frames queued ahead of the display each wait a frame before being shown
+                let queued = pacing
+                    .mean_frame_time()
+                    .map(|time| time * frames_in_flight as f32);
+                ui.label(format!("Queued frame latency: ~{}", millis(queued)));
+
+                ui.separator();
+
+                ui.label(format!("Input latency: {}", millis(pacing.last_latency())));
+                ui.label(format!(
+                    "Mean input latency: {}",
+                    millis(pacing.mean_latency())
+                ));
+                ui.label(format!(
+                    "Max input latency: {}",
+                    millis(pacing.max_latency())
+                ));
+
+                let histogram = pacing.latency_histogram();
+                let max = histogram.iter().copied().max().unwrap_or(0).max(1);
+
+                let (rect, _) = ui.allocate_exact_size(vec2(200.0, 60.0), Sense::hover());
+                let bar_width = rect.width() / histogram.len() as f32;
+
+                for (i, &count) in histogram.iter().enumerate() {
+                    let height = rect.height() * count as f32 / max as f32;
+                    let left = rect.left() + i as f32 * bar_width;
+
+                    let bar = Rect::from_min_max(
+                        pos2(left, rect.bottom() - height),
+                        pos2(left + bar_width - 1.0, rect.bottom()),
+                    );
+
+                    ui.painter()
+                        .rect_filled(bar, 0.0, Color32::from_rgb(100, 160, 220));
+                }
+
+                ui.label(format!(
+                    "0 to {} ms, {} ms per bar",
+                    pacing::BUCKET_MS * pacing::BUCKETS as f32,
+                    pacing::BUCKET_MS
+                ));
+            });
+    }
+
+    fn preferences_ui(&mut self) {
+        let mut preferences = self.preferences;
+        let scale_factor = self.scale_factor;
+
+        Window::new("Preferences")
+            .open(&mut self.preferences_open)
+            .show(&self.ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Theme");
+                    ui.radio_value(&mut preferences.dark, true, "Dark");
+                    ui.radio_value(&mut preferences.dark, false, "Light");
+                });
+
+                ui.add(Slider::new(&mut preferences.ui_scale, 0.5..=3.0).text("UI Scale"));
+
+                ui.add(Slider::new(&mut preferences.spacing, 0.5..=2.0).text("Spacing"));
+
+                ui.checkbox(&mut preferences.gizmo, "Axis Gizmo");
+                ui.add(Slider::new(&mut preferences.gizmo_size, 40.0..=200.0).text("Gizmo Size"));
+
+                ui.checkbox(&mut preferences.grid, "Grid");
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut preferences.grid_plane, GridPlane::XY, "XY");
+                    ui.radio_value(&mut preferences.grid_plane, GridPlane::XZ, "XZ");
+                    ui.radio_value(&mut preferences.grid_plane, GridPlane::YZ, "YZ");
+                });
+                ui.label("Grid Origin");
+                drag_vec3(ui, &mut preferences.grid_origin);
+                ui.add(
+                    DragValue::new(&mut preferences.grid_spacing)
+                        .speed(0.1)
+                        .clamp_range(0.01..=100.0)
+                        .prefix("Grid Spacing "),
+                );
+
+                ui.label(format!("Window scale factor: {}", scale_factor));
+
+                if ui.button("Reset").clicked() {
+                    preferences = Preferences::default();
+                }
+            });
+
+        if preferences != self.preferences {
+            self.preferences = preferences;
+            self.preferences.save();
+            self.ctx.set_style(preferences.style());
+        }
     }
 
     pub fn texture(&self, instance: &Instance) -> Arc<wgpu::TextureView> {
@@ -156,11 +1637,10 @@ impl Editor {
                 dimension: wgpu::TextureDimension::D2,
                 usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
             },
+            // premultiplied srgb, matching the linear premultiplied vertex colors
             &texture
-                .pixels
-                .iter()
-                .map(|a| vec![255u8, 255, 255, *a])
-                .flatten()
+                .srgba_pixels()
+                .flat_map(|color| color.to_array())
                 .collect::<Vec<_>>(),
         );
 
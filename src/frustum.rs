@@ -0,0 +1,153 @@
+use crate::mesh::{Aabb, BoundingSphere};
+use glam::*;
+
+// the six planes of a view projection as (normal, distance), normals point inwards and are
+// unit length, so plane distances are world space distances
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    // `None` for matrices without a usable frustum, e.g. before any camera was set
+    pub fn new(view_proj: Mat4) -> Option<Self> {
+        let x = view_proj.row(0);
+        let y = view_proj.row(1);
+        let z = view_proj.row(2);
+        let w = view_proj.row(3);
+
+        // clip space depth goes from 0 to 1, so the near plane is just z
+        let mut planes = [w + x, w - x, w + y, w - y, z, w - z];
+
+        for plane in &mut planes {
+            let length = plane.truncate().length();
+
+            if length <= f32::EPSILON || !length.is_finite() {
+                return None;
+            }
+
+            *plane /= length;
+        }
+
+        Some(Self { planes })
+    }
+
+    #[inline]
+    fn distance(plane: Vec4, point: Vec3) -> f32 {
+        plane.truncate().dot(point) + plane.w
+    }
+
+    // true if any part of the box may be inside, boxes near a corner of the frustum can pass
+    // without being inside
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // the corner furthest along the plane normal
+            let corner = Vec3::select(plane.truncate().cmpge(Vec3::ZERO), aabb.max, aabb.min);
+
+            Self::distance(*plane, corner) >= 0.0
+        })
+    }
+
+    // true if a mesh with local `aabb` and `sphere` drawn with `matrix` may be visible, the
+    // sphere is tested first and the transformed box only for spheres crossing a plane
+    pub fn is_visible(&self, aabb: &Aabb, sphere: &BoundingSphere, matrix: Mat4) -> bool {
+        let sphere = sphere.transform(matrix);
+
+        let mut crossing = false;
+
+        for plane in &self.planes {
+            let distance = Self::distance(*plane, sphere.center);
+
+            if distance < -sphere.radius {
+                return false;
+            }
+
+            crossing |= distance < sphere.radius;
+        }
+
+        !crossing || self.intersects_aabb(&aabb.transform(matrix))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sphere_pre_test_never_culls_visible_boxes() {
+        let view = Mat4::look_at_rh(Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO, Vec3::Y);
+        let proj = Mat4::perspective_rh(1.0, 1.5, 0.1, 50.0);
+        let frustum = Frustum::new(proj * view).unwrap();
+
+        // a unit cube with the sphere through its corners
+        let aabb = Aabb {
+            min: Vec3::splat(-0.5),
+            max: Vec3::splat(0.5),
+        };
+        let sphere = BoundingSphere {
+            center: Vec3::ZERO,
+            radius: 3f32.sqrt() / 2.0,
+        };
+
+        let scales = [
+            Vec3::ONE,
+            Vec3::new(6.0, 0.2, 1.0),
+            Vec3::new(0.1, 0.1, 12.0),
+        ];
+        let rotations = [
+            Quat::IDENTITY,
+            Quat::from_rotation_y(0.7) * Quat::from_rotation_x(0.3),
+        ];
+
+        // sweeps across every side of the frustum, so boxes straddle, touch and clear each plane
+        let mut visible = 0;
+        let mut culled = 0;
+
+        for x in (-30..=30).step_by(2) {
+            for y in (-30..=30).step_by(2) {
+                for z in (-70..=15).step_by(5) {
+                    let position = Vec3::new(x as f32, y as f32, z as f32) * 0.5;
+
+                    for &scale in &scales {
+                        for &rotation in &rotations {
+                            let matrix =
+                                Mat4::from_scale_rotation_translation(scale, rotation, position);
+
+                            if frustum.is_visible(&aabb, &sphere, matrix) {
+                                visible += 1;
+
+                                // the box is its own bounds when it isn't rotated
+                                if rotation == Quat::IDENTITY {
+                                    assert!(frustum.intersects_aabb(&aabb.transform(matrix)));
+                                }
+
+                                continue;
+                            }
+
+                            culled += 1;
+
+                            // culled boxes are entirely behind one of the planes
+                            let corners = (0..8).map(|i| {
+                                let corner = Vec3::select(
+                                    BVec3::new(i & 1 != 0, i & 2 != 0, i & 4 != 0),
+                                    aabb.max,
+                                    aabb.min,
+                                );
+
+                                matrix.transform_point3(corner)
+                            });
+
+                            assert!(frustum.planes.iter().any(|plane| {
+                                corners
+                                    .clone()
+                                    .all(|corner| Frustum::distance(*plane, corner) < 0.0)
+                            }));
+                        }
+                    }
+                }
+            }
+        }
+
+        assert!(visible > 1000 && culled > 1000);
+    }
+}
@@ -0,0 +1,278 @@
+use crate::{node::NodeId, transform::Transform, world::World};
+use egui::{
+    Align2, Color32, CtxRef, Event, Key, LayerId, PointerButton, Pos2, Rect, Stroke, TextStyle,
+    Vec2,
+};
+use glam::{Quat, Vec3};
+
+// length of the axis handles and how close the pointer has to be to grab one, in points
+const HANDLE_LENGTH: f32 = 80.0;
+const GRAB_DISTANCE: f32 = 8.0;
+
+// radians and scale change per point dragged
+const ROTATE_SPEED: f32 = 0.01;
+const SCALE_SPEED: f32 = 0.01;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GizmoMode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+impl GizmoMode {
+    #[inline]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Translate => "Translate",
+            Self::Rotate => "Rotate",
+            Self::Scale => "Scale",
+        }
+    }
+}
+
+// an axis of the gizmo as seen on screen
+struct Handle {
+    // world space, along the node's own axes when scaling
+    axis: Vec3,
+    end: Pos2,
+    // unit screen direction of the axis
+    direction: Vec2,
+    // points moved on screen per world unit moved along the axis
+    points_per_unit: f32,
+}
+
+pub struct Gizmo {
+    pub mode: GizmoMode,
+    // index of the axis being dragged
+    active: Option<usize>,
+}
+
+impl Gizmo {
+    pub fn new() -> Self {
+        Self {
+            mode: GizmoMode::Translate,
+            active: None,
+        }
+    }
+
+    #[inline]
+    pub fn is_dragging(&self) -> bool {
+        self.active.is_some()
+    }
+
+    // w, e and r switch between translating, rotating and scaling
+    pub fn shortcuts(&mut self, ctx: &CtxRef) {
+        let input = ctx.input();
+
+        if ctx.wants_keyboard_input() || input.modifiers.ctrl || self.is_dragging() {
+            return;
+        }
+
+        if input.key_pressed(Key::W) {
+            self.mode = GizmoMode::Translate;
+        } else if input.key_pressed(Key::E) {
+            self.mode = GizmoMode::Rotate;
+        } else if input.key_pressed(Key::R) {
+            self.mode = GizmoMode::Scale;
+        }
+    }
+
+    // draws the handles of `id` over the scene and applies drags on them to its transform,
+    // `screen_size` is in pixels, like `World::world_to_screen`
+    pub fn ui(
+        &mut self,
+        ctx: &CtxRef,
+        world: &mut World,
+        id: NodeId,
+        screen_size: glam::Vec2,
+        pixels_per_point: f32,
+    ) {
+        let node = match world.node(&id) {
+            Some(node) => node,
+            None => {
+                self.active = None;
+                return;
+            }
+        };
+
+        // composed from the local transforms, so it follows drags made since the last update
+        let parent = world.parent_transform(&id).matrix();
+        let global = parent * node.transform.matrix();
+
+        // edits are made in the parent's space, so it has to be invertible
+        if !parent.is_finite() || parent.determinant().abs() <= f32::EPSILON {
+            self.active = None;
+            return;
+        }
+
+        let origin = global.transform_point3(Vec3::ZERO);
+        let rotation = Transform::from_matrix(global).rotation;
+
+        let to_points = |point: Vec3| {
+            world
+                .world_to_screen(point, screen_size)
+                .map(|pixel| Pos2::new(pixel.x / pixels_per_point, pixel.y / pixels_per_point))
+        };
+
+        let center = match to_points(origin) {
+            Some(center) => center,
+            None => {
+                self.active = None;
+                return;
+            }
+        };
+
+        // a short step along each axis gives its direction and scale on screen
+        let step = 0.01;
+        let handles = [Vec3::X, Vec3::Y, Vec3::Z]
+            .iter()
+            .map(|&axis| {
+                let axis = match self.mode {
+                    GizmoMode::Scale => rotation * axis,
+                    _ => axis,
+                };
+
+                let offset = to_points(origin + axis * step)? - center;
+                let length = offset.length();
+
+                // axes pointing at the camera can't be dragged along
+                if length <= f32::EPSILON {
+                    return None;
+                }
+
+                let direction = offset / length;
+
+                Some(Handle {
+                    axis,
+                    end: center + direction * HANDLE_LENGTH,
+                    direction,
+                    points_per_unit: length / step,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let input = ctx.input();
+
+        if !input.pointer.button_down(PointerButton::Primary) {
+            self.active = None;
+        }
+
+        let pressed = input.events.iter().any(|event| {
+            matches!(
+                event,
+                Event::PointerButton {
+                    button: PointerButton::Primary,
+                    pressed: true,
+                    ..
+                }
+            )
+        });
+
+        if pressed && !self.is_dragging() && !ctx.is_pointer_over_area() {
+            if let Some(pointer) = input.pointer.interact_pos() {
+                self.active = handles
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, handle)| {
+                        let handle = handle.as_ref()?;
+                        let distance = segment_distance(pointer, center, handle.end);
+
+                        (distance < GRAB_DISTANCE).then_some((i, distance))
+                    })
+                    .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                    .map(|(i, _)| i);
+            }
+        }
+
+        let delta = input.pointer.delta();
+
+        let active = self.active.and_then(|i| Some((i, handles[i].as_ref()?)));
+
+        if let (Some((i, handle)), true) = (active, delta != Vec2::ZERO) {
+            let node = world.node_mut(&id).unwrap();
+
+            match self.mode {
+                GizmoMode::Translate => {
+                    let distance = dot(delta, handle.direction) / handle.points_per_unit;
+                    let world_delta = handle.axis * distance;
+
+                    node.transform.translation += parent.inverse().transform_vector3(world_delta);
+                }
+                // dragging across the handle turns around it
+                GizmoMode::Rotate => {
+                    let across = Vec2::new(-handle.direction.y, handle.direction.x);
+                    let angle = dot(delta, across) * ROTATE_SPEED;
+
+                    let parent_rotation = Transform::from_matrix(parent).rotation;
+                    let rotated = Quat::from_axis_angle(handle.axis, angle) * rotation;
+
+                    node.transform.rotation = (parent_rotation.inverse() * rotated).normalize();
+                }
+                GizmoMode::Scale => {
+                    let factor: f32 = 1.0 + dot(delta, handle.direction) * SCALE_SPEED;
+                    let scale = &mut node.transform.scale[i];
+
+                    *scale = (*scale * factor.max(0.0)).max(0.001);
+                }
+            }
+        }
+
+        let painter = ctx.layer_painter(LayerId::background());
+
+        let colors = [
+            Color32::from_rgb(230, 70, 70),
+            Color32::from_rgb(70, 200, 70),
+            Color32::from_rgb(70, 110, 230),
+        ];
+
+        for (i, handle) in handles.iter().enumerate() {
+            let handle = match handle {
+                Some(handle) => handle,
+                None => continue,
+            };
+
+            let color = if self.active == Some(i) {
+                Color32::YELLOW
+            } else {
+                colors[i]
+            };
+
+            painter.line_segment([center, handle.end], Stroke::new(2.0, color));
+
+            match self.mode {
+                GizmoMode::Translate => painter.circle_filled(handle.end, 5.0, color),
+                GizmoMode::Rotate => {
+                    painter.circle_stroke(handle.end, 6.0, Stroke::new(2.0, color))
+                }
+                GizmoMode::Scale => painter.rect_filled(
+                    Rect::from_center_size(handle.end, Vec2::splat(9.0)),
+                    0.0,
+                    color,
+                ),
+            }
+        }
+
+        painter.text(
+            center + Vec2::new(8.0, 8.0),
+            Align2::LEFT_TOP,
+            self.mode.name(),
+            TextStyle::Small,
+            Color32::WHITE,
+        );
+    }
+}
+
+// distance from `point` to the segment from `a` to `b`
+#[inline]
+fn segment_distance(point: Pos2, a: Pos2, b: Pos2) -> f32 {
+    let ab = b - a;
+    let t = (dot(point - a, ab) / ab.length_sq().max(f32::EPSILON)).clamp(0.0, 1.0);
+
+    point.distance(a + ab * t)
+}
+
+#[inline]
+fn dot(a: Vec2, b: Vec2) -> f32 {
+    a.x * b.x + a.y * b.y
+}
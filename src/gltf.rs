@@ -1,20 +1,37 @@
-use gltf::{buffer::Source, Gltf};
+use crate::{mesh::Vertex, renderer::PbrMaterial};
+use bytemuck::cast_slice;
+use gltf::{
+    buffer::Source,
+    json::{self, validation::Checked::Valid},
+    Gltf,
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 #[derive(Clone, Debug)]
 pub enum GltfError {
     MissingPositions,
     MissingBlob,
+    UnreadableBuffer(PathBuf),
 }
 
 impl std::fmt::Display for GltfError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            Self::UnreadableBuffer(path) => {
+                write!(f, "failed to read buffer file '{}'", path.display())
+            }
+            _ => write!(f, "{:?}", self),
+        }
     }
 }
 
 impl std::error::Error for GltfError {}
 
-pub fn load_buffers(gltf: &Gltf) -> anyhow::Result<Vec<Vec<u8>>> {
+// `base` is the directory of the .gltf file, external buffers are relative to it
+pub fn load_buffers(gltf: &Gltf, base: Option<&Path>) -> anyhow::Result<Vec<Vec<u8>>> {
     let mut buffer_data = Vec::new();
 
     for buffer in gltf.buffers() {
@@ -22,7 +39,12 @@ pub fn load_buffers(gltf: &Gltf) -> anyhow::Result<Vec<Vec<u8>>> {
             Source::Uri(uri) => {
                 let buffer_bytes = match DataUri::parse(uri) {
                     Ok(data_uri) => data_uri.decode()?,
-                    Err(()) => return Err(GltfError::MissingBlob.into()),
+                    Err(()) => {
+                        let base = base.ok_or(GltfError::MissingBlob)?;
+                        let path = base.join(percent_decode(uri));
+
+                        std::fs::read(&path).map_err(|_| GltfError::UnreadableBuffer(path))?
+                    }
                 };
 
                 buffer_data.push(buffer_bytes);
@@ -73,3 +95,370 @@ impl<'a> DataUri<'a> {
         }
     }
 }
+
+// decodes `%xx` escapes in a uri, invalid escapes are kept as is
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escape = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match escape {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// encodes everything but unreserved characters and path separators as `%xx`
+fn percent_encode(uri: &str) -> String {
+    let mut encoded = String::with_capacity(uri.len());
+
+    for byte in uri.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
+
+// a mesh with its transform baked into the vertices, textures are the files they were loaded
+// from
+pub struct ExportMesh<'a> {
+    pub name: &'a str,
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub material: &'a PbrMaterial,
+    pub albedo: Option<&'a Path>,
+    pub emission: Option<&'a Path>,
+    pub normal: Option<&'a Path>,
+}
+
+// writes `meshes` as one node each to a .gltf at `path` with the geometry embedded, textures
+// are copied next to it
+pub fn export(meshes: &[ExportMesh], path: &Path) -> anyhow::Result<()> {
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    std::fs::create_dir_all(dir)?;
+
+    let mut root = json::Root {
+        asset: json::Asset {
+            generator: Some(String::from("phil-noire")),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let mut buffer = Vec::new();
+    // texture index of every copied image by the file it came from
+    let mut textures: HashMap<PathBuf, u32> = HashMap::new();
+
+    for mesh in meshes {
+        let mut texture = |image: Option<&Path>| -> anyhow::Result<Option<json::Index<_>>> {
+            let image = match image {
+                Some(image) => image,
+                None => return Ok(None),
+            };
+
+            if let Some(index) = textures.get(image) {
+                return Ok(Some(json::Index::new(*index)));
+            }
+
+            let index = root.textures.len() as u32;
+
+            // prefixed with the index, so images with the same name from different folders
+            // don't overwrite each other
+            let file_name = image
+                .file_name()
+                .map(|name| format!("{}_{}", index, name.to_string_lossy()))
+                .ok_or_else(|| anyhow::anyhow!("'{}' isn't a file", image.display()))?;
+
+            std::fs::copy(image, dir.join(&file_name))?;
+
+            root.images.push(json::Image {
+                buffer_view: None,
+                mime_type: None,
+                name: None,
+                uri: Some(percent_encode(&file_name)),
+                extensions: None,
+                extras: Default::default(),
+            });
+            root.textures.push(json::Texture {
+                name: None,
+                sampler: None,
+                source: json::Index::new(index),
+                extensions: None,
+                extras: Default::default(),
+            });
+
+            textures.insert(image.to_path_buf(), index);
+
+            Ok(Some(json::Index::new(index)))
+        };
+
+        let info = |index| json::texture::Info {
+            index,
+            tex_coord: 0,
+            extensions: None,
+            extras: Default::default(),
+        };
+
+        let albedo = texture(mesh.albedo)?;
+        let emission = texture(mesh.emission)?;
+        let normal = texture(mesh.normal)?;
+
+        let emission_factor = (mesh.material.emission * mesh.material.emission_strength)
+            .clamp(glam::Vec3::ZERO, glam::Vec3::ONE);
+
+        let material = json::Index::new(root.materials.len() as u32);
+        root.materials.push(json::Material {
+            name: Some(mesh.name.to_string()),
+            pbr_metallic_roughness: json::material::PbrMetallicRoughness {
+                base_color_factor: json::material::PbrBaseColorFactor(
+                    mesh.material.albedo.extend(1.0).into(),
+                ),
+                base_color_texture: albedo.map(info),
+                metallic_factor: json::material::StrengthFactor(0.0),
+                roughness_factor: json::material::StrengthFactor(mesh.material.roughness),
+                ..Default::default()
+            },
+            normal_texture: normal.map(|index| json::material::NormalTexture {
+                index,
+                scale: 1.0,
+                tex_coord: 0,
+                extensions: None,
+                extras: Default::default(),
+            }),
+            emissive_texture: emission.map(info),
+            emissive_factor: json::material::EmissiveFactor(emission_factor.into()),
+            ..Default::default()
+        });
+
+        // interleaved vertices, then the indices
+        let vertices_offset = buffer.len() as u32;
+        buffer.extend_from_slice(cast_slice(&mesh.vertices));
+        let indices_offset = buffer.len() as u32;
+        buffer.extend_from_slice(cast_slice(&mesh.indices));
+
+        let vertex_view = json::Index::new(root.buffer_views.len() as u32);
+        root.buffer_views.push(json::buffer::View {
+            buffer: json::Index::new(0),
+            byte_length: indices_offset - vertices_offset,
+            byte_offset: Some(vertices_offset),
+            byte_stride: Some(std::mem::size_of::<Vertex>() as u32),
+            name: None,
+            target: Some(Valid(json::buffer::Target::ArrayBuffer)),
+            extensions: None,
+            extras: Default::default(),
+        });
+
+        let index_view = json::Index::new(root.buffer_views.len() as u32);
+        root.buffer_views.push(json::buffer::View {
+            buffer: json::Index::new(0),
+            byte_length: buffer.len() as u32 - indices_offset,
+            byte_offset: Some(indices_offset),
+            byte_stride: None,
+            name: None,
+            target: Some(Valid(json::buffer::Target::ElementArrayBuffer)),
+            extensions: None,
+            extras: Default::default(),
+        });
+
+        let (min, max) = mesh.vertices.iter().fold(
+            (glam::Vec3::splat(f32::MAX), glam::Vec3::splat(f32::MIN)),
+            |(min, max), vertex| (min.min(vertex.position), max.max(vertex.position)),
+        );
+
+        let mut accessor = |view, offset, count, component_type, type_, bounds: Option<_>| {
+            let index = json::Index::new(root.accessors.len() as u32);
+
+            let (min, max) = match bounds {
+                Some((min, max)) => (Some(min), Some(max)),
+                None => (None, None),
+            };
+
+            root.accessors.push(json::Accessor {
+                buffer_view: Some(view),
+                byte_offset: offset,
+                count,
+                component_type: Valid(json::accessor::GenericComponentType(component_type)),
+                extensions: None,
+                extras: Default::default(),
+                type_: Valid(type_),
+                min,
+                max,
+                name: None,
+                normalized: false,
+                sparse: None,
+            });
+
+            index
+        };
+
+        use json::accessor::{ComponentType, Type};
+
+        let count = mesh.vertices.len() as u32;
+
+        // positions need bounds
+        let bounds = (
+            json::Value::from(min.to_array().to_vec()),
+            json::Value::from(max.to_array().to_vec()),
+        );
+
+        let positions = accessor(
+            vertex_view,
+            0,
+            count,
+            ComponentType::F32,
+            Type::Vec3,
+            Some(bounds),
+        );
+        let normals = accessor(vertex_view, 12, count, ComponentType::F32, Type::Vec3, None);
+        let uvs = accessor(vertex_view, 24, count, ComponentType::F32, Type::Vec2, None);
+        let colors = accessor(vertex_view, 32, count, ComponentType::F32, Type::Vec4, None);
+        // gltf tangents can't be missing on some vertices, so partial ones aren't written
+        let tangents = (count > 0 && mesh.vertices.iter().all(|vertex| vertex.tangent.w != 0.0))
+            .then(|| accessor(vertex_view, 48, count, ComponentType::F32, Type::Vec4, None));
+        let indices = accessor(
+            index_view,
+            0,
+            mesh.indices.len() as u32,
+            ComponentType::U32,
+            Type::Scalar,
+            None,
+        );
+
+        let attributes = vec![
+            (json::mesh::Semantic::Positions, positions),
+            (json::mesh::Semantic::Normals, normals),
+            (json::mesh::Semantic::TexCoords(0), uvs),
+            (json::mesh::Semantic::Colors(0), colors),
+        ]
+        .into_iter()
+        .chain(tangents.map(|tangents| (json::mesh::Semantic::Tangents, tangents)))
+        .map(|(semantic, accessor)| (Valid(semantic), accessor))
+        .collect();
+
+        let gltf_mesh = json::Index::new(root.meshes.len() as u32);
+        root.meshes.push(json::Mesh {
+            extensions: None,
+            extras: Default::default(),
+            name: Some(mesh.name.to_string()),
+            primitives: vec![json::mesh::Primitive {
+                attributes,
+                extensions: None,
+                extras: Default::default(),
+                indices: Some(indices),
+                material: Some(material),
+                mode: Valid(json::mesh::Mode::Triangles),
+                targets: None,
+            }],
+            weights: None,
+        });
+
+        root.nodes.push(json::Node {
+            camera: None,
+            children: None,
+            extensions: None,
+            extras: Default::default(),
+            matrix: None,
+            mesh: Some(gltf_mesh),
+            name: Some(mesh.name.to_string()),
+            rotation: None,
+            scale: None,
+            translation: None,
+            skin: None,
+            weights: None,
+        });
+    }
+
+    root.buffers.push(json::Buffer {
+        byte_length: buffer.len() as u32,
+        name: None,
+        uri: Some(format!(
+            "data:application/octet-stream;base64,{}",
+            base64::encode(&buffer)
+        )),
+        extensions: None,
+        extras: Default::default(),
+    });
+
+    root.scenes.push(json::Scene {
+        extensions: None,
+        extras: Default::default(),
+        name: None,
+        nodes: (0..root.nodes.len() as u32).map(json::Index::new).collect(),
+    });
+    root.scene = Some(json::Index::new(0));
+
+    std::fs::write(path, root.to_string_pretty()?)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gltf_reads_sibling_bin_file() {
+        // 'triangle.gltf' refers to its sibling 'triangle data.bin' as 'triangle%20data.bin'
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let gltf = Gltf::open(fixtures.join("triangle.gltf")).unwrap();
+
+        let buffer_data = load_buffers(&gltf, Some(&fixtures)).unwrap();
+
+        assert_eq!(buffer_data.len(), 1);
+        assert_eq!(
+            buffer_data[0],
+            std::fs::read(fixtures.join("triangle data.bin")).unwrap()
+        );
+
+        // a missing .bin names the file it looked for
+        let err = load_buffers(&gltf, fixtures.parent()).unwrap_err();
+
+        match err.downcast_ref::<GltfError>() {
+            Some(GltfError::UnreadableBuffer(missing)) => {
+                assert_eq!(
+                    missing,
+                    &fixtures.parent().unwrap().join("triangle data.bin")
+                )
+            }
+            _ => panic!("expected an unreadable buffer, got {}", err),
+        }
+
+        // without a directory to look in the buffer can't be found
+        assert!(load_buffers(&gltf, None).is_err());
+    }
+
+    #[test]
+    fn percent_escapes_round_trip() {
+        assert_eq!(
+            percent_decode("scene%20data%2Fpart.bin"),
+            "scene data/part.bin"
+        );
+        // broken escapes are kept
+        assert_eq!(percent_decode("100%.bin"), "100%.bin");
+        assert_eq!(percent_decode("a%zz.bin"), "a%zz.bin");
+
+        let name = "wood & stone (1).png";
+        assert_eq!(percent_decode(&percent_encode(name)), name);
+    }
+}
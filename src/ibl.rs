@@ -0,0 +1,205 @@
+use crate::{bindings::BindGroup, instance::Instance, renderer::RenderPipelines};
+use std::{num::NonZeroU32, sync::Arc};
+
+// filtered cube maps are hdr, the sum of a bright sky can go past 1
+pub const ENVIRONMENT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+pub const BRDF_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+
+// irradiance varies slowly with the normal, so a small cube is enough
+pub const IRRADIANCE_SIZE: u32 = 32;
+pub const SPECULAR_SIZE: u32 = 128;
+// matches `SPECULAR_MIPS` in ibl.wgsl and light.wgsl
+pub const SPECULAR_MIPS: u32 = 5;
+pub const BRDF_SIZE: u32 = 256;
+
+// image based lighting, the environment map prefiltered for the light pass
+pub struct Ibl {
+    // cosine weighted environment around each normal, for ambient diffuse
+    pub irradiance: Arc<wgpu::TextureView>,
+    // the environment blurred by the ggx lobe, rougher with every mip, for reflections
+    pub specular: Arc<wgpu::TextureView>,
+    // split sum scale and bias of f0 by n dot v and roughness, independent of the environment
+    pub brdf: Arc<wgpu::TextureView>,
+    // linear across faces and mips
+    pub sampler: Arc<wgpu::Sampler>,
+    irradiance_faces: Vec<wgpu::TextureView>,
+    // six faces per mip, mip by mip
+    specular_faces: Vec<wgpu::TextureView>,
+    environment_bindings: BindGroup,
+    // environment the cubes were filtered from, kept alive so a new one can't reuse its address
+    source: Option<Arc<wgpu::TextureView>>,
+}
+
+impl Ibl {
+    pub fn new(instance: &Instance, pipelines: &RenderPipelines) -> Self {
+        let sampler = instance.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("ibl sampler"),
+            // equirectangular maps wrap around horizontally
+            address_mode_u: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let irradiance = cube_texture(instance, "irradiance", IRRADIANCE_SIZE, 1);
+        let specular = cube_texture(instance, "specular", SPECULAR_SIZE, SPECULAR_MIPS);
+
+        let brdf = instance.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("brdf lut"),
+            size: wgpu::Extent3d {
+                width: BRDF_SIZE,
+                height: BRDF_SIZE,
+                depth_or_array_layers: 1,
+            },
+            format: BRDF_FORMAT,
+            dimension: wgpu::TextureDimension::D2,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+        });
+        let brdf = brdf.create_view(&Default::default());
+
+        // the lut only depends on the brdf, so it's drawn once
+        let mut encoder = instance
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("brdf encoder"),
+            });
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("brdf pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: &brdf,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+
+        render_pass.set_pipeline(&pipelines.brdf);
+        render_pass.draw(0..3, 0..1);
+
+        drop(render_pass);
+
+        instance.queue.submit(std::iter::once(encoder.finish()));
+
+        Self {
+            irradiance: Arc::new(cube_view(&irradiance)),
+            specular: Arc::new(cube_view(&specular)),
+            brdf: Arc::new(brdf),
+            sampler: Arc::new(sampler),
+            irradiance_faces: face_views(&irradiance, 1),
+            specular_faces: face_views(&specular, SPECULAR_MIPS),
+            environment_bindings: BindGroup::new(instance),
+            source: None,
+        }
+    }
+
+    // filters `environment` into the cubes unless they already hold it, the light pass skips
+    // them without an environment
+    pub fn prefilter(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        pipelines: &RenderPipelines,
+        environment: Option<&Arc<wgpu::TextureView>>,
+    ) {
+        let environment = match environment {
+            Some(environment) => environment,
+            None => {
+                self.source = None;
+                return;
+            }
+        };
+
+        if let Some(source) = &self.source {
+            if Arc::ptr_eq(source, environment) {
+                return;
+            }
+        }
+
+        self.environment_bindings.bind_texture(
+            0,
+            environment,
+            wgpu::TextureSampleType::Float { filterable: true },
+        );
+        self.environment_bindings.bind_sampler(1, &self.sampler);
+        let bind_group = self.environment_bindings.generate();
+
+        let passes = std::iter::repeat(&pipelines.irradiance)
+            .zip(&self.irradiance_faces)
+            .chain(std::iter::repeat(&pipelines.prefilter).zip(&self.specular_faces));
+
+        for (i, (pipeline, view)) in passes.enumerate() {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("environment filter pass"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+
+            // the instance tells the shader the face and mip, irradiance has a single mip
+            let instance = if i < self.irradiance_faces.len() {
+                i
+            } else {
+                i - self.irradiance_faces.len()
+            } as u32;
+
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.draw(0..3, instance..instance + 1);
+        }
+
+        self.source = Some(environment.clone());
+    }
+}
+
+fn cube_texture(instance: &Instance, label: &str, size: u32, mips: u32) -> wgpu::Texture {
+    instance.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 6,
+        },
+        format: ENVIRONMENT_FORMAT,
+        dimension: wgpu::TextureDimension::D2,
+        mip_level_count: mips,
+        sample_count: 1,
+        usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+    })
+}
+
+#[inline]
+fn cube_view(texture: &wgpu::Texture) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        dimension: Some(wgpu::TextureViewDimension::Cube),
+        ..Default::default()
+    })
+}
+
+// a view of every face of every mip to render into, ordered like the instances in ibl.wgsl
+fn face_views(texture: &wgpu::Texture, mips: u32) -> Vec<wgpu::TextureView> {
+    (0..mips)
+        .flat_map(|mip| (0..6).map(move |face| (mip, face)))
+        .map(|(mip, face)| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_mip_level: mip,
+                mip_level_count: NonZeroU32::new(1),
+                base_array_layer: face,
+                array_layer_count: NonZeroU32::new(1),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
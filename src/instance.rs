@@ -26,22 +26,31 @@ impl Instance {
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: Some("Rendering Device"),
-                    features: wgpu::Features::default(),
+                    // line polygon mode is only used for wireframe overlays, so it's optional
+                    features: adapter.features() & wgpu::Features::NON_FILL_POLYGON_MODE,
                     limits: wgpu::Limits::default(),
                 },
                 None,
             )
             .await?;
 
+        let format = adapter
+            .get_swap_chain_preferred_format(&surface)
+            .expect("no preferred format");
+
+        // wgpu can't list the formats a surface supports and treats creating a swap chain in an
+        // unsupported one as fatal, so there's no probing for hdr, it's used when it's the
+        // surface's preferred format and sdr otherwise
+        log::debug!("hdr output: {}", format == Swapchain::HDR_FORMAT);
+
         let desc = wgpu::SwapChainDescriptor {
-            format: adapter
-                .get_swap_chain_preferred_format(&surface)
-                .expect("no preferred format"),
+            format,
             present_mode: wgpu::PresentMode::Fifo,
             width,
             height,
             usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
         };
+
         let swap_chain = device.create_swap_chain(&surface, &desc);
 
         let instance = Self {
@@ -57,6 +66,28 @@ impl Instance {
 
         Ok((instance, swap_chain))
     }
+
+    // a device without a window for tests, `None` when there's no adapter to run them on
+    #[cfg(test)]
+    pub async fn headless() -> Option<Instance> {
+        let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: None,
+            })
+            .await?;
+
+        let (device, queue) = adapter
+            .request_device(&Default::default(), None)
+            .await
+            .ok()?;
+
+        Some(Self {
+            device: Arc::new(device),
+            queue: Arc::new(queue),
+        })
+    }
 }
 
 pub struct Swapchain {
@@ -66,6 +97,8 @@ pub struct Swapchain {
 }
 
 impl Swapchain {
+    pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
     #[inline]
     pub fn format(&self) -> wgpu::TextureFormat {
         self.desc.format
@@ -78,6 +111,12 @@ impl Swapchain {
         self.swap_chain = instance.device.create_swap_chain(&self.surface, &self.desc);
     }
 
+    // the size the swap chain was last created with
+    #[inline]
+    pub fn size(&self) -> (u32, u32) {
+        (self.desc.width, self.desc.height)
+    }
+
     #[inline]
     pub fn next_frame(&self) -> Result<wgpu::SwapChainFrame, wgpu::SwapChainError> {
         self.swap_chain.get_current_frame()
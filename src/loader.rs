@@ -0,0 +1,152 @@
+use crate::{instance::Instance, mesh::Mesh, world::Resources};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+pub enum Job {
+    Mesh {
+        path: PathBuf,
+        key: PathBuf,
+        scale: f32,
+    },
+    Image {
+        path: PathBuf,
+        key: PathBuf,
+    },
+}
+
+impl Job {
+    #[inline]
+    pub fn key(&self) -> &PathBuf {
+        match self {
+            Self::Mesh { key, .. } | Self::Image { key, .. } => key,
+        }
+    }
+
+    fn decode(&self, instance: &Instance) -> anyhow::Result<Decoded> {
+        match self {
+            Self::Mesh { path, key, scale } => Ok(Decoded::Meshes(Resources::decode_mesh(
+                instance, path, key, *scale,
+            )?)),
+            Self::Image { path, .. } => Ok(Decoded::Image(Resources::decode_image(path)?)),
+        }
+    }
+}
+
+// cpu side of an asset, its gpu resources are created on the main thread by `Resources`
+pub enum Decoded {
+    Meshes(Vec<(PathBuf, Mesh)>),
+    Image(image::RgbaImage),
+}
+
+// decodes assets on worker threads, in the order they were queued
+pub struct AssetLoader {
+    jobs: mpsc::Sender<Job>,
+    finished: mpsc::Receiver<(PathBuf, anyhow::Result<Decoded>)>,
+    // jobs per key that haven't finished, cancelled keys are removed so workers skip their
+    // jobs and results that were already on the way are dropped
+    pending: Arc<Mutex<HashMap<PathBuf, usize>>>,
+    queued: usize,
+    done: usize,
+}
+
+impl AssetLoader {
+    // workers exit once the loader is dropped
+    pub fn new(instance: &Instance, threads: usize) -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<Job>();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let (finished_sender, finished) = mpsc::channel();
+        let pending = Arc::new(Mutex::new(HashMap::<PathBuf, usize>::new()));
+
+        for i in 0..threads.max(1) {
+            let job_receiver = job_receiver.clone();
+            let finished_sender = finished_sender.clone();
+            let pending = pending.clone();
+            let instance = instance.clone();
+
+            thread::Builder::new()
+                .name(format!("asset loader {}", i))
+                .spawn(move || loop {
+                    // the lock is released before decoding, so workers decode in parallel
+                    let job = match job_receiver.lock().unwrap().recv() {
+                        Ok(job) => job,
+                        Err(_) => break,
+                    };
+
+                    if !pending.lock().unwrap().contains_key(job.key()) {
+                        continue;
+                    }
+
+                    let decoded = job.decode(&instance);
+
+                    if finished_sender.send((job.key().clone(), decoded)).is_err() {
+                        break;
+                    }
+                })
+                .expect("failed to spawn asset loader thread");
+        }
+
+        Self {
+            jobs,
+            finished,
+            pending,
+            queued: 0,
+            done: 0,
+        }
+    }
+
+    #[inline]
+    pub fn queue(&mut self, job: Job) {
+        *self
+            .pending
+            .lock()
+            .unwrap()
+            .entry(job.key().clone())
+            .or_default() += 1;
+
+        self.queued += 1;
+        self.jobs.send(job).expect("asset loader threads exited");
+    }
+
+    // drops the jobs queued for `key`, false if there were none
+    pub fn cancel(&mut self, key: &Path) -> bool {
+        match self.pending.lock().unwrap().remove(key) {
+            Some(jobs) => {
+                self.done += jobs;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // assets decoded since the last call, doesn't block
+    pub fn finished(&mut self) -> Vec<(PathBuf, anyhow::Result<Decoded>)> {
+        let mut pending = self.pending.lock().unwrap();
+
+        let finished = self
+            .finished
+            .try_iter()
+            .filter(|(key, _)| match pending.get_mut(key) {
+                Some(1) => pending.remove(key).is_some(),
+                Some(jobs) => {
+                    *jobs -= 1;
+                    true
+                }
+                None => false,
+            })
+            .collect::<Vec<_>>();
+
+        self.done += finished.len();
+
+        finished
+    }
+
+    // (done, queued) while assets are still decoding
+    #[inline]
+    pub fn progress(&self) -> Option<(usize, usize)> {
+        (self.done < self.queued).then_some((self.done, self.queued))
+    }
+}
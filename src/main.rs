@@ -1,25 +1,41 @@
 mod bindings;
+mod bvh;
 mod camera;
+mod console;
+mod cursor;
 mod differed;
 mod editor;
+mod frustum;
+mod gizmo;
 mod gltf;
+mod ibl;
 mod instance;
+mod loader;
 mod mesh;
 mod node;
+mod pacing;
 mod renderer;
+mod rng;
+mod scatter;
+mod spatial;
+mod spline;
 mod transform;
 mod ui;
 mod ui_pipelines;
 mod world;
 
+use cursor::CursorGrab;
 use editor::Editor;
 use futures::executor::block_on;
 use glam::*;
 use renderer::{Frame, Renderer};
+use std::time::Instant;
 use ui::{UiMesh, UiVertex};
+use wgpu::SwapChainError;
 use winit::{
     event::{
-        ElementState, Event, ModifiersState, MouseButton, StartCause, VirtualKeyCode, WindowEvent,
+        DeviceEvent, ElementState, Event, ModifiersState, MouseButton, StartCause, VirtualKeyCode,
+        WindowEvent,
     },
     event_loop::{ControlFlow, EventLoop},
     window::WindowBuilder,
@@ -43,22 +59,58 @@ fn to_vec(vec: Vec2) -> egui::Vec2 {
 }
 
 #[inline]
-fn to_pos(vec: Vec2) -> egui::Pos2 {
-    egui::Pos2::new(vec.x, vec.y)
+fn has_arg(name: &str) -> bool {
+    std::env::args().skip(1).any(|arg| arg == name)
+}
+
+// value following the last `name` argument
+fn arg_value(name: &str) -> Option<String> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    args.iter()
+        .rposition(|arg| arg == name)
+        .and_then(|i| args.get(i + 1).cloned())
+}
+
+// asset directories from `--assets <dir>` arguments, searched in order
+fn asset_roots() -> Vec<String> {
+    let mut roots = Vec::new();
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        if arg == "--assets" {
+            if let Some(root) = args.next() {
+                roots.push(root);
+            }
+        }
+    }
+
+    if roots.is_empty() {
+        roots.push(String::from("assets"));
+    }
+
+    roots
 }
 
 fn main() -> anyhow::Result<()> {
-    simple_logger::SimpleLogger::new()
-        .with_level(log::LevelFilter::Debug)
-        .with_module_level("wgpu", log::LevelFilter::Warn)
-        .with_module_level("naga", log::LevelFilter::Warn)
-        .with_module_level("gfx", log::LevelFilter::Warn)
-        .init()
-        .unwrap();
+    // logged records also show in the editor's console
+    let log_records = console::init(
+        simple_logger::SimpleLogger::new()
+            .with_level(log::LevelFilter::Debug)
+            .with_module_level("wgpu", log::LevelFilter::Warn)
+            .with_module_level("naga", log::LevelFilter::Warn)
+            .with_module_level("gfx", log::LevelFilter::Warn),
+        log::LevelFilter::Debug,
+    )
+    .unwrap();
 
     let event_loop = EventLoop::new();
+    // `--transparent` lets the desktop show through where nothing is rendered
+    let transparent = has_arg("--transparent");
+
     let window = WindowBuilder::new()
         .with_title("Phil Noire")
+        .with_transparent(transparent)
         .build(&event_loop)
         .expect("failed to create window");
 
@@ -67,63 +119,162 @@ fn main() -> anyhow::Result<()> {
         block_on(instance::Instance::new(&window, size.width, size.height))?;
 
     let mut renderer = Renderer::new(&instance, swap_chain.format(), size.width, size.height);
+    renderer.transparent = transparent;
 
     let mut resources = Resources::new(&&instance);
-    resources.load_assets("assets")?;
 
-    let mut editor = Editor::new();
-    editor.input.screen_rect = Some(egui::Rect::from_min_size(
-        Default::default(),
-        egui::Vec2::new(size.width as f32, size.height as f32),
-    ));
+    // `--import-scale <scale>` for meshes without an `.import` file
+    if let Some(scale) = arg_value("--import-scale") {
+        match scale.parse() {
+            Ok(scale) => resources.import_scale = scale,
+            Err(err) => log::warn!("invalid import scale '{}': {}", scale, err),
+        }
+    }
+
+    // `--loader-threads <n>` decodes assets on n background threads, 0 loads them at startup
+    let loader_threads = match arg_value("--loader-threads").map(|threads| threads.parse()) {
+        Some(Ok(threads)) => threads,
+        Some(Err(err)) => {
+            log::warn!("invalid loader thread count: {}", err);
+            Resources::DEFAULT_LOADER_THREADS
+        }
+        None => Resources::DEFAULT_LOADER_THREADS,
+    };
+
+    resources.set_loader_threads(loader_threads);
+
+    for root in asset_roots() {
+        if let Err(err) = resources.load_assets(&root) {
+            log::warn!("failed to load assets from '{}': {}", root, err);
+        }
+    }
 
-    let loaded_world = "assets/office.world";
-    let mut world = resources.get_world(loaded_world).cloned().unwrap();
+    let mut editor = Editor::new(
+        window.scale_factor() as f32,
+        size.width,
+        size.height,
+        log_records,
+    );
+    editor.present_mode = swap_chain.desc.present_mode;
+
+    // changed by the console's `load` command
+    let mut loaded_world = String::from("assets/office.world");
+    if resources.get_world(&loaded_world).is_none() {
+        log::warn!("'{}' not found, starting with an empty world", loaded_world);
+
+        resources.load_world_from_str(world::DEFAULT_WORLD_KEY, world::DEFAULT_WORLD)?;
+    }
+
+    let mut world = resources
+        .get_world(&loaded_world)
+        .or_else(|| resources.get_world(world::DEFAULT_WORLD_KEY))
+        .cloned()
+        .unwrap();
 
     let mut aspect = size.width as f32 / size.height as f32;
     let mut cursor_position = Vec2::ZERO;
-    let mut egui_texture = None;
+    let mut egui_texture: Option<(u64, _)> = None;
+    let mut last_update = Instant::now();
+    let mut cursor_grab = CursorGrab::new();
 
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Poll;
 
         match event {
             Event::RedrawRequested(_) | Event::NewEvents(StartCause::Poll) => {
-                editor.ctx.begin_frame(editor.input.take());
+                editor.begin_frame();
 
-                if let Some(texture) = &mut egui_texture {
-                } else {
-                    egui_texture = Some(editor.texture(&instance));
+                // the font atlas is rebuilt whenever the ui scale changes
+                let version = editor.ctx.texture().version;
+                if egui_texture.as_ref().map(|(v, _)| *v) != Some(version) {
+                    egui_texture = Some((version, editor.texture(&instance)));
                 }
 
-                editor.ui(&mut world, loaded_world);
+                resources.poll_loader();
+
+                editor.ui(&mut world, &mut resources, &renderer, &mut loaded_world);
+
+                resources.prepare_normal_maps(&world);
 
                 let (output, shapes) = editor.ctx.end_frame();
                 let clipped_meshes = editor.ctx.tessellate(shapes);
 
-                world.update(&resources);
+                let now = Instant::now();
+                let delta_time = (now - last_update).as_secs_f32();
+                last_update = now;
+
+                // applied while paused too, so the editor can look around
+                world.mouse_look(cursor_grab.take_delta());
+
+                if let Some(delta_time) = editor.update_delta_time(delta_time) {
+                    world.update(&resources, delta_time);
+                }
+
+                if editor.frames_in_flight != renderer.frames_in_flight() {
+                    renderer.set_frames_in_flight(&instance, editor.frames_in_flight);
+                }
+
+                renderer.passes = editor.passes;
+
+                // minimized windows have nothing to draw into
+                if editor.screen_size.x < 1.0 || editor.screen_size.y < 1.0 {
+                    return;
+                }
+
+                let frame = match swap_chain.next_frame() {
+                    Ok(frame) => frame,
+                    // the surface changed under us, e.g. after a resize or restoring the window
+                    Err(err @ (SwapChainError::Outdated | SwapChainError::Lost)) => {
+                        log::warn!("{}, recreating the swap chain", err);
+
+                        let (width, height) = swap_chain.size();
+                        swap_chain.resize(&instance, width, height);
+                        return;
+                    }
+                    Err(SwapChainError::Timeout) => return,
+                    Err(err @ SwapChainError::OutOfMemory) => {
+                        log::error!("failed to acquire next frame: {}", err);
+
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+                };
+
+                let mut main_frame = Frame::new();
 
-                let frame = swap_chain
-                    .next_frame()
-                    .expect("failed to acquire next frame");
+                main_frame.aspect = aspect;
+                main_frame.grid = editor.grid();
+                main_frame.scale_factor = editor.scale_factor;
 
-                let mut render_frame = Frame::new();
+                editor.draw_world_bounds(&world, &mut main_frame);
+                editor.draw_selected_splines(&world, &mut main_frame);
 
-                render_frame.aspect = aspect;
+                // the editor overlays go with the main camera, which renders first
+                let mut main_frame = Some(main_frame);
+                let mut render_frames = world.render_cameras(&resources, || {
+                    main_frame.take().unwrap_or_else(|| {
+                        let mut frame = Frame::new();
 
-                world.render(&resources, &mut render_frame);
+                        frame.aspect = aspect;
+                        frame.scale_factor = editor.scale_factor;
+
+                        frame
+                    })
+                });
 
                 let mut ui_meshes = Vec::new();
+                let pixels_per_point = editor.pixels_per_point();
 
-                for egui::ClippedMesh(_, mesh) in clipped_meshes {
+                for egui::ClippedMesh(clip_rect, mesh) in clipped_meshes {
                     let mut vertices = Vec::with_capacity(mesh.vertices.len());
 
                     for vertex in mesh.vertices {
+                        // linear premultiplied, the srgb target encodes it back on write
                         let rgba = egui::Rgba::from(vertex.color);
                         let color = Vec4::new(rgba.r(), rgba.g(), rgba.b(), rgba.a());
 
                         vertices.push(UiVertex {
-                            position: Vec2::new(vertex.pos.x, vertex.pos.y),
+                            position: Vec2::new(vertex.pos.x, vertex.pos.y) * pixels_per_point,
                             uv: Vec2::new(vertex.uv.x, vertex.uv.y),
                             color,
                         });
@@ -132,54 +283,102 @@ fn main() -> anyhow::Result<()> {
                     ui_meshes.push(UiMesh {
                         vertices,
                         indices: mesh.indices,
+                        clip_rect: Vec4::new(
+                            clip_rect.min.x,
+                            clip_rect.min.y,
+                            clip_rect.max.x,
+                            clip_rect.max.y,
+                        ) * pixels_per_point,
+                        layer: UiMesh::EGUI_LAYER,
                     });
                 }
 
+                // drawn last, over every view
+                let ui_frame = render_frames.last_mut().unwrap();
+
                 for mesh in &ui_meshes {
-                    render_frame.render_ui_mesh(mesh, egui_texture.as_ref().unwrap());
+                    ui_frame.render_ui_mesh(mesh, &egui_texture.as_ref().unwrap().1);
                 }
 
-                renderer.render_frame(&instance, &frame.output.view, render_frame);
+                let views = render_frames
+                    .into_iter()
+                    .map(|render_frame| (&frame.output.view, render_frame))
+                    .collect::<Vec<_>>();
+
+                editor.stats = renderer.render_frames(&instance, &views);
+
+                // dropping the frame presents it
+                drop(frame);
+                editor.pacing.presented(Instant::now());
+
+                // the scene again without the editor ui, into an offscreen target
+                if std::mem::take(&mut editor.screenshot_requested) {
+                    let capture_frames = world.render_cameras(&resources, || {
+                        let mut frame = Frame::new();
+
+                        frame.aspect = aspect;
+                        frame.scale_factor = editor.scale_factor;
+
+                        frame
+                    });
+
+                    let path = &editor.screenshot_path;
+
+                    match renderer.capture_frames(&instance, capture_frames) {
+                        Ok(image) => match image.save(path) {
+                            Ok(()) => log::info!("saved screenshot to '{}'", path),
+                            Err(err) => log::error!("failed to save '{}': {}", path, err),
+                        },
+                        Err(err) => log::error!("failed to capture screenshot: {}", err),
+                    }
+                }
             }
             Event::WindowEvent {
                 event,
                 window_id: _,
             } => match event {
                 WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                WindowEvent::Resized(size) => {
+                WindowEvent::Resized(size) if size.width > 0 && size.height > 0 => {
                     aspect = size.width as f32 / size.height as f32;
 
                     swap_chain.resize(&instance, size.width, size.height);
                     renderer.resize(&instance, size.width, size.height);
 
-                    editor.input.screen_rect = Some(egui::Rect::from_min_size(
-                        Default::default(),
-                        egui::Vec2::new(size.width as f32, size.height as f32),
-                    ));
+                    editor.screen_size = egui::Vec2::new(size.width as f32, size.height as f32);
                 }
+                WindowEvent::Resized(_) => editor.screen_size = egui::Vec2::ZERO,
                 WindowEvent::ScaleFactorChanged {
+                    scale_factor,
                     new_inner_size: size,
-                    ..
-                } => {
+                } if size.width > 0 && size.height > 0 => {
                     aspect = size.width as f32 / size.height as f32;
 
                     swap_chain.resize(&instance, size.width, size.height);
                     renderer.resize(&instance, size.width, size.height);
 
-                    editor.input.screen_rect = Some(egui::Rect::from_min_size(
-                        Default::default(),
-                        egui::Vec2::new(size.width as f32, size.height as f32),
-                    ));
+                    editor.scale_factor = scale_factor as f32;
+                    editor.screen_size = egui::Vec2::new(size.width as f32, size.height as f32);
                 }
                 WindowEvent::CursorMoved { position, .. } => {
+                    editor.pacing.input(Instant::now());
                     cursor_position = Vec2::new(position.x as f32, position.y as f32);
 
-                    editor
-                        .input
-                        .events
-                        .push(egui::Event::PointerMoved(to_pos(cursor_position)));
+                    editor.input.events.push(egui::Event::PointerMoved(
+                        editor.to_points(cursor_position.x, cursor_position.y),
+                    ));
                 }
+                WindowEvent::Focused(false) => cursor_grab.release(&window),
                 WindowEvent::MouseInput { state, button, .. } => {
+                    editor.pacing.input(Instant::now());
+
+                    // holding right mouse enables mouse-look
+                    if button == MouseButton::Right {
+                        match state {
+                            ElementState::Pressed => cursor_grab.grab(&window, cursor_position),
+                            ElementState::Released => cursor_grab.release(&window),
+                        }
+                    }
+
                     let button = match button {
                         MouseButton::Left => Some(egui::PointerButton::Primary),
                         MouseButton::Right => Some(egui::PointerButton::Secondary),
@@ -191,7 +390,7 @@ fn main() -> anyhow::Result<()> {
                         let pressed = state == ElementState::Pressed;
 
                         editor.input.events.push(egui::Event::PointerButton {
-                            pos: to_pos(cursor_position),
+                            pos: editor.to_points(cursor_position.x, cursor_position.y),
                             button,
                             pressed,
                             modifiers: editor.input.modifiers,
@@ -199,7 +398,13 @@ fn main() -> anyhow::Result<()> {
                     }
                 }
                 WindowEvent::KeyboardInput { input, .. } => {
+                    editor.pacing.input(Instant::now());
+
                     if let Some(keycode) = input.virtual_keycode {
+                        if keycode == VirtualKeyCode::Escape {
+                            cursor_grab.release(&window);
+                        }
+
                         let key = match keycode {
                             VirtualKeyCode::Escape => Some(egui::Key::Escape),
                             VirtualKeyCode::Tab => Some(egui::Key::Tab),
@@ -276,6 +481,21 @@ fn main() -> anyhow::Result<()> {
                 }
                 _ => {}
             },
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
+                ..
+            } => {
+                editor.pacing.input(Instant::now());
+                cursor_grab.mouse_motion(delta);
+            }
+            // winit exits the process after this without dropping the closure
+            Event::LoopDestroyed => {
+                log::info!("shutting down");
+
+                instance.device.poll(wgpu::Maintain::Wait);
+                resources.shutdown();
+                instance.device.poll(wgpu::Maintain::Wait);
+            }
             _ => {}
         }
     });
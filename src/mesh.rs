@@ -1,18 +1,31 @@
-use crate::{
-    gltf::{load_buffers, GltfError},
-    instance::Instance,
-};
+use crate::{bvh::Bvh, gltf::GltfError, instance::Instance};
 use bytemuck::{cast_slice, Pod, Zeroable};
 use glam::*;
-use gltf::Gltf;
+use gltf::{mesh::util::ReadColors, Gltf};
+use std::{collections::HashMap, sync::OnceLock};
 use wgpu::util::DeviceExt;
 
 #[repr(C)]
-#[derive(Default, Clone, Copy, Zeroable, Pod)]
+#[derive(Clone, Copy, Zeroable, Pod)]
 pub struct Vertex {
     pub position: Vec3,
     pub normal: Vec3,
     pub uv: Vec2,
+    pub color: Vec4,
+    // along +u, w is the handedness of the bitangent, zero when the vertex has no tangent
+    pub tangent: Vec4,
+}
+
+impl Default for Vertex {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            normal: Vec3::ZERO,
+            uv: Vec2::ZERO,
+            color: Vec4::ONE,
+            tangent: Vec4::ZERO,
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -29,6 +42,433 @@ impl Vertex {
             ..Default::default()
         }
     }
+
+    #[inline]
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            position: self.position.lerp(other.position, t),
+            normal: self.normal.lerp(other.normal, t).normalize_or_zero(),
+            uv: self.uv.lerp(other.uv, t),
+            color: self.color.lerp(other.color, t),
+            tangent: self
+                .tangent
+                .truncate()
+                .lerp(other.tangent.truncate(), t)
+                .normalize_or_zero()
+                .extend(self.tangent.w),
+        }
+    }
+}
+
+// appends the primitives of a gltf mesh, `buffer_data` is from `gltf::load_buffers`
+fn read_gltf_mesh(
+    mesh: &gltf::Mesh,
+    buffer_data: &[Vec<u8>],
+    mesh_vertices: &mut Vec<Vertex>,
+    mesh_indices: &mut Vec<u32>,
+) -> anyhow::Result<()> {
+    for primitive in mesh.primitives() {
+        let reader = primitive.reader(|buffer| Some(&buffer_data[buffer.index()]));
+
+        let positions = reader
+            .read_positions()
+            .ok_or_else(|| GltfError::MissingPositions)?
+            .map(|v| v.into())
+            .collect::<Vec<Vec3>>();
+
+        let normals = reader
+            .read_normals()
+            .ok_or_else(|| GltfError::MissingPositions)?
+            .map(|v| v.into())
+            .collect::<Vec<Vec3>>();
+
+        let uvs = reader
+            .read_tex_coords(0)
+            .ok_or_else(|| GltfError::MissingPositions)?
+            .into_f32()
+            .map(|v| v.into())
+            .collect::<Vec<Vec2>>();
+
+        // gltf divides 8 bit colors by 32767 when converting them, so those are done here
+        let colors = reader.read_colors(0).map(|colors| match colors {
+            ReadColors::RgbU8(colors) => colors
+                .map(|[r, g, b]| Vec4::new(r as f32, g as f32, b as f32, 255.0) / 255.0)
+                .collect::<Vec<_>>(),
+            ReadColors::RgbaU8(colors) => colors
+                .map(|[r, g, b, a]| Vec4::new(r as f32, g as f32, b as f32, a as f32) / 255.0)
+                .collect(),
+            colors => colors.into_rgba_f32().map(|v| v.into()).collect(),
+        });
+
+        let tangents = reader
+            .read_tangents()
+            .map(|tangents| tangents.map(|v| v.into()).collect::<Vec<Vec4>>());
+
+        let indices = reader
+            .read_indices()
+            .map(|indices| indices.into_u32().collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        let mut vertices: Vec<Vertex> = vec![Default::default(); positions.len()];
+
+        for i in 0..vertices.len() {
+            let vertex = &mut vertices[i];
+
+            vertex.position = positions[i];
+            vertex.normal = normals[i];
+            vertex.uv = uvs[i];
+
+            if let Some(ref colors) = colors {
+                vertex.color = colors[i];
+            }
+
+            if let Some(ref tangents) = tangents {
+                vertex.tangent = tangents[i];
+            }
+        }
+
+        if tangents.is_none() {
+            compute_tangents(&mut vertices, &indices);
+        }
+
+        let offset = mesh_vertices.len() as u32;
+        mesh_indices.extend(indices.into_iter().map(|i| i + offset));
+        mesh_vertices.append(&mut vertices);
+    }
+
+    Ok(())
+}
+
+// counter-clockwise triangles face the viewer, like gltf
+#[inline]
+fn face_normal(a: Vec3, b: Vec3, c: Vec3) -> Vec3 {
+    (b - a).cross(c - a).normalize()
+}
+
+// per vertex normals averaged from the faces around each vertex
+fn compute_normals(vertices: &mut [Vertex], indices: &[u32]) {
+    for vertex in vertices.iter_mut() {
+        vertex.normal = Vec3::ZERO;
+    }
+
+    for triangle in indices.chunks_exact(3) {
+        let [i0, i1, i2] = [
+            triangle[0] as usize,
+            triangle[1] as usize,
+            triangle[2] as usize,
+        ];
+
+        let normal = face_normal(
+            vertices[i0].position,
+            vertices[i1].position,
+            vertices[i2].position,
+        );
+
+        vertices[i0].normal += normal;
+        vertices[i1].normal += normal;
+        vertices[i2].normal += normal;
+    }
+
+    for vertex in vertices.iter_mut() {
+        vertex.normal = vertex.normal.normalize();
+    }
+}
+
+// merges vertices whose position, normal, uv, color and tangent all lie within `epsilon` of each
+// other and remaps `indices` to them, triangles collapsed by the merge are dropped
+fn weld(input: &[Vertex], indices: &[u32], epsilon: f32) -> (Vec<Vertex>, Vec<u32>) {
+    let epsilon = epsilon.max(f32::EPSILON);
+
+    let close = |a: &Vertex, b: &Vertex| {
+        a.position.abs_diff_eq(b.position, epsilon)
+            && a.normal.abs_diff_eq(b.normal, epsilon)
+            && a.uv.abs_diff_eq(b.uv, epsilon)
+            && a.color.abs_diff_eq(b.color, epsilon)
+            && a.tangent.abs_diff_eq(b.tangent, epsilon)
+    };
+
+    // welded vertices by the `epsilon` sized cell their position falls in
+    let mut cells: HashMap<IVec3, Vec<u32>> = HashMap::new();
+    let mut vertices: Vec<Vertex> = Vec::with_capacity(input.len());
+    let mut remap = Vec::with_capacity(input.len());
+
+    for vertex in input {
+        let cell = (vertex.position / epsilon).floor().as_i32();
+
+        // a match within `epsilon` is at most one cell away on every axis
+        let mut found = None;
+
+        'search: for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let candidates = match cells.get(&(cell + IVec3::new(x, y, z))) {
+                        Some(candidates) => candidates,
+                        None => continue,
+                    };
+
+                    for &index in candidates {
+                        if close(&vertices[index as usize], vertex) {
+                            found = Some(index);
+                            break 'search;
+                        }
+                    }
+                }
+            }
+        }
+
+        let index = found.unwrap_or_else(|| {
+            vertices.push(*vertex);
+
+            let index = vertices.len() as u32 - 1;
+            cells.entry(cell).or_default().push(index);
+            index
+        });
+
+        remap.push(index);
+    }
+
+    let indices = indices
+        .chunks_exact(3)
+        .map(|triangle| {
+            [
+                remap[triangle[0] as usize],
+                remap[triangle[1] as usize],
+                remap[triangle[2] as usize],
+            ]
+        })
+        .filter(|[i0, i1, i2]| i0 != i1 && i1 != i2 && i2 != i0)
+        .flatten()
+        .collect();
+
+    (vertices, indices)
+}
+
+// swaps the last two corners of every triangle, turning it to face the other way
+fn reverse_winding(indices: &mut [u32]) {
+    for triangle in indices.chunks_exact_mut(3) {
+        triangle.swap(1, 2);
+    }
+}
+
+// indices of every triangle split into four, the midpoints are appended to `vertices` and
+// shared between neighbours
+fn subdivide_triangles(vertices: &mut Vec<Vertex>, indices: &[u32]) -> Vec<u32> {
+    let mut midpoints = HashMap::new();
+    let mut subdivided = Vec::with_capacity(indices.len() * 4);
+
+    let len = vertices.len() as u32;
+
+    for triangle in indices.chunks_exact(3) {
+        let (i0, i1, i2) = (triangle[0], triangle[1], triangle[2]);
+
+        // skip triangles referencing missing vertices or collapsed to a line
+        if i0.max(i1).max(i2) >= len || i0 == i1 || i1 == i2 || i2 == i0 {
+            continue;
+        }
+
+        let mut midpoint = |a: u32, b: u32| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                let vertex = vertices[a as usize].lerp(&vertices[b as usize], 0.5);
+                vertices.push(vertex);
+                vertices.len() as u32 - 1
+            })
+        };
+
+        let m01 = midpoint(i0, i1);
+        let m12 = midpoint(i1, i2);
+        let m20 = midpoint(i2, i0);
+
+        subdivided.extend_from_slice(&[i0, m01, m20]);
+        subdivided.extend_from_slice(&[m01, i1, m12]);
+        subdivided.extend_from_slice(&[m20, m12, i2]);
+        subdivided.extend_from_slice(&[m01, m12, m20]);
+    }
+
+    subdivided
+}
+
+// per vertex tangents from the uv deltas of the triangles around it, averaged like normals,
+// vertices only touching triangles with no uv area are left without a tangent
+fn compute_tangents(vertices: &mut [Vertex], indices: &[u32]) {
+    let mut tangents = vec![Vec3::ZERO; vertices.len()];
+    let mut bitangents = vec![Vec3::ZERO; vertices.len()];
+
+    for triangle in indices.chunks_exact(3) {
+        let (i0, i1, i2) = (
+            triangle[0] as usize,
+            triangle[1] as usize,
+            triangle[2] as usize,
+        );
+
+        if i0.max(i1).max(i2) >= vertices.len() {
+            continue;
+        }
+
+        let (v0, v1, v2) = (vertices[i0], vertices[i1], vertices[i2]);
+
+        let edge1 = v1.position - v0.position;
+        let edge2 = v2.position - v0.position;
+        let duv1 = v1.uv - v0.uv;
+        let duv2 = v2.uv - v0.uv;
+
+        let det = duv1.x * duv2.y - duv2.x * duv1.y;
+
+        if det.abs() <= f32::EPSILON {
+            continue;
+        }
+
+        let tangent = (edge1 * duv2.y - edge2 * duv1.y) / det;
+        let bitangent = (edge2 * duv1.x - edge1 * duv2.x) / det;
+
+        for &i in &[i0, i1, i2] {
+            tangents[i] += tangent;
+            bitangents[i] += bitangent;
+        }
+    }
+
+    for (vertex, (tangent, bitangent)) in vertices
+        .iter_mut()
+        .zip(tangents.into_iter().zip(bitangents))
+    {
+        let normal = vertex.normal;
+
+        // perpendicular to the normal, so the tangent frame stays orthonormal
+        let tangent = (tangent - normal * normal.dot(tangent)).normalize_or_zero();
+
+        vertex.tangent = if tangent == Vec3::ZERO {
+            Vec4::ZERO
+        } else if normal.cross(tangent).dot(bitangent) < 0.0 {
+            tangent.extend(-1.0)
+        } else {
+            tangent.extend(1.0)
+        };
+    }
+}
+
+// nearest intersection of a ray with a mesh, in the mesh's local space
+#[derive(Clone, Copy, Debug)]
+pub struct RayHit {
+    // distance along the ray in multiples of its direction
+    pub t: f32,
+    pub position: Vec3,
+    // interpolated vertex normal, or the face normal where the vertices have none
+    pub normal: Vec3,
+}
+
+// möller-trumbore, hits both sides, returns (t, u, v) with u and v weighting `b` and `c`
+#[inline]
+pub fn ray_triangle(origin: Vec3, direction: Vec3, a: Vec3, b: Vec3, c: Vec3) -> Option<Vec3> {
+    let ab = b - a;
+    let ac = c - a;
+
+    let p = direction.cross(ac);
+    let det = ab.dot(p);
+
+    // parallel to the triangle's plane
+    if det.abs() < f32::EPSILON {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+    let to_origin = origin - a;
+
+    let u = to_origin.dot(p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = to_origin.cross(ab);
+
+    let v = direction.dot(q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = ac.dot(q) * inv_det;
+
+    (t >= 0.0).then_some(Vec3::new(t, u, v))
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    #[inline]
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    // bounds of the transformed box
+    #[inline]
+    pub fn transform(&self, matrix: Mat4) -> Self {
+        let mut min = Vec3::splat(f32::INFINITY);
+        let mut max = Vec3::splat(f32::NEG_INFINITY);
+
+        for i in 0..8 {
+            let corner = Vec3::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            );
+
+            let corner = matrix.transform_point3(corner);
+
+            min = min.min(corner);
+            max = max.max(corner);
+        }
+
+        Self { min, max }
+    }
+
+    #[inline]
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    // distance from `point` to the furthest corner
+    #[inline]
+    pub fn max_distance(&self, point: Vec3) -> f32 {
+        (point - self.min)
+            .abs()
+            .max((point - self.max).abs())
+            .length()
+    }
+
+    #[inline]
+    pub fn distance_squared(&self, point: Vec3) -> f32 {
+        (point.max(self.min).min(self.max) - point).length_squared()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    // contains the transformed sphere, scaled by the largest axis scale
+    #[inline]
+    pub fn transform(&self, matrix: Mat4) -> Self {
+        let scale = matrix
+            .x_axis
+            .truncate()
+            .length_squared()
+            .max(matrix.y_axis.truncate().length_squared())
+            .max(matrix.z_axis.truncate().length_squared())
+            .sqrt();
+
+        Self {
+            center: matrix.transform_point3(self.center),
+            radius: self.radius * scale,
+        }
+    }
 }
 
 pub struct Mesh {
@@ -37,6 +477,13 @@ pub struct Mesh {
     vertex_buffer: Option<wgpu::Buffer>,
     pub indices: Vec<u32>,
     index_buffer: Option<wgpu::Buffer>,
+    // extra usage added to both buffers, e.g. `STORAGE | COPY_SRC` for compute access
+    usage: wgpu::BufferUsage,
+    // built by the first raycast, cleared along with the buffers when the mesh changes
+    bvh: OnceLock<Bvh>,
+    // box and sphere used for culling and the spatial hash, built on first use and cleared with
+    // the bvh
+    culling_bounds: OnceLock<Option<(Aabb, BoundingSphere)>>,
 }
 
 impl Clone for Mesh {
@@ -47,6 +494,9 @@ impl Clone for Mesh {
             vertex_buffer: None,
             indices: self.indices.clone(),
             index_buffer: None,
+            usage: self.usage,
+            bvh: OnceLock::new(),
+            culling_bounds: OnceLock::new(),
         }
     }
 }
@@ -54,103 +504,293 @@ impl Clone for Mesh {
 impl Mesh {
     #[inline]
     pub fn new(instance: &Instance) -> Self {
+        Self::with_usage(instance, wgpu::BufferUsage::empty())
+    }
+
+    #[inline]
+    pub fn with_usage(instance: &Instance, usage: wgpu::BufferUsage) -> Self {
         Self {
             instance: instance.clone(),
             vertices: Vec::new(),
             vertex_buffer: None,
             indices: Vec::new(),
             index_buffer: None,
+            usage,
+            bvh: OnceLock::new(),
+            culling_bounds: OnceLock::new(),
         }
     }
 
-    pub fn load_gltf(&mut self, gltf: &Gltf) -> anyhow::Result<()> {
-        let buffer_data = load_buffers(gltf)?;
-
+    // every mesh in the file flattened into one, `buffer_data` is from `gltf::load_buffers`
+    pub fn load_gltf(&mut self, gltf: &Gltf, buffer_data: &[Vec<u8>]) -> anyhow::Result<()> {
         self.vertices.clear();
         self.indices.clear();
 
         for mesh in gltf.meshes() {
-            for primitive in mesh.primitives() {
-                let reader = primitive.reader(|buffer| Some(&buffer_data[buffer.index()]));
-
-                let positions = reader
-                    .read_positions()
-                    .ok_or_else(|| GltfError::MissingPositions)?
-                    .map(|v| v.into())
-                    .collect::<Vec<Vec3>>();
-
-                let normals = reader
-                    .read_normals()
-                    .ok_or_else(|| GltfError::MissingPositions)?
-                    .map(|v| v.into())
-                    .collect::<Vec<Vec3>>();
-
-                let uvs = reader
-                    .read_tex_coords(0)
-                    .ok_or_else(|| GltfError::MissingPositions)?
-                    .into_f32()
-                    .map(|v| v.into())
-                    .collect::<Vec<Vec2>>();
-
-                if let Some(indices) = reader.read_indices() {
-                    self.indices.append(
-                        &mut indices
-                            .into_u32()
-                            .map(|i| i + self.vertices.len() as u32)
-                            .collect(),
-                    );
-                }
-
-                let mut vertices: Vec<Vertex> = vec![Default::default(); positions.len()];
-
-                for i in 0..vertices.len() {
-                    let vertex = &mut vertices[i];
-
-                    vertex.position = positions[i];
-                    vertex.normal = normals[i];
-                    vertex.uv = uvs[i];
-                }
-
-                self.vertices.append(&mut vertices);
-            }
+            self.append_gltf_mesh(&mesh, buffer_data)?;
         }
 
         Ok(())
     }
 
+    // adds the primitives of a single mesh in the file
+    pub fn append_gltf_mesh(
+        &mut self,
+        mesh: &gltf::Mesh,
+        buffer_data: &[Vec<u8>],
+    ) -> anyhow::Result<()> {
+        self.invalidate_bvh();
+
+        read_gltf_mesh(mesh, buffer_data, &mut self.vertices, &mut self.indices)
+    }
+
     #[inline]
     pub fn len_indices(&self) -> u32 {
         self.indices.len() as u32
     }
 
-    #[allow(dead_code)]
-    pub fn calculate_normals(&mut self) {
-        for vertex in &mut self.vertices {
-            vertex.normal = Vec3::ZERO;
+    #[inline]
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.culling_bounds().map(|(bounds, _)| bounds)
+    }
+
+    // box around the vertices and a sphere around its center reaching the furthest vertex,
+    // tighter than one around the box corners for most meshes, scanned once and cached until
+    // the vertices change
+    pub fn culling_bounds(&self) -> Option<(Aabb, BoundingSphere)> {
+        *self.culling_bounds.get_or_init(|| {
+            let first = self.vertices.first()?.position;
+
+            let (min, max) = self
+                .vertices
+                .iter()
+                .fold((first, first), |(min, max), vertex| {
+                    (min.min(vertex.position), max.max(vertex.position))
+                });
+
+            let center = (min + max) / 2.0;
+
+            let radius = self
+                .vertices
+                .iter()
+                .map(|vertex| vertex.position.distance_squared(center))
+                .fold(0.0, f32::max)
+                .sqrt();
+
+            Some((Aabb { min, max }, BoundingSphere { center, radius }))
+        })
+    }
+
+    // nearest triangle hit by the ray, `origin` and `direction` are in the mesh's local space,
+    // so callers transform them by the inverse of the node's global matrix
+    pub fn raycast(&self, origin: Vec3, direction: Vec3) -> Option<RayHit> {
+        let bvh = self
+            .bvh
+            .get_or_init(|| Bvh::new(&self.vertices, &self.indices));
+
+        bvh.raycast(origin, direction, |triangle| {
+            let hit = self.raycast_triangle(origin, direction, triangle)?;
+            Some((hit.t, hit))
+        })
+    }
+
+    // the bvh is rebuilt by the next raycast and the culling bounds by the next draw, called by
+    // everything moving vertices
+    #[inline]
+    pub fn invalidate_bvh(&mut self) {
+        self.bvh.take();
+        self.culling_bounds.take();
+    }
+
+    #[inline]
+    fn raycast_triangle(&self, origin: Vec3, direction: Vec3, triangle: usize) -> Option<RayHit> {
+        let indices = &self.indices[triangle * 3..triangle * 3 + 3];
+
+        let a = &self.vertices[indices[0] as usize];
+        let b = &self.vertices[indices[1] as usize];
+        let c = &self.vertices[indices[2] as usize];
+
+        let tuv = ray_triangle(origin, direction, a.position, b.position, c.position)?;
+        let (u, v) = (tuv.y, tuv.z);
+
+        let normal = a.normal * (1.0 - u - v) + b.normal * u + c.normal * v;
+        let normal = if normal.length_squared() > f32::EPSILON {
+            normal.normalize()
+        } else {
+            (b.position - a.position)
+                .cross(c.position - a.position)
+                .normalize_or_zero()
+        };
+
+        Some(RayHit {
+            t: tuv.x,
+            position: origin + direction * tuv.x,
+            normal,
+        })
+    }
+
+    // a quad in the xz plane facing +y, split into `subdivisions` levels of subdivision
+    pub fn quad(instance: &Instance, size: Vec2, subdivisions: u32) -> Self {
+        let mut mesh = Self::new(instance);
+
+        let half = size / 2.0;
+
+        for (x, z) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].iter() {
+            mesh.vertices.push(Vertex {
+                position: Vec3::new(x * half.x, 0.0, z * half.y),
+                normal: Vec3::Y,
+                uv: Vec2::new((x + 1.0) / 2.0, (z + 1.0) / 2.0),
+                ..Default::default()
+            });
+        }
+
+        mesh.indices = vec![0, 2, 1, 0, 3, 2];
+
+        mesh.subdivide(subdivisions);
+
+        mesh
+    }
+
+    // splits every triangle into four, `levels` times, sharing midpoints between neighbours
+    pub fn subdivide(&mut self, levels: u32) {
+        for _ in 0..levels {
+            self.indices = subdivide_triangles(&mut self.vertices, &self.indices);
+        }
+
+        // buffer sizes changed, so they have to be recreated
+        self.regenerate_buffers();
+    }
+
+    // simplified copy made by merging all vertices within each cell of a `resolution`^3 grid
+    // over the bounds, triangles collapsed by the merge are dropped
+    pub fn decimate(&self, resolution: u32) -> Self {
+        let mut mesh = Self::with_usage(&self.instance, self.usage);
+
+        let bounds = match self.bounds() {
+            Some(bounds) => bounds,
+            None => return mesh,
+        };
+
+        let cell_size = bounds.size().max_element().max(f32::EPSILON) / resolution.max(1) as f32;
+
+        // cell to (merged vertex index, number of vertices merged into it)
+        let mut cells: HashMap<IVec3, (u32, f32)> = HashMap::new();
+        let mut remap = Vec::with_capacity(self.vertices.len());
+
+        for vertex in &self.vertices {
+            let cell = ((vertex.position - bounds.min) / cell_size)
+                .floor()
+                .as_i32();
+
+            let vertices = &mut mesh.vertices;
+            let (index, count) = cells.entry(cell).or_insert_with(|| {
+                vertices.push(Vertex {
+                    normal: Vec3::ZERO,
+                    tangent: Vec4::new(0.0, 0.0, 0.0, vertex.tangent.w),
+                    ..*vertex
+                });
+
+                (vertices.len() as u32 - 1, 0.0)
+            });
+
+            // running average of everything merged into the cell
+            *count += 1.0;
+            let merged = &mut mesh.vertices[*index as usize];
+            let t = 1.0 / *count;
+            merged.position = merged.position.lerp(vertex.position, t);
+            merged.uv = merged.uv.lerp(vertex.uv, t);
+            merged.color = merged.color.lerp(vertex.color, t);
+            merged.normal += vertex.normal;
+            merged.tangent += vertex.tangent.truncate().extend(0.0);
+
+            remap.push(*index);
         }
 
-        for i in 0..self.indices.len() / 3 {
-            let i0 = self.indices[i * 3 + 0] as usize;
-            let i1 = self.indices[i * 3 + 1] as usize;
-            let i2 = self.indices[i * 3 + 2] as usize;
+        for vertex in &mut mesh.vertices {
+            vertex.normal = vertex.normal.normalize_or_zero();
 
-            let v0 = self.vertices[i0];
-            let v1 = self.vertices[i1];
-            let v2 = self.vertices[i2];
+            let tangent = vertex.tangent.truncate().normalize_or_zero();
+            vertex.tangent = if tangent == Vec3::ZERO {
+                Vec4::ZERO
+            } else {
+                tangent.extend(vertex.tangent.w)
+            };
+        }
 
-            let normal = (v2.position - v0.position)
-                .cross(v1.position - v0.position)
-                .normalize();
+        for triangle in self.indices.chunks_exact(3) {
+            let i0 = remap[triangle[0] as usize];
+            let i1 = remap[triangle[1] as usize];
+            let i2 = remap[triangle[2] as usize];
 
-            self.vertices[i0].normal += normal;
-            self.vertices[i1].normal += normal;
-            self.vertices[i2].normal += normal;
+            if i0 != i1 && i1 != i2 && i2 != i0 {
+                mesh.indices.extend_from_slice(&[i0, i1, i2]);
+            }
         }
 
+        mesh
+    }
+
+    // merges coincident vertices with `weld`, returns the vertices removed
+    pub fn weld_vertices(&mut self, epsilon: f32) -> usize {
+        let (vertices, indices) = weld(&self.vertices, &self.indices, epsilon);
+        let removed = self.vertices.len() - vertices.len();
+
+        self.vertices = vertices;
+        self.indices = indices;
+        self.regenerate_buffers();
+
+        removed
+    }
+
+    // scales vertex positions, normals are unaffected by uniform scale
+    pub fn scale(&mut self, factor: f32) {
         for vertex in &mut self.vertices {
-            vertex.normal = vertex.normal.normalize();
+            vertex.position *= factor;
         }
 
+        self.invalidate_bvh();
+    }
+
+    pub fn flip_normals(&mut self) {
+        for vertex in &mut self.vertices {
+            vertex.normal = -vertex.normal;
+            // keeps the bitangent, `cross(normal, tangent)`, pointing the same way
+            vertex.tangent.w = -vertex.tangent.w;
+        }
+
+        self.regenerate_buffers();
+    }
+
+    // reverses the winding of every triangle
+    pub fn flip_winding(&mut self) {
+        reverse_winding(&mut self.indices);
+
+        self.regenerate_buffers();
+    }
+
+    // recreates existing buffers from the current vertices and indices
+    #[inline]
+    pub fn regenerate_buffers(&mut self) {
+        self.invalidate_bvh();
+
+        if self.vertex_buffer.is_some() || self.index_buffer.is_some() {
+            self.vertex_buffer = None;
+            self.index_buffer = None;
+            self.generate_buffers();
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn calculate_normals(&mut self) {
+        compute_normals(&mut self.vertices, &self.indices);
+
+        self.write_vertex_buffer();
+    }
+
+    // replaces every tangent with ones generated from the uvs, like `calculate_normals`
+    pub fn calculate_tangents(&mut self) {
+        compute_tangents(&mut self.vertices, &self.indices);
+
         self.write_vertex_buffer();
     }
 
@@ -162,7 +802,7 @@ impl Mesh {
                     .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                         label: Some("vertex buffer"),
                         contents: cast_slice(&self.vertices),
-                        usage: wgpu::BufferUsage::VERTEX,
+                        usage: wgpu::BufferUsage::VERTEX | self.usage,
                     });
 
             self.vertex_buffer = Some(buffer);
@@ -170,6 +810,8 @@ impl Mesh {
     }
 
     pub fn write_vertex_buffer(&mut self) {
+        self.invalidate_bvh();
+
         if let Some(ref buffer) = self.vertex_buffer {
             self.instance
                 .queue
@@ -197,7 +839,7 @@ impl Mesh {
                     .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                         label: Some("index buffer"),
                         contents: cast_slice(&self.indices),
-                        usage: wgpu::BufferUsage::INDEX,
+                        usage: wgpu::BufferUsage::INDEX | self.usage,
                     });
 
             self.index_buffer = Some(buffer);
@@ -212,3 +854,343 @@ impl Mesh {
         Some((self.vertex_buffer.as_ref()?, self.index_buffer.as_ref()?))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counter_clockwise_triangle_faces_z() {
+        assert_eq!(face_normal(Vec3::ZERO, Vec3::X, Vec3::Y), Vec3::Z);
+
+        let mut vertices = [Vec3::ZERO, Vec3::X, Vec3::Y]
+            .iter()
+            .map(|&position| Vertex {
+                position,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+
+        compute_normals(&mut vertices, &[0, 1, 2]);
+
+        for vertex in &vertices {
+            assert!(vertex.normal.abs_diff_eq(Vec3::Z, 1e-6));
+        }
+    }
+
+    #[test]
+    fn flipped_winding_reverses_index_order() {
+        let positions = [Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::new(1.0, 1.0, 0.0)];
+        let mut indices = vec![0, 1, 2, 2, 1, 3];
+
+        reverse_winding(&mut indices);
+
+        assert_eq!(indices, [0, 2, 1, 2, 3, 1]);
+
+        // both triangles now face away from the viewer
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| positions[triangle[i] as usize]);
+            assert!(face_normal(a, b, c).abs_diff_eq(-Vec3::Z, 1e-6));
+        }
+    }
+
+    #[test]
+    fn welding_drops_duplicates_and_keeps_the_shape() {
+        let vertex = |x: f32, y: f32| Vertex {
+            position: Vec3::new(x, y, 0.0),
+            normal: Vec3::Z,
+            uv: Vec2::new(x, y),
+            ..Default::default()
+        };
+
+        // a quad exported as two separate triangles, the seam vertices a hair apart, and a
+        // sliver that welding collapses
+        let vertices = [
+            vertex(0.0, 0.0),
+            vertex(1.0, 0.0),
+            vertex(1.0, 1.0),
+            vertex(0.0, 0.0),
+            vertex(1.0, 1.0 + 1e-5),
+            vertex(0.0, 1.0),
+            vertex(0.5, 0.5),
+            vertex(0.5 + 1e-5, 0.5),
+            vertex(1.0, 1.0),
+        ];
+        let indices = (0..9).collect::<Vec<u32>>();
+
+        let triangles = |vertices: &[Vertex], indices: &[u32]| {
+            indices
+                .chunks_exact(3)
+                .map(|triangle| [0, 1, 2].map(|i| vertices[triangle[i] as usize].position.round()))
+                .collect::<Vec<_>>()
+        };
+
+        let (welded, welded_indices) = weld(&vertices, &indices, 1e-4);
+
+        assert_eq!(welded.len(), 5);
+        assert_eq!(welded_indices, [0, 1, 2, 0, 2, 3]);
+        assert_eq!(
+            triangles(&welded, &welded_indices),
+            triangles(&vertices, &indices[..6])
+        );
+
+        // vertices that only share a position stay apart
+        let mut seam = vertices;
+        seam[3].uv = Vec2::new(0.25, 0.0);
+        assert_eq!(weld(&seam, &indices, 1e-4).0.len(), 6);
+    }
+
+    #[test]
+    fn quad_tangents_follow_u() {
+        // a quad standing in the xy plane with u along +x and v running down it like gltf, which
+        // flips the handedness
+        let mut vertices = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]
+            .iter()
+            .map(|&(x, y)| Vertex {
+                position: Vec3::new(x, y, 0.0),
+                normal: Vec3::Z,
+                uv: Vec2::new(x, 1.0 - y),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let indices = [0, 1, 2, 0, 2, 3];
+
+        compute_tangents(&mut vertices, &indices);
+
+        for vertex in &vertices {
+            assert!(vertex
+                .tangent
+                .abs_diff_eq(Vec4::new(1.0, 0.0, 0.0, -1.0), 1e-6));
+        }
+
+        // the same uv on every corner gives no tangent rather than nan
+        for vertex in &mut vertices {
+            vertex.uv = Vec2::ZERO;
+        }
+
+        compute_tangents(&mut vertices, &indices);
+
+        for vertex in &vertices {
+            assert_eq!(vertex.tangent, Vec4::ZERO);
+        }
+    }
+
+    #[test]
+    fn subdivided_triangle_becomes_four() {
+        let mut vertices = [Vec3::ZERO, Vec3::X, Vec3::Y]
+            .iter()
+            .map(|&position| Vertex {
+                position,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+
+        let indices = subdivide_triangles(&mut vertices, &[0, 1, 2]);
+
+        assert_eq!(indices.len(), 12);
+        assert_eq!(vertices.len(), 6);
+
+        let midpoints = vertices[3..]
+            .iter()
+            .map(|vertex| vertex.position)
+            .collect::<Vec<_>>();
+
+        for midpoint in [Vec3::X / 2.0, (Vec3::X + Vec3::Y) / 2.0, Vec3::Y / 2.0].iter() {
+            assert!(midpoints.contains(midpoint));
+        }
+
+        // collapsed triangles are dropped
+        assert!(subdivide_triangles(&mut vertices, &[0, 0, 1]).is_empty());
+    }
+
+    #[test]
+    fn ray_hits_triangle_at_barycentric_point() {
+        let (a, b, c) = (Vec3::ZERO, Vec3::X, Vec3::Y);
+
+        let origin = Vec3::new(0.25, 0.5, 2.0);
+        let tuv = ray_triangle(origin, -Vec3::Z, a, b, c).unwrap();
+
+        assert!(tuv.abs_diff_eq(Vec3::new(2.0, 0.25, 0.5), 1e-6));
+
+        // the weights give back the hit point
+        let point = a * (1.0 - tuv.y - tuv.z) + b * tuv.y + c * tuv.z;
+        assert!(point.abs_diff_eq(origin - Vec3::Z * tuv.x, 1e-6));
+
+        assert!(ray_triangle(Vec3::new(1.0, 1.0, 2.0), -Vec3::Z, a, b, c).is_none());
+    }
+
+    #[test]
+    fn glb_reads_embedded_buffer() {
+        let path =
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/triangle.glb");
+
+        let gltf = Gltf::open(&path).unwrap();
+        let buffer_data = crate::gltf::load_buffers(&gltf, path.parent()).unwrap();
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+
+        for mesh in gltf.meshes() {
+            read_gltf_mesh(&mesh, &buffer_data, &mut vertices, &mut indices).unwrap();
+        }
+
+        assert_eq!(vertices.len(), 3);
+        assert_eq!(indices, [0, 1, 2]);
+        assert_eq!(vertices[1].position, Vec3::X);
+    }
+
+    #[test]
+    fn gltf_vertex_colors_round_trip() {
+        let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+
+        let read = |name: &str| {
+            let gltf = Gltf::open(fixtures.join(name)).unwrap();
+            let buffer_data = crate::gltf::load_buffers(&gltf, Some(&fixtures)).unwrap();
+
+            let mut vertices = Vec::new();
+            let mut indices = Vec::new();
+
+            for mesh in gltf.meshes() {
+                read_gltf_mesh(&mesh, &buffer_data, &mut vertices, &mut indices).unwrap();
+            }
+
+            vertices
+                .iter()
+                .map(|vertex| vertex.color)
+                .collect::<Vec<_>>()
+        };
+
+        // stored as normalized bytes
+        assert_eq!(
+            read("colored_triangle.gltf"),
+            [
+                Vec4::new(1.0, 0.0, 0.0, 1.0),
+                Vec4::new(0.0, 1.0, 0.0, 1.0),
+                Vec4::new(0.0, 0.0, 1.0, 0.0),
+            ]
+        );
+
+        // meshes without colors are white
+        assert_eq!(read("triangle.glb"), [Vec4::ONE; 3]);
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn compute_pass_reads_and_writes_storage_mesh() {
+        const SHADER: &str = r#"
+            [[block]]
+            struct Vertices {
+                data: [[stride(4)]] array<f32>;
+            };
+
+            [[group(0), binding(0)]]
+            var<storage> vertices: [[access(read_write)]] Vertices;
+
+            // doubles the position of every 16 float vertex
+            [[stage(compute), workgroup_size(1)]]
+            fn main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+                let base = id.x * 16u;
+
+                vertices.data[base] = vertices.data[base] * 2.0;
+                vertices.data[base + 1u] = vertices.data[base + 1u] * 2.0;
+                vertices.data[base + 2u] = vertices.data[base + 2u] * 2.0;
+            }
+        "#;
+
+        let instance = futures::executor::block_on(Instance::headless()).expect("no GPU adapter");
+        let device = &instance.device;
+
+        assert_eq!(std::mem::size_of::<Vertex>(), 16 * 4);
+
+        let mut mesh = Mesh::with_usage(
+            &instance,
+            wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_SRC,
+        );
+        mesh.vertices = [Vec3::ZERO, Vec3::X, Vec3::new(0.0, 1.0, 2.0)]
+            .iter()
+            .map(|&position| Vertex {
+                position,
+                ..Default::default()
+            })
+            .collect();
+        mesh.indices = vec![0, 1, 2];
+
+        let (vertex_buffer, _) = mesh.generate_buffers();
+
+        let module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("double positions shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+            flags: wgpu::ShaderFlags::all(),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: vertex_buffer.as_entire_binding(),
+            }],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("double positions pipeline"),
+            layout: Some(&layout),
+            module: &module,
+            entry_point: "main",
+        });
+
+        let size = (mesh.vertices.len() * std::mem::size_of::<Vertex>()) as u64;
+
+        let read_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("vertex read buffer"),
+            size,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&Default::default());
+
+        let mut pass = encoder.begin_compute_pass(&Default::default());
+        pass.set_pipeline(&pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch(mesh.vertices.len() as u32, 1, 1);
+        drop(pass);
+
+        let (vertex_buffer, _) = mesh.get_buffers().unwrap();
+        encoder.copy_buffer_to_buffer(vertex_buffer, 0, &read_buffer, 0, size);
+
+        instance.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = read_buffer.slice(..);
+        let future = slice.map_async(wgpu::MapMode::Read);
+        device.poll(wgpu::Maintain::Wait);
+        futures::executor::block_on(future).unwrap();
+
+        let vertices = cast_slice::<_, Vertex>(&slice.get_mapped_range()).to_vec();
+
+        for (read, vertex) in vertices.iter().zip(&mesh.vertices) {
+            assert_eq!(read.position, vertex.position * 2.0);
+            assert_eq!(read.normal, vertex.normal);
+        }
+    }
+}
@@ -1,6 +1,11 @@
 use crate::{
-    renderer::{DirectionalLight, Frame, PbrMaterial},
-    transform::Transform,
+    mesh::Aabb,
+    renderer::{
+        DirectionalLight, Frame, ParallaxLayer, PbrMaterial, PointLight, SpotLight, Viewport,
+    },
+    rng::Rng,
+    scatter, spline,
+    transform::{GlobalTransform, Transform},
     world::{Resources, World},
 };
 use egui::*;
@@ -10,27 +15,194 @@ use serde::{Deserialize, Serialize};
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct NodeId(pub u64);
 
+impl NodeId {
+    // the value written to the renderer's id buffer, zero is left for pixels without a node
+    #[inline]
+    pub fn pick_id(self) -> u32 {
+        (self.0 as u32).wrapping_add(1)
+    }
+
+    #[inline]
+    pub fn from_pick_id(id: u32) -> Option<Self> {
+        id.checked_sub(1).map(|id| Self(id as u64))
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Node {
     pub name: String,
     pub transform: Transform,
+    #[serde(default)]
+    pub parent: Option<NodeId>,
+    #[serde(skip)]
+    pub global: GlobalTransform,
     pub components: Vec<Component>,
+    // bumped whenever the node changes, systems caching work per node compare it to skip
+    // unchanged nodes
+    #[serde(skip)]
+    pub version: u64,
 }
 
 impl Node {
     #[inline]
-    pub fn update(&mut self, id: NodeId, resources: &Resources, world: &mut World) {
+    pub fn changed(&mut self) {
+        self.version = self.version.wrapping_add(1);
+    }
+
+    #[inline]
+    pub fn update(&mut self, id: NodeId, world: &mut World) {
         for component in &mut self.components {
-            component.update(id, &mut self.transform, resources, world);
+            component.update(id, &mut self.transform, self.parent, world);
         }
+
+        self.sync_materials();
     }
 
-    #[inline]
-    pub fn render<'a>(&'a mut self, id: NodeId, resources: &'a Resources, frame: &mut Frame<'a>) {
+    // copies what other components drive into the node's mesh materials
+    pub fn sync_materials(&mut self) {
+        let uv_offset = self
+            .components
+            .iter()
+            .find_map(|component| match component {
+                Component::UvScroll { offset, .. } => Some(*offset),
+                _ => None,
+            });
+
+        if let Some(uv_offset) = uv_offset {
+            for component in &mut self.components {
+                if let Component::Mesh { material, .. } = component {
+                    material.uv_offset = uv_offset;
+                }
+            }
+        }
+
+        // tints over the saved albedo rather than replacing it, so saving mid fade keeps it
+        let tint = self
+            .components
+            .iter()
+            .find_map(Component::fade_color)
+            .unwrap_or(Vec4::ONE);
+
         for component in &mut self.components {
-            component.render(id, resources, &self.transform, frame);
+            if let Component::Mesh {
+                material, baked, ..
+            } = component
+            {
+                material.tint = tint;
+
+                if let Some((_, baked_material)) = baked {
+                    baked_material.tint = tint;
+                }
+            }
+        }
+
+        let reflection = self
+            .components
+            .iter()
+            .find_map(|component| match component {
+                Component::MirrorPlane { strength } => Some(strength.clamp(0.0, 1.0)),
+                _ => None,
+            })
+            .unwrap_or(0.0);
+
+        for component in &mut self.components {
+            if let Component::Mesh { material, .. } = component {
+                material.reflection = reflection;
+            }
         }
     }
+
+    #[inline]
+    pub fn render<'a>(&'a self, id: NodeId, resources: &'a Resources, frame: &mut Frame<'a>) {
+        for component in &self.components {
+            component.render(id, resources, &self.global, frame);
+        }
+    }
+}
+
+// texture offset of a parallax layer, in screen heights, for a camera at `camera_position`
+#[inline]
+pub fn parallax_offset(camera_position: Vec3, depth: f32, scroll_offset: Vec2) -> Vec2 {
+    // texture v points down while world y points up
+    let camera = Vec2::new(camera_position.x, -camera_position.y);
+
+    scroll_offset + camera / depth.max(0.01)
+}
+
+// lighting at one time of day, `DAY_NIGHT_KEYS` are interpolated between
+#[derive(Clone, Copy)]
+pub struct SkyKey {
+    pub hour: f32,
+    pub ambient_color: Vec3,
+    pub ambient_strength: f32,
+    pub sun_color: Vec3,
+    pub sun_strength: f32,
+}
+
+impl SkyKey {
+    #[inline]
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            hour: self.hour + (other.hour - self.hour) * t,
+            ambient_color: self.ambient_color.lerp(other.ambient_color, t),
+            ambient_strength: self.ambient_strength
+                + (other.ambient_strength - self.ambient_strength) * t,
+            sun_color: self.sun_color.lerp(other.sun_color, t),
+            sun_strength: self.sun_strength + (other.sun_strength - self.sun_strength) * t,
+        }
+    }
+}
+
+const fn sky_key(hour: f32, ambient: [f32; 4], sun: [f32; 4]) -> SkyKey {
+    SkyKey {
+        hour,
+        ambient_color: const_vec3!([ambient[0], ambient[1], ambient[2]]),
+        ambient_strength: ambient[3],
+        sun_color: const_vec3!([sun[0], sun[1], sun[2]]),
+        sun_strength: sun[3],
+    }
+}
+
+// sorted by hour, colors and strengths as [r, g, b, strength], the sun is below the horizon
+// from 18 to 6
+pub const DAY_NIGHT_KEYS: [SkyKey; 8] = [
+    sky_key(0.0, [0.05, 0.07, 0.15, 0.1], [0.2, 0.25, 0.4, 0.0]),
+    sky_key(5.0, [0.1, 0.1, 0.2, 0.15], [1.0, 0.5, 0.3, 0.0]),
+    sky_key(6.5, [0.6, 0.45, 0.4, 0.3], [1.0, 0.6, 0.35, 0.6]),
+    sky_key(9.0, [0.6, 0.7, 0.9, 0.4], [1.0, 0.95, 0.85, 1.0]),
+    sky_key(12.0, [0.6, 0.75, 1.0, 0.45], [1.0, 1.0, 0.95, 1.0]),
+    sky_key(15.0, [0.6, 0.7, 0.9, 0.4], [1.0, 0.95, 0.85, 1.0]),
+    sky_key(17.5, [0.6, 0.45, 0.4, 0.3], [1.0, 0.6, 0.35, 0.6]),
+    sky_key(19.0, [0.1, 0.1, 0.2, 0.15], [1.0, 0.5, 0.3, 0.0]),
+];
+
+// the sky at `hour`, wrapping from the last key back to the first at midnight
+pub fn day_night_sky(hour: f32) -> SkyKey {
+    let hour = hour.rem_euclid(24.0);
+
+    let next = DAY_NIGHT_KEYS
+        .iter()
+        .position(|key| key.hour > hour)
+        .unwrap_or(0);
+    let prev = (next + DAY_NIGHT_KEYS.len() - 1) % DAY_NIGHT_KEYS.len();
+
+    let (from, to) = (&DAY_NIGHT_KEYS[prev], &DAY_NIGHT_KEYS[next]);
+    let span = (to.hour - from.hour).rem_euclid(24.0);
+    let t = (hour - from.hour).rem_euclid(24.0) / span.max(f32::EPSILON);
+
+    SkyKey {
+        hour,
+        ..from.lerp(to, t)
+    }
+}
+
+// direction the sun shines in at `hour`, rising in +x at 6 and highest at 12, tilted towards
+// +z so shadows at noon aren't straight down
+#[inline]
+pub fn sun_direction(hour: f32) -> Vec3 {
+    let angle = (hour - 6.0) / 24.0 * std::f32::consts::TAU;
+
+    -Vec3::new(angle.cos(), angle.sin(), 0.4).normalize()
 }
 
 pub fn drag3(
@@ -58,6 +230,26 @@ pub fn drag_vec3(ui: &mut egui::Ui, vec3: &mut Vec3) {
     vec3.z = z;
 }
 
+pub fn transform_ui(ui: &mut egui::Ui, transform: &mut Transform) {
+    drag_vec3(ui, &mut transform.translation);
+
+    let (mut y, mut x, mut z) = transform.rotation.to_euler(EulerRot::YXZ);
+
+    x = x / std::f32::consts::PI * 180.0;
+    y = y / std::f32::consts::PI * 180.0;
+    z = z / std::f32::consts::PI * 180.0;
+
+    drag3(ui, &mut x, &mut y, &mut z);
+
+    x = x / 180.0 * std::f32::consts::PI;
+    y = y / 180.0 * std::f32::consts::PI;
+    z = z / 180.0 * std::f32::consts::PI;
+
+    transform.rotation = Quat::from_euler(EulerRot::YXZ, y, x, z);
+
+    drag_vec3(ui, &mut transform.scale);
+}
+
 #[macro_export]
 macro_rules! labled {
     ($ui:ident, $label:literal, $add:expr) => {
@@ -68,39 +260,426 @@ macro_rules! labled {
     };
 }
 
+// far/near is capped so depth precision stays usable when the camera is inside the scene
+const MAX_CLIP_RATIO: f32 = 10_000.0;
+const MIN_NEAR: f32 = 0.01;
+
+// clip planes enclosing `bounds` as seen from `position`
+pub fn auto_clip_planes(bounds: Aabb, position: Vec3) -> (f32, f32) {
+    let far = (bounds.max_distance(position) * 1.01).max(MIN_NEAR * 2.0);
+    let near = bounds
+        .distance_squared(position)
+        .sqrt()
+        .max(far / MAX_CLIP_RATIO)
+        .max(MIN_NEAR);
+
+    (near.min(far * 0.5), far)
+}
+
+// a simplified mesh used from `distance` away from the camera
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Lod {
+    pub mesh: String,
+    pub distance: f32,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum LightKind {
+    Directional {
+        direction: Vec3,
+        // only the first `MAX_SHADOW_MAPS` lights casting shadows get a shadow map
+        #[serde(default = "LightKind::default_casts_shadow")]
+        casts_shadow: bool,
+    },
+    // positioned by the node, lights nothing further than `radius` away
+    Point {
+        radius: f32,
+    },
+    // shines down the node's -z axis like a camera, angles are in radians from the center
+    // of the cone
+    Spot {
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+impl LightKind {
+    // the kinds the editor can switch between, with the values a new light starts with
+    #[inline]
+    pub fn kinds() -> [Self; 3] {
+        [
+            Self::Directional {
+                direction: -Vec3::Y,
+                casts_shadow: true,
+            },
+            Self::Point { radius: 10.0 },
+            Self::Spot {
+                range: 20.0,
+                inner_angle: 20f32.to_radians(),
+                outer_angle: 30f32.to_radians(),
+            },
+        ]
+    }
+
+    #[inline]
+    fn default_casts_shadow() -> bool {
+        true
+    }
+
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Directional { .. } => "Directional",
+            Self::Point { .. } => "Point",
+            Self::Spot { .. } => "Spot",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum Projection {
+    // vertical field of view in degrees
+    Perspective { fov: f32 },
+    // world units seen vertically, the width follows the aspect ratio
+    Orthographic { height: f32 },
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Self::Perspective { fov: 70.0 }
+    }
+}
+
+impl Projection {
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Perspective { .. } => "Perspective",
+            Self::Orthographic { .. } => "Orthographic",
+        }
+    }
+
+    // the projections the editor can switch between, with the values a new camera starts with
+    #[inline]
+    pub fn projections() -> [Self; 2] {
+        [Self::default(), Self::Orthographic { height: 10.0 }]
+    }
+
+    #[inline]
+    pub fn matrix(&self, aspect: f32, near: f32, far: f32) -> Mat4 {
+        match *self {
+            Self::Perspective { fov } => {
+                Mat4::perspective_rh(fov / 180.0 * std::f32::consts::PI, aspect, near, far)
+            }
+            Self::Orthographic { height } => {
+                let half_height = height / 2.0;
+                let half_width = half_height * aspect;
+
+                Mat4::orthographic_rh(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    near,
+                    far,
+                )
+            }
+        }
+    }
+}
+
+// mesh dominates the size, components are few enough per node that boxing isn't worth it
+#[allow(clippy::large_enum_variant)]
 #[derive(Clone, Serialize, Deserialize)]
 pub enum Component {
     Mesh {
         mesh: String,
         albedo: String,
         emission: String,
+        #[serde(default)]
+        normal: String,
         material: PbrMaterial,
+        #[serde(default)]
+        lods: Vec<Lod>,
+        // meshes sharing a batch key and mesh are drawn instanced together by `World::render`
+        #[serde(default)]
+        batch: Option<String>,
+        // draws the mesh edges on top of it, for inspecting its topology
+        #[serde(default)]
+        wireframe: bool,
+        #[serde(default = "Component::default_shadow")]
+        cast_shadow: bool,
+        #[serde(default = "Component::default_shadow")]
+        receive_shadow: bool,
+        // the mesh isn't drawn at all when the camera is further away than this
+        #[serde(default)]
+        max_draw_distance: Option<f32>,
+        // mesh with lighting baked into its vertex colors and the material to draw it with
+        #[serde(skip)]
+        baked: Option<(String, PbrMaterial)>,
     },
+    Light {
+        kind: LightKind,
+        color: Vec3,
+        strength: f32,
+    },
+    // legacy form, replaced by `Light` in `Component::migrate` when a world is loaded
+    #[serde(skip_serializing)]
     DirectionalLight(DirectionalLight),
     Camera {
-        fov: f32,
+        #[serde(default)]
+        projection: Projection,
+        // legacy perspective fov, moved into `projection` by `Component::migrate`
+        #[serde(
+            default,
+            skip_serializing,
+            deserialize_with = "Component::deserialize_legacy_fov"
+        )]
+        fov: Option<f32>,
         near: f32,
         far: f32,
+        #[serde(default)]
+        viewport: Viewport,
+        // computes near and far from the scene bounds instead of using the set values
+        #[serde(default)]
+        auto_clip: bool,
     },
     Player,
     PlayerCamera,
+    Spline {
+        points: Vec<Vec3>,
+        closed: bool,
+    },
+    FollowPath {
+        spline: NodeId,
+        speed: f32,
+        t: f32,
+        orient: bool,
+    },
+    Attach {
+        target: NodeId,
+        offset: Transform,
+    },
+    UvScroll {
+        speed: Vec2,
+        #[serde(skip)]
+        offset: Vec2,
+    },
+    // tints the node's meshes from `from` to `to` over `duration` seconds, alpha fades them out
+    FadeAnim {
+        from: Vec4,
+        to: Vec4,
+        duration: f32,
+        #[serde(rename = "loop")]
+        looping: bool,
+        #[serde(skip)]
+        elapsed: f32,
+    },
+    // reflects the scene across the node's xz plane onto the node's meshes, `strength` blends
+    // the reflection over their albedo, only one mirror per scene is rendered
+    MirrorPlane {
+        strength: f32,
+    },
+    // textured backdrop behind the scene, following the camera's x and y divided by `depth`,
+    // so deeper layers move less, `scroll` moves it on its own in screen heights per second
+    ParallaxLayer {
+        texture: String,
+        depth: f32,
+        scroll: Vec2,
+        #[serde(skip)]
+        offset: Vec2,
+    },
+    // drives the ambient light and the world's sun, `time_of_day` is in hours and a full day
+    // takes `day_length` seconds, zero stops the clock
+    DayNightCycle {
+        time_of_day: f32,
+        day_length: f32,
+    },
+    // text facing the screen at the node's position plus `offset`, drawn at a fixed size so it
+    // reads the same at any distance, hidden behind the camera and kept on screen if clamped
+    Label {
+        text: String,
+        color: Vec3,
+        offset: Vec3,
+        #[serde(default)]
+        clamp_to_screen: bool,
+    },
+    // `count` instances of `mesh` placed inside `area` from `seed`, drawn instanced
+    Scatter {
+        mesh: String,
+        #[serde(default)]
+        albedo: String,
+        #[serde(default)]
+        material: PbrMaterial,
+        count: u32,
+        area: Vec3,
+        seed: u64,
+        // local transforms of the instances and the parameters they were placed with
+        #[serde(skip)]
+        instances: Vec<Mat4>,
+        #[serde(skip)]
+        placed: Option<(u32, Vec3, u64)>,
+    },
 }
 
 impl Component {
+    // a mesh component with default material and no textures
+    #[inline]
+    pub fn mesh(mesh: String) -> Self {
+        Self::Mesh {
+            mesh,
+            albedo: String::new(),
+            emission: String::new(),
+            normal: String::new(),
+            material: PbrMaterial::default(),
+            lods: Vec::new(),
+            batch: None,
+            wireframe: false,
+            cast_shadow: true,
+            receive_shadow: true,
+            max_draw_distance: None,
+            baked: None,
+        }
+    }
+
+    #[inline]
+    fn default_shadow() -> bool {
+        true
+    }
+
+    // old worlds store the fov as a plain number rather than an option
+    fn deserialize_legacy_fov<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<f32>, D::Error> {
+        f32::deserialize(deserializer).map(Some)
+    }
+
     pub const MESH: &'static str = "Mesh";
-    pub const DIRECTIONAL_LIGHT: &'static str = "Directional Light";
+    pub const LIGHT: &'static str = "Light";
     pub const CAMERA: &'static str = "Camera";
     pub const PLAYER: &'static str = "Player";
     pub const PLAYER_CAMERA: &'static str = "Player Camera";
+    pub const SPLINE: &'static str = "Spline";
+    pub const FOLLOW_PATH: &'static str = "Follow Path";
+    pub const ATTACH: &'static str = "Attach";
+    pub const UV_SCROLL: &'static str = "Uv Scroll";
+    pub const SCATTER: &'static str = "Scatter";
+    pub const FADE_ANIM: &'static str = "Fade Anim";
+    pub const MIRROR_PLANE: &'static str = "Mirror Plane";
+    pub const PARALLAX_LAYER: &'static str = "Parallax Layer";
+    pub const DAY_NIGHT_CYCLE: &'static str = "Day Night Cycle";
+    pub const LABEL: &'static str = "Label";
 
     #[inline]
     pub fn name(&self) -> &'static str {
         match self {
             Self::Mesh { .. } => Self::MESH,
-            Self::DirectionalLight { .. } => Self::DIRECTIONAL_LIGHT,
+            Self::Light { .. } => Self::LIGHT,
+            Self::DirectionalLight { .. } => Self::LIGHT,
             Self::Camera { .. } => Self::CAMERA,
             Self::Player { .. } => Self::PLAYER,
             Self::PlayerCamera { .. } => Self::PLAYER_CAMERA,
+            Self::Spline { .. } => Self::SPLINE,
+            Self::FollowPath { .. } => Self::FOLLOW_PATH,
+            Self::Attach { .. } => Self::ATTACH,
+            Self::UvScroll { .. } => Self::UV_SCROLL,
+            Self::Scatter { .. } => Self::SCATTER,
+            Self::FadeAnim { .. } => Self::FADE_ANIM,
+            Self::MirrorPlane { .. } => Self::MIRROR_PLANE,
+            Self::ParallaxLayer { .. } => Self::PARALLAX_LAYER,
+            Self::DayNightCycle { .. } => Self::DAY_NIGHT_CYCLE,
+            Self::Label { .. } => Self::LABEL,
+        }
+    }
+
+    // current color of a fade animation
+    #[inline]
+    pub fn fade_color(&self) -> Option<Vec4> {
+        match self {
+            Self::FadeAnim {
+                from,
+                to,
+                duration,
+                elapsed,
+                ..
+            } => {
+                let t = if *duration > 0.0 {
+                    (*elapsed / *duration).min(1.0)
+                } else {
+                    1.0
+                };
+
+                Some(from.lerp(*to, t))
+            }
+            _ => None,
+        }
+    }
+
+    // places scatter instances again when their parameters changed
+    #[inline]
+    fn place_scatter(&mut self) {
+        if let Self::Scatter {
+            count,
+            area,
+            seed,
+            instances,
+            placed,
+            ..
+        } = self
+        {
+            let params = (*count, *area, *seed);
+
+            if *placed != Some(params) {
+                *instances = scatter::place(*count, *area, *seed);
+                *placed = Some(params);
+            }
+        }
+    }
+
+    #[inline]
+    pub fn migrate(&mut self) {
+        if let Self::DirectionalLight(light) = *self {
+            *self = Self::Light {
+                kind: LightKind::Directional {
+                    direction: light.direction,
+                    casts_shadow: light.casts_shadow,
+                },
+                color: light.color,
+                strength: light.strength,
+            };
+        }
+
+        if let Self::Camera {
+            projection, fov, ..
+        } = self
+        {
+            if let Some(fov) = fov.take() {
+                *projection = Projection::Perspective { fov };
+            }
+        }
+
+        self.sync_shadow_flags();
+    }
+
+    // copies the shadow toggles into the materials they're drawn with, materials are written
+    // right when the toggles change, so the shadow map redrawn for the change sees them
+    fn sync_shadow_flags(&mut self) {
+        if let Self::Mesh {
+            material,
+            cast_shadow,
+            receive_shadow,
+            baked,
+            ..
+        } = self
+        {
+            material.skip_shadow_cast = !*cast_shadow as u32;
+            material.skip_shadow_receive = !*receive_shadow as u32;
+
+            if let Some((_, baked_material)) = baked {
+                baked_material.skip_shadow_cast = material.skip_shadow_cast;
+                baked_material.skip_shadow_receive = material.skip_shadow_receive;
+            }
         }
     }
 
@@ -111,7 +690,15 @@ impl Component {
                 mesh,
                 albedo,
                 emission,
+                normal,
                 material,
+                lods,
+                batch,
+                wireframe,
+                cast_shadow,
+                receive_shadow,
+                max_draw_distance,
+                ..
             } => {
                 ui.text_edit_singleline(mesh);
 
@@ -119,7 +706,7 @@ impl Component {
                 labled!(ui, "albedo", ui.color_edit_button_rgb(&mut albedo_color));
                 material.albedo = albedo_color.into();
 
-                ui.text_edit_singleline(albedo);
+                labled!(ui, "albedo texture", ui.text_edit_singleline(albedo));
 
                 let mut emission_color = material.emission.into();
                 labled!(
@@ -129,26 +716,418 @@ impl Component {
                 );
                 material.emission = emission_color.into();
 
-                ui.text_edit_singleline(emission);
+                labled!(
+                    ui,
+                    "emission strength",
+                    ui.add(DragValue::new(&mut material.emission_strength).speed(0.1))
+                );
+
+                labled!(ui, "emission texture", ui.text_edit_singleline(emission));
+
+                labled!(ui, "normal texture", ui.text_edit_singleline(normal));
 
                 ui.add(Slider::new(&mut material.specular_bloom, 0.0..=1.0).text("specular bloom"));
+
+                ui.add(Slider::new(&mut material.roughness, 0.0..=1.0).text("roughness"));
+
+                labled!(
+                    ui,
+                    "mip bias",
+                    ui.add(
+                        DragValue::new(&mut material.mip_bias)
+                            .speed(0.05)
+                            .clamp_range(-4.0..=4.0)
+                    )
+                );
+
+                labled!(
+                    ui,
+                    "max anisotropy",
+                    for &level in &PbrMaterial::ANISOTROPY_LEVELS {
+                        ui.selectable_value(&mut material.max_anisotropy, level, level.to_string());
+                    }
+                );
+
+                let mut batched = batch.is_some();
+                ui.checkbox(&mut batched, "batch");
+
+                if batched != batch.is_some() {
+                    *batch = batched.then(String::new);
+                }
+
+                if let Some(batch) = batch {
+                    labled!(ui, "batch key", ui.text_edit_singleline(batch));
+                }
+
+                ui.checkbox(wireframe, "wireframe");
+
+                ui.horizontal(|ui| {
+                    ui.checkbox(cast_shadow, "cast shadow");
+                    ui.checkbox(receive_shadow, "receive shadow");
+                });
+
+                let mut culled = max_draw_distance.is_some();
+                ui.checkbox(&mut culled, "max draw distance");
+
+                if culled != max_draw_distance.is_some() {
+                    *max_draw_distance = culled.then_some(100.0);
+                }
+
+                if let Some(max_draw_distance) = max_draw_distance {
+                    labled!(
+                        ui,
+                        "draw distance",
+                        ui.add(
+                            DragValue::new(max_draw_distance)
+                                .speed(0.5)
+                                .clamp_range(0.0..=f32::MAX)
+                        )
+                    );
+                }
+
+                let mut remove = None;
+
+                for (i, lod) in lods.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.button("-").clicked() {
+                            remove = Some(i);
+                        }
+
+                        ui.label(&lod.mesh);
+                        ui.add(DragValue::new(&mut lod.distance).prefix("from "));
+                    });
+                }
+
+                if let Some(i) = remove {
+                    lods.remove(i);
+                }
             }
-            Self::Camera { fov, near, far } => {
-                labled!(ui, "fov", ui.add(DragValue::new(fov)));
+            Self::Camera {
+                projection,
+                near,
+                far,
+                viewport,
+                auto_clip,
+                ..
+            } => {
+                ui.horizontal(|ui| {
+                    for other in Projection::projections() {
+                        let selected = projection.name() == other.name();
+
+                        if ui.selectable_label(selected, other.name()).clicked() && !selected {
+                            *projection = other;
+                        }
+                    }
+                });
+
+                match projection {
+                    Projection::Perspective { fov } => {
+                        labled!(ui, "fov", ui.add(DragValue::new(fov)));
+                    }
+                    Projection::Orthographic { height } => {
+                        labled!(
+                            ui,
+                            "height",
+                            ui.add(
+                                DragValue::new(height)
+                                    .speed(0.1)
+                                    .clamp_range(0.01..=f32::MAX)
+                            )
+                        );
+                    }
+                }
+
+                ui.checkbox(auto_clip, "auto near/far");
                 labled!(ui, "near", ui.add(DragValue::new(near)));
                 labled!(ui, "far", ui.add(DragValue::new(far)));
+
+                ui.label("viewport");
+                ui.columns(4, |columns| {
+                    let speed = 0.01;
+                    let range = 0.0..=1.0;
+                    columns[0].add(
+                        DragValue::new(&mut viewport.x)
+                            .speed(speed)
+                            .clamp_range(range.clone()),
+                    );
+                    columns[1].add(
+                        DragValue::new(&mut viewport.y)
+                            .speed(speed)
+                            .clamp_range(range.clone()),
+                    );
+                    columns[2].add(
+                        DragValue::new(&mut viewport.width)
+                            .speed(speed)
+                            .clamp_range(range.clone()),
+                    );
+                    columns[3].add(
+                        DragValue::new(&mut viewport.height)
+                            .speed(speed)
+                            .clamp_range(range),
+                    );
+                });
+            }
+            Self::Light {
+                kind,
+                color,
+                strength,
+            } => {
+                ui.horizontal(|ui| {
+                    for other in LightKind::kinds() {
+                        let selected = kind.name() == other.name();
+
+                        if ui.selectable_label(selected, other.name()).clicked() && !selected {
+                            *kind = other;
+                        }
+                    }
+                });
+
+                match kind {
+                    LightKind::Directional {
+                        direction,
+                        casts_shadow,
+                    } => {
+                        labled!(ui, "direction", drag_vec3(ui, direction));
+                        ui.checkbox(casts_shadow, "cast shadow");
+                    }
+                    LightKind::Point { radius } => {
+                        labled!(
+                            ui,
+                            "radius",
+                            ui.add(
+                                DragValue::new(radius)
+                                    .speed(0.1)
+                                    .clamp_range(0.01..=f32::MAX)
+                            )
+                        );
+                    }
+                    LightKind::Spot {
+                        range,
+                        inner_angle,
+                        outer_angle,
+                    } => {
+                        labled!(
+                            ui,
+                            "range",
+                            ui.add(
+                                DragValue::new(range)
+                                    .speed(0.1)
+                                    .clamp_range(0.01..=f32::MAX)
+                            )
+                        );
+
+                        let mut inner = *inner_angle / std::f32::consts::PI * 180.0;
+                        let mut outer = *outer_angle / std::f32::consts::PI * 180.0;
+
+                        labled!(
+                            ui,
+                            "inner angle",
+                            ui.add(DragValue::new(&mut inner).clamp_range(0.0..=89.0))
+                        );
+                        labled!(
+                            ui,
+                            "outer angle",
+                            ui.add(DragValue::new(&mut outer).clamp_range(0.0..=89.0))
+                        );
+
+                        *inner_angle = inner / 180.0 * std::f32::consts::PI;
+                        *outer_angle = outer.max(inner) / 180.0 * std::f32::consts::PI;
+                    }
+                }
+
+                let mut rgb = (*color).into();
+                labled!(ui, "color", ui.color_edit_button_rgb(&mut rgb));
+                *color = rgb.into();
+
+                labled!(ui, "strength", ui.add(DragValue::new(strength)));
+            }
+            Self::Spline { points, closed } => {
+                ui.checkbox(closed, "closed");
+
+                let mut remove = None;
+
+                for (i, point) in points.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.button("-").clicked() {
+                            remove = Some(i);
+                        }
+
+                        drag_vec3(ui, point);
+                    });
+                }
+
+                if let Some(i) = remove {
+                    points.remove(i);
+                }
+
+                if ui.button("Add Point").clicked() {
+                    let point = points.last().copied().unwrap_or(Vec3::ZERO);
+                    points.push(point + Vec3::X);
+                }
+            }
+            Self::FollowPath {
+                spline,
+                speed,
+                t,
+                orient,
+            } => {
+                labled!(ui, "spline", ui.add(DragValue::new(&mut spline.0)));
+                labled!(ui, "speed", ui.add(DragValue::new(speed).speed(0.01)));
+                ui.add(Slider::new(t, 0.0..=1.0).text("t"));
+                ui.checkbox(orient, "orient");
+            }
+            Self::Attach { target, offset } => {
+                labled!(ui, "target", ui.add(DragValue::new(&mut target.0)));
+
+                ui.label("offset");
+                transform_ui(ui, offset);
+            }
+            Self::UvScroll { speed, .. } => {
+                labled!(
+                    ui,
+                    "speed",
+                    ui.columns(2, |columns| {
+                        columns[0].add(DragValue::new(&mut speed.x).speed(0.01));
+                        columns[1].add(DragValue::new(&mut speed.y).speed(0.01));
+                    })
+                );
+            }
+            Self::FadeAnim {
+                from,
+                to,
+                duration,
+                looping,
+                elapsed,
+            } => {
+                let mut from_color = from.to_array();
+                labled!(
+                    ui,
+                    "from",
+                    ui.color_edit_button_rgba_unmultiplied(&mut from_color)
+                );
+                *from = Vec4::from(from_color);
+
+                let mut to_color = to.to_array();
+                labled!(
+                    ui,
+                    "to",
+                    ui.color_edit_button_rgba_unmultiplied(&mut to_color)
+                );
+                *to = Vec4::from(to_color);
+
+                labled!(
+                    ui,
+                    "duration",
+                    ui.add(
+                        DragValue::new(duration)
+                            .speed(0.05)
+                            .clamp_range(0.0..=f32::MAX)
+                    )
+                );
+
+                ui.horizontal(|ui| {
+                    ui.checkbox(looping, "loop");
+
+                    if ui.button("Restart").clicked() {
+                        *elapsed = 0.0;
+                    }
+                });
             }
-            Self::DirectionalLight(light) => {
-                labled!(ui, "direction", drag_vec3(ui, &mut light.direction));
+            Self::MirrorPlane { strength } => {
+                ui.add(Slider::new(strength, 0.0..=1.0).text("strength"));
+            }
+            Self::DayNightCycle {
+                time_of_day,
+                day_length,
+            } => {
+                ui.add(Slider::new(time_of_day, 0.0..=24.0).text("time of day"));
+                labled!(
+                    ui,
+                    "day length",
+                    ui.add(
+                        DragValue::new(day_length)
+                            .speed(1.0)
+                            .clamp_range(0.0..=f32::MAX)
+                    )
+                );
+            }
+            Self::Label {
+                text,
+                color,
+                offset,
+                clamp_to_screen,
+            } => {
+                labled!(ui, "text", ui.text_edit_singleline(text));
+
+                let mut label_color = (*color).into();
+                labled!(ui, "color", ui.color_edit_button_rgb(&mut label_color));
+                *color = label_color.into();
+
+                ui.label("offset");
+                drag_vec3(ui, offset);
+
+                ui.checkbox(clamp_to_screen, "clamp to screen");
+            }
+            Self::ParallaxLayer {
+                texture,
+                depth,
+                scroll,
+                ..
+            } => {
+                labled!(ui, "texture", ui.text_edit_singleline(texture));
+                labled!(
+                    ui,
+                    "depth",
+                    ui.add(
+                        DragValue::new(depth)
+                            .speed(0.1)
+                            .clamp_range(0.01..=f32::MAX)
+                    )
+                );
+                labled!(
+                    ui,
+                    "scroll",
+                    ui.columns(2, |columns| {
+                        columns[0].add(DragValue::new(&mut scroll.x).speed(0.01));
+                        columns[1].add(DragValue::new(&mut scroll.y).speed(0.01));
+                    })
+                );
+            }
+            Self::Scatter {
+                mesh,
+                albedo,
+                material,
+                count,
+                area,
+                seed,
+                ..
+            } => {
+                ui.text_edit_singleline(mesh);
+                labled!(ui, "albedo texture", ui.text_edit_singleline(albedo));
+
+                let mut albedo_color = material.albedo.into();
+                labled!(ui, "albedo", ui.color_edit_button_rgb(&mut albedo_color));
+                material.albedo = albedo_color.into();
+
+                labled!(ui, "count", ui.add(DragValue::new(count)));
 
-                let mut color = light.color.into();
-                ui.color_edit_button_rgb(&mut color);
-                light.color = color.into();
+                ui.label("area");
+                drag_vec3(ui, area);
 
-                labled!(ui, "strength", ui.add(DragValue::new(&mut light.strength)));
+                ui.horizontal(|ui| {
+                    ui.label("seed");
+                    ui.add(DragValue::new(seed));
+
+                    if ui.button("Reseed").clicked() {
+                        *seed = Rng::new(*seed).next_u64();
+                    }
+                });
             }
             _ => {}
         }
+
+        self.place_scatter();
+        self.sync_shadow_flags();
     }
 
     #[inline]
@@ -156,7 +1135,7 @@ impl Component {
         &mut self,
         id: NodeId,
         transform: &mut Transform,
-        resources: &Resources,
+        parent: Option<NodeId>,
         world: &mut World,
     ) {
         match self {
@@ -171,49 +1150,674 @@ impl Component {
                     transform.look_at(player.transform.translation, Vec3::Z);
                 }
             }
+            Self::FollowPath {
+                spline,
+                speed,
+                t,
+                orient,
+            } => {
+                if let Some(node) = world.node(spline) {
+                    let points = node
+                        .components
+                        .iter()
+                        .find_map(|component| match component {
+                            Self::Spline { points, closed } => Some((points, *closed)),
+                            _ => None,
+                        });
+
+                    if let Some((points, closed)) = points {
+                        *t += *speed * world.delta_time;
+
+                        if closed {
+                            *t = t.rem_euclid(1.0);
+                        } else {
+                            *t = t.clamp(0.0, 1.0);
+                        }
+
+                        // `transform` is local, so go through the follower's parent too
+                        let parent_matrix = parent
+                            .and_then(|parent| world.node(&parent))
+                            .map(|parent| parent.global.matrix);
+
+                        let matrix = match parent_matrix {
+                            Some(parent) => parent.inverse() * node.global.matrix,
+                            None => node.global.matrix,
+                        };
+
+                        if let Some(position) = spline::sample(points, closed, *t) {
+                            transform.translation = matrix.transform_point3(position);
+                        }
+
+                        if *orient {
+                            if let Some(tangent) = spline::tangent(points, closed, *t) {
+                                let tangent = matrix.transform_vector3(tangent);
+
+                                if tangent.length_squared() > f32::EPSILON {
+                                    let target = transform.translation + tangent;
+                                    transform.look_at(target, Vec3::Y);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            Self::Attach { target, offset } => {
+                if let Some(target) = world.node(target) {
+                    let global = Transform::from_matrix(target.global.matrix).mul_transform(offset);
+
+                    // keep the node's own parent in mind, since `transform` is local
+                    let parent_matrix = parent
+                        .and_then(|parent| world.node(&parent))
+                        .map(|parent| parent.global.matrix);
+
+                    *transform = match parent_matrix {
+                        Some(matrix) => Transform::from_matrix(matrix.inverse() * global.matrix()),
+                        None => global,
+                    };
+                }
+            }
+            Self::UvScroll { speed, offset } => {
+                // wrapped to keep precision, textures repeat every unit anyway
+                *offset = (*offset + *speed * world.delta_time).fract();
+            }
+            Self::ParallaxLayer { scroll, offset, .. } => {
+                *offset = (*offset + *scroll * world.delta_time).fract();
+            }
+            Self::DayNightCycle {
+                time_of_day,
+                day_length,
+            } => {
+                if *day_length > 0.0 {
+                    *time_of_day += world.delta_time / *day_length * 24.0;
+                }
+
+                *time_of_day = time_of_day.rem_euclid(24.0);
+
+                let sky = day_night_sky(*time_of_day);
+
+                let settings = &mut world.data.render_settings;
+                settings.ambient_color = sky.ambient_color;
+                settings.ambient_strength = sky.ambient_strength;
+
+                // the sun is switched back on, the cycle doesn't mean much without it
+                let sun = world.data.sun.get_or_insert_with(DirectionalLight::default);
+                sun.direction = sun_direction(*time_of_day);
+                sun.color = sky.sun_color;
+                sun.strength = sky.sun_strength;
+            }
+            Self::FadeAnim {
+                duration,
+                looping,
+                elapsed,
+                ..
+            } => {
+                *elapsed += world.delta_time;
+
+                if *looping && *duration > 0.0 {
+                    *elapsed %= *duration;
+                } else {
+                    // stops at the end, so it doesn't grow forever
+                    *elapsed = elapsed.min(*duration);
+                }
+            }
+            Self::Scatter { .. } => self.place_scatter(),
             _ => {}
         }
     }
 
+    // makes `frame` view the scene through this camera
+    #[inline]
+    pub fn bind_camera(&self, global: &GlobalTransform, frame: &mut Frame<'_>) {
+        if let Self::Camera {
+            projection,
+            near,
+            far,
+            viewport,
+            auto_clip,
+            ..
+        } = self
+        {
+            let (near, far) = match frame.scene_bounds {
+                Some(bounds) if *auto_clip => auto_clip_planes(bounds, global.translation()),
+                _ => (*near, *far),
+            };
+
+            let aspect =
+                frame.aspect * viewport.width.max(f32::EPSILON) / viewport.height.max(f32::EPSILON);
+
+            let proj = projection.matrix(aspect, near, far);
+            let view_proj = proj * global.matrix.inverse();
+
+            frame.camera_matrix = view_proj;
+            frame.camera_position = global.translation();
+            frame.viewport = *viewport;
+        }
+    }
+
     #[inline]
     pub fn render<'a>(
-        &'a mut self,
+        &'a self,
         id: NodeId,
         resources: &'a Resources,
-        transform: &Transform,
+        global: &GlobalTransform,
         frame: &mut Frame<'a>,
     ) {
         match self {
-            Self::Mesh {
+            // batched meshes are drawn by the world
+            Self::Mesh { batch: None, .. } => self.render_mesh(resources, global, frame),
+            Self::Light {
+                kind,
+                color,
+                strength,
+            } => match *kind {
+                LightKind::Directional {
+                    direction,
+                    casts_shadow,
+                } => frame.add_directional_light(DirectionalLight {
+                    direction,
+                    color: *color,
+                    strength: *strength,
+                    casts_shadow,
+                }),
+                LightKind::Point { radius } => frame.add_point_light(PointLight {
+                    position: global.translation(),
+                    color: *color,
+                    strength: *strength,
+                    radius,
+                }),
+                LightKind::Spot {
+                    range,
+                    inner_angle,
+                    outer_angle,
+                } => frame.add_spot_light(SpotLight {
+                    position: global.translation(),
+                    direction: global.matrix.transform_vector3(-Vec3::Z),
+                    color: *color,
+                    strength: *strength,
+                    range,
+                    inner_angle,
+                    outer_angle,
+                }),
+            },
+            Self::ParallaxLayer {
+                texture,
+                depth,
+                offset,
+                ..
+            } => {
+                if let Some(texture) = resources.texture_slot(texture) {
+                    frame.add_parallax_layer(ParallaxLayer {
+                        texture,
+                        depth: *depth,
+                        offset: parallax_offset(frame.camera_position, *depth, *offset),
+                    });
+                }
+            }
+            Self::MirrorPlane { .. } if frame.mirror.is_none() => {
+                let normal = global
+                    .matrix
+                    .inverse()
+                    .transpose()
+                    .transform_vector3(Vec3::Y)
+                    .normalize();
+
+                frame.mirror = Some(normal.extend(-normal.dot(global.translation())));
+            }
+            Self::Scatter {
                 mesh,
                 albedo,
-                emission,
                 material,
+                instances,
+                ..
             } => {
                 if let Some(mesh) = resources.get_mesh(mesh) {
-                    frame.render_mesh(
+                    let transforms = instances
+                        .iter()
+                        .map(|instance| global.matrix * *instance)
+                        .collect();
+
+                    frame.render_mesh_instanced(
                         mesh,
                         material,
-                        resources.get_texture(albedo),
-                        resources.get_texture(emission),
-                        transform.matrix(),
-                    )
+                        resources.texture_slot(albedo),
+                        None,
+                        None,
+                        transforms,
+                    );
                 }
             }
-            Self::DirectionalLight(light) => frame.add_directional_light(*light),
-            Self::Camera { fov, near, far } => {
-                let proj = Mat4::perspective_rh(
-                    *fov / 180.0 * std::f32::consts::PI,
-                    frame.aspect,
-                    *near,
-                    *far,
+            _ => {}
+        }
+    }
+
+    // true for meshes further from the camera than their max draw distance
+    #[inline]
+    pub fn beyond_draw_distance(&self, global: &GlobalTransform, frame: &Frame) -> bool {
+        match self {
+            Self::Mesh {
+                max_draw_distance: Some(max_draw_distance),
+                ..
+            } => global.translation().distance(frame.camera_position) > *max_draw_distance,
+            _ => false,
+        }
+    }
+
+    // how many lod distances a mesh is past when seen from `camera_position`, `None` once it's
+    // beyond its max draw distance, the shadow pass draws the same meshes so moving the camera
+    // across either has to render shadows again
+    pub fn draw_selection(&self, global: &GlobalTransform, camera_position: Vec3) -> Option<usize> {
+        match self {
+            Self::Mesh {
+                lods,
+                max_draw_distance,
+                ..
+            } => {
+                let distance = global.translation().distance(camera_position);
+
+                if max_draw_distance.is_some_and(|max| distance > max) {
+                    return None;
+                }
+
+                Some(lods.iter().filter(|lod| distance >= lod.distance).count())
+            }
+            _ => Some(0),
+        }
+    }
+
+    // draws a mesh component on its own, picking the baked mesh or a lod
+    pub fn render_mesh<'a>(
+        &'a self,
+        resources: &'a Resources,
+        global: &GlobalTransform,
+        frame: &mut Frame<'a>,
+    ) {
+        if let Self::Mesh {
+            mesh,
+            albedo,
+            emission,
+            normal,
+            material,
+            lods,
+            baked,
+            wireframe,
+            ..
+        } = self
+        {
+            if self.beyond_draw_distance(global, frame) {
+                return;
+            }
+
+            if frame.baked_lighting {
+                if let Some((mesh, material)) = baked {
+                    if let Some(mesh) = resources.get_mesh(mesh) {
+                        frame.render_mesh(
+                            mesh,
+                            material,
+                            resources.texture_slot(albedo),
+                            resources.texture_slot(emission),
+                            resources.normal_slot(normal),
+                            global.matrix,
+                        );
+
+                        if *wireframe {
+                            frame.render_wireframe(mesh, global.matrix);
+                        }
+
+                        return;
+                    }
+                }
+            }
+
+            let distance = global.translation().distance(frame.camera_position);
+
+            // the furthest lod in range that is loaded, lods are baked at runtime so they
+            // may be missing
+            let mesh = lods
+                .iter()
+                .filter(|lod| distance >= lod.distance)
+                .filter_map(|lod| Some((lod.distance, resources.get_mesh(&lod.mesh)?)))
+                .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+                .map(|(_, mesh)| mesh)
+                .or_else(|| resources.get_mesh(mesh));
+
+            if let Some(mesh) = mesh {
+                frame.render_mesh(
+                    mesh,
+                    material,
+                    resources.texture_slot(albedo),
+                    resources.texture_slot(emission),
+                    resources.normal_slot(normal),
+                    global.matrix,
                 );
-                let view_proj = proj * transform.matrix().inverse();
 
-                frame.camera_matrix = view_proj;
-                frame.camera_position = transform.translation;
+                if *wireframe {
+                    frame.render_wireframe(mesh, global.matrix);
+                }
             }
-            _ => {}
+        }
+    }
+
+    // draws the full detail mesh of a mesh component once per transform, with its material,
+    // `ids` are the id buffer values of the instances
+    pub fn render_instanced<'a>(
+        &'a self,
+        resources: &'a Resources,
+        transforms: Vec<Mat4>,
+        ids: Vec<u32>,
+        frame: &mut Frame<'a>,
+    ) {
+        if let Self::Mesh {
+            mesh,
+            albedo,
+            emission,
+            normal,
+            material,
+            ..
+        } = self
+        {
+            if let Some(mesh) = resources.get_mesh(mesh) {
+                frame.render_mesh_batch(
+                    mesh,
+                    material,
+                    resources.texture_slot(albedo),
+                    resources.texture_slot(emission),
+                    resources.normal_slot(normal),
+                    transforms,
+                    ids,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a mesh and a light as saved before the newer material, shadow and light fields existed
+    const OLD_COMPONENTS: &str = r#"[
+        Mesh(
+            mesh: "assets/desk.gltf",
+            albedo: "assets/desk.png",
+            emission: "",
+            material: (
+                albedo: (0.1, 0.2, 0.3),
+                emission: (0, 0, 0),
+            ),
+        ),
+        DirectionalLight((
+            direction: (2, -0.5, 1),
+            color: (0.9, 0.8, 0.7),
+            strength: 2,
+        )),
+    ]"#;
+
+    #[test]
+    fn old_components_default_new_fields() {
+        let mut components: Vec<Component> = ron::from_str(OLD_COMPONENTS).unwrap();
+
+        for component in &mut components {
+            component.migrate();
+        }
+
+        match &components[0] {
+            Component::Mesh {
+                material,
+                normal,
+                lods,
+                cast_shadow,
+                receive_shadow,
+                ..
+            } => {
+                let default = PbrMaterial::default();
+
+                assert_eq!(material.albedo, Vec3::new(0.1, 0.2, 0.3));
+                assert_eq!(material.specular_bloom, default.specular_bloom);
+                assert_eq!(material.emission_strength, default.emission_strength);
+                assert_eq!(material.max_anisotropy, default.max_anisotropy);
+                assert_eq!(material.roughness, default.roughness);
+                assert_eq!(material.skip_shadow_cast, 0);
+                assert!(normal.is_empty() && lods.is_empty());
+                assert!(*cast_shadow && *receive_shadow);
+            }
+            _ => panic!("expected a mesh"),
+        }
+
+        match components[1] {
+            Component::Light {
+                kind:
+                    LightKind::Directional {
+                        direction,
+                        casts_shadow,
+                    },
+                color,
+                strength,
+            } => {
+                assert_eq!(direction, Vec3::new(2.0, -0.5, 1.0));
+                assert_eq!(color, Vec3::new(0.9, 0.8, 0.7));
+                assert_eq!(strength, 2.0);
+                assert!(casts_shadow);
+            }
+            _ => panic!("expected a migrated directional light"),
+        }
+    }
+
+    #[test]
+    fn uv_scroll_offset_accumulates_and_wraps() {
+        let mut world = World::new();
+        world.delta_time = 0.5;
+
+        let mut scroll = Component::UvScroll {
+            speed: Vec2::new(0.5, -0.1),
+            offset: Vec2::ZERO,
+        };
+        let mut transform = Transform::IDENTITY;
+
+        let mut step = |world: &mut World| {
+            scroll.update(NodeId(0), &mut transform, None, world);
+
+            match scroll {
+                Component::UvScroll { offset, .. } => offset,
+                _ => unreachable!(),
+            }
+        };
+
+        // wrapped into [0, 1), so scrolling backwards starts from the far end
+        assert!(step(&mut world).abs_diff_eq(Vec2::new(0.25, 0.95), 1e-6));
+        assert!(step(&mut world).abs_diff_eq(Vec2::new(0.5, 0.9), 1e-6));
+
+        // a whole unit more lands on the same offset rather than growing, which would lose
+        // precision over time
+        world.delta_time = 2.0;
+        assert!(step(&mut world).abs_diff_eq(Vec2::new(0.5, 0.7), 1e-5));
+    }
+
+    #[test]
+    fn meshes_past_max_draw_distance_are_culled() {
+        let mut mesh = Component::mesh("pebble.gltf".into());
+
+        if let Component::Mesh {
+            max_draw_distance, ..
+        } = &mut mesh
+        {
+            *max_draw_distance = Some(10.0);
+        }
+
+        let mut global = GlobalTransform::default();
+        global.recompute(Mat4::IDENTITY, &Transform::from_xyz(0.0, 0.0, -2.0));
+
+        let mut frame = Frame::new();
+
+        frame.camera_position = Vec3::new(0.0, 0.0, 7.99);
+        assert!(!mesh.beyond_draw_distance(&global, &frame));
+        assert!(mesh
+            .draw_selection(&global, frame.camera_position)
+            .is_some());
+
+        frame.camera_position = Vec3::new(0.0, 0.0, 8.01);
+        assert!(mesh.beyond_draw_distance(&global, &frame));
+        assert!(mesh
+            .draw_selection(&global, frame.camera_position)
+            .is_none());
+
+        // without a max distance nothing is culled however far away
+        frame.camera_position = Vec3::splat(1.0e6);
+        assert!(!Component::mesh("rock.gltf".into()).beyond_draw_distance(&global, &frame));
+    }
+
+    #[test]
+    fn auto_clip_far_encloses_farthest_node() {
+        // nodes spread over 20 x 2 x 40 units, the camera stands just outside
+        let bounds = Aabb {
+            min: Vec3::new(-10.0, -1.0, -10.0),
+            max: Vec3::new(10.0, 1.0, 30.0),
+        };
+        let position = Vec3::new(0.0, 2.0, -20.0);
+
+        let (near, far) = auto_clip_planes(bounds, position);
+
+        let farthest = Vec3::new(10.0, -1.0, 30.0).distance(position);
+        assert!(far >= farthest);
+        assert!(near <= bounds.distance_squared(position).sqrt());
+        assert!(near >= MIN_NEAR && far / near <= MAX_CLIP_RATIO * 1.001);
+
+        // the camera picks them up, so the back of the scene lands inside the depth range
+        let camera = Component::Camera {
+            projection: Projection::default(),
+            fov: None,
+            near: 1.0,
+            far: 2.0,
+            viewport: Viewport::default(),
+            auto_clip: true,
+        };
+
+        let mut transform = Transform::from_translation(position);
+        transform.look_at(Vec3::new(0.0, 2.0, 30.0), Vec3::Y);
+
+        let mut global = GlobalTransform::default();
+        global.recompute(Mat4::IDENTITY, &transform);
+
+        let mut frame = Frame::new();
+        frame.aspect = 1.0;
+        frame.scene_bounds = Some(bounds);
+        camera.bind_camera(&global, &mut frame);
+
+        let depth = frame
+            .camera_matrix
+            .project_point3(Vec3::new(0.0, 0.0, 30.0))
+            .z;
+        assert!(depth > 0.0 && depth <= 1.0);
+    }
+
+    #[test]
+    fn parallax_layers_shift_less_the_deeper_they_are() {
+        let scroll = Vec2::new(0.25, 0.0);
+        let shift = |depth| {
+            let from = parallax_offset(Vec3::new(0.0, 0.0, 5.0), depth, scroll);
+            let to = parallax_offset(Vec3::new(3.0, 1.5, 9.0), depth, scroll);
+
+            to - from
+        };
+
+        // moving right slides the texture right, moving up slides it down, moving in z does nothing
+        assert!(shift(1.0).abs_diff_eq(Vec2::new(3.0, -1.5), 1e-6));
+        assert!(shift(2.0).abs_diff_eq(shift(1.0) / 2.0, 1e-6));
+        assert!(shift(8.0).abs_diff_eq(shift(1.0) / 8.0, 1e-6));
+
+        // scrolling is on top of the shift, whatever the depth
+        assert_eq!(parallax_offset(Vec3::ZERO, 8.0, scroll), scroll);
+    }
+
+    #[test]
+    fn path_followers_with_a_parent_land_on_the_path() {
+        let mut world = World::new();
+
+        let spline = world.spawn(Node {
+            name: String::from("Path"),
+            transform: Transform::from_xyz(0.0, 0.0, 10.0),
+            parent: None,
+            global: GlobalTransform::default(),
+            components: vec![Component::Spline {
+                points: vec![Vec3::ZERO, Vec3::new(4.0, 0.0, 0.0)],
+                closed: false,
+            }],
+            version: 0,
+        });
+        let parent = world.spawn(Node {
+            name: String::from("Cart"),
+            transform: Transform::from_xyz(5.0, 2.0, 0.0),
+            parent: None,
+            global: GlobalTransform::default(),
+            components: Vec::new(),
+            version: 0,
+        });
+        world.update_transforms();
+
+        let mut follow = Component::FollowPath {
+            spline,
+            speed: 0.0,
+            t: 0.5,
+            orient: true,
+        };
+        let mut transform = Transform::IDENTITY;
+        follow.update(NodeId(2), &mut transform, Some(parent), &mut world);
+
+        // the local translation undoes the parent, so the follower ends up on the path in world space
+        let global = world.node(&parent).unwrap().global.matrix * transform.matrix();
+        let position = global.transform_point3(Vec3::ZERO);
+        assert!(position.abs_diff_eq(Vec3::new(2.0, 0.0, 10.0), 1e-5));
+
+        // and it faces along the path, which runs down +x
+        let forward = global.transform_vector3(-Vec3::Z).normalize();
+        assert!(forward.abs_diff_eq(Vec3::X, 1e-5));
+    }
+
+    #[test]
+    fn fade_anim_tints_halfway_at_mid_duration() {
+        let albedo = Vec3::new(0.2, 0.4, 0.6);
+        let mut mesh = Component::mesh("crate.gltf".into());
+
+        if let Component::Mesh { material, .. } = &mut mesh {
+            material.albedo = albedo;
+        }
+
+        let mut node = Node {
+            name: String::from("Crate"),
+            transform: Transform::IDENTITY,
+            parent: None,
+            global: GlobalTransform::default(),
+            components: vec![
+                mesh,
+                Component::FadeAnim {
+                    from: Vec4::new(1.0, 0.0, 0.0, 1.0),
+                    to: Vec4::new(0.0, 0.0, 1.0, 0.0),
+                    duration: 2.0,
+                    looping: false,
+                    elapsed: 1.0,
+                },
+            ],
+            version: 0,
+        };
+
+        node.sync_materials();
+
+        match &node.components[0] {
+            Component::Mesh { material, .. } => {
+                assert_eq!(material.tint, Vec4::new(0.5, 0.0, 0.5, 0.5));
+                // the authored albedo is what gets saved
+                assert_eq!(material.albedo, albedo);
+            }
+            _ => panic!("expected a mesh"),
+        }
+
+        let saved: Node = ron::from_str(&ron::to_string(&node).unwrap()).unwrap();
+
+        match &saved.components[0] {
+            Component::Mesh { material, .. } => {
+                assert_eq!(material.albedo, albedo);
+                assert_eq!(material.tint, Vec4::ONE);
+            }
+            _ => panic!("expected a mesh"),
         }
     }
 }
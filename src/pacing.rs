@@ -0,0 +1,116 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+// samples kept for the rolling statistics, a few seconds at common refresh rates
+const HISTORY: usize = 240;
+
+// latency histogram buckets, the last one also counts everything slower
+pub const BUCKET_MS: f32 = 4.0;
+pub const BUCKETS: usize = 25;
+
+// times input events against the frame that consumed them, and frame to frame intervals
+pub struct FramePacing {
+    // arrival of the oldest input not yet consumed by a frame
+    pending_input: Option<Instant>,
+    last_frame: Option<Instant>,
+    frame_times: VecDeque<f32>,
+    latencies: VecDeque<f32>,
+}
+
+impl FramePacing {
+    pub fn new() -> Self {
+        Self {
+            pending_input: None,
+            last_frame: None,
+            frame_times: VecDeque::with_capacity(HISTORY),
+            latencies: VecDeque::with_capacity(HISTORY),
+        }
+    }
+
+    #[inline]
+    pub fn input(&mut self, now: Instant) {
+        self.pending_input.get_or_insert(now);
+    }
+
+    // called once a frame has been presented, it reflects all input that arrived before it
+    pub fn presented(&mut self, now: Instant) {
+        if let Some(last_frame) = self.last_frame.replace(now) {
+            push(&mut self.frame_times, millis(now - last_frame));
+        }
+
+        if let Some(input) = self.pending_input.take() {
+            push(&mut self.latencies, millis(now - input));
+        }
+    }
+
+    #[inline]
+    pub fn last_latency(&self) -> Option<f32> {
+        self.latencies.back().copied()
+    }
+
+    #[inline]
+    pub fn max_latency(&self) -> Option<f32> {
+        self.latencies.iter().copied().reduce(f32::max)
+    }
+
+    #[inline]
+    pub fn mean_latency(&self) -> Option<f32> {
+        mean(&self.latencies)
+    }
+
+    #[inline]
+    pub fn mean_frame_time(&self) -> Option<f32> {
+        mean(&self.frame_times)
+    }
+
+    // standard deviation of the frame time, high values are visible as stutter
+    pub fn frame_time_deviation(&self) -> Option<f32> {
+        let mean = self.mean_frame_time()?;
+
+        let variance = self
+            .frame_times
+            .iter()
+            .map(|time| (time - mean) * (time - mean))
+            .sum::<f32>()
+            / self.frame_times.len() as f32;
+
+        Some(variance.sqrt())
+    }
+
+    // latency sample counts, bucket i covers `i * BUCKET_MS` up to `(i + 1) * BUCKET_MS`
+    pub fn latency_histogram(&self) -> [u32; BUCKETS] {
+        let mut buckets = [0; BUCKETS];
+
+        for latency in &self.latencies {
+            let bucket = ((latency / BUCKET_MS) as usize).min(BUCKETS - 1);
+            buckets[bucket] += 1;
+        }
+
+        buckets
+    }
+}
+
+#[inline]
+fn push(samples: &mut VecDeque<f32>, sample: f32) {
+    if samples.len() == HISTORY {
+        samples.pop_front();
+    }
+
+    samples.push_back(sample);
+}
+
+#[inline]
+fn mean(samples: &VecDeque<f32>) -> Option<f32> {
+    if samples.is_empty() {
+        return None;
+    }
+
+    Some(samples.iter().sum::<f32>() / samples.len() as f32)
+}
+
+#[inline]
+fn millis(duration: Duration) -> f32 {
+    duration.as_secs_f32() * 1000.0
+}
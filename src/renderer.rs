@@ -1,15 +1,18 @@
 use crate::{
-    bindings::{BindGroup, UniformBlock},
+    bindings::{self, BindGroup, UniformBlock},
     differed::*,
-    instance::Instance,
-    mesh::Mesh,
+    frustum::Frustum,
+    ibl::Ibl,
+    instance::{Instance, Swapchain},
+    mesh::{Aabb, BoundingSphere, Mesh},
     ui::{UiMesh, UiVertex},
     ui_pipelines::ui_pipeline,
 };
 use bytemuck::{cast_slice, Pod, Zeroable};
+use futures::FutureExt;
 use glam::*;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::{future::Future, pin::Pin, sync::Arc};
 use wgpu::util::DeviceExt;
 
 fn render_texture(
@@ -44,9 +47,18 @@ fn render_texture(
     }))
 }
 
+// emission and bloom are hdr so values above 1 survive into the bloom
+pub const EMISSION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+// node ids written by the separate pass, see `NodeId::pick_id`
+pub const ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+
 pub struct GBuffer {
     pub sampler: Arc<wgpu::Sampler>,
-    pub shadow: Arc<wgpu::TextureView>,
+    // compares against the shadow maps, filtering blends the results of neighbouring texels
+    pub shadow_sampler: Arc<wgpu::Sampler>,
+    // one per shadow casting light, independent of the screen size
+    pub shadows: Vec<Arc<wgpu::TextureView>>,
     pub depth: Arc<wgpu::TextureView>,
     pub position: Arc<wgpu::TextureView>,
     pub normal: Arc<wgpu::TextureView>,
@@ -54,6 +66,12 @@ pub struct GBuffer {
     pub emission: Arc<wgpu::TextureView>,
     pub bloom: Arc<wgpu::TextureView>,
     pub light: Arc<wgpu::TextureView>,
+    pub reflection: Arc<wgpu::TextureView>,
+    // ids of the view being drawn, copied into `ids` inside the view's viewport
+    pub id: Arc<wgpu::TextureView>,
+    id_texture: wgpu::Texture,
+    ids: wgpu::Texture,
+    ids_view: wgpu::TextureView,
 }
 
 impl GBuffer {
@@ -63,73 +81,402 @@ impl GBuffer {
             ..Default::default()
         });
 
+        let shadow_sampler = instance.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("shadow sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let ids = id_texture(instance, width, height);
+        let ids_view = ids.create_view(&Default::default());
+        let id_texture = id_texture(instance, width, height);
+
         Self {
             sampler: Arc::new(sampler),
-            shadow: render_texture(instance, wgpu::TextureFormat::Depth32Float, width, height),
+            shadow_sampler: Arc::new(shadow_sampler),
+            shadows: (0..MAX_SHADOW_MAPS)
+                .map(|_| {
+                    render_texture(
+                        instance,
+                        wgpu::TextureFormat::Depth32Float,
+                        SHADOW_MAP_SIZE,
+                        SHADOW_MAP_SIZE,
+                    )
+                })
+                .collect(),
             depth: render_texture(instance, wgpu::TextureFormat::Depth32Float, width, height),
             position: render_texture(instance, wgpu::TextureFormat::Rgba32Float, width, height),
             normal: render_texture(instance, wgpu::TextureFormat::Rgba32Float, width, height),
             albedo: render_texture(instance, wgpu::TextureFormat::Rgba8UnormSrgb, width, height),
-            emission: render_texture(instance, wgpu::TextureFormat::Rgba8UnormSrgb, width, height),
-            bloom: render_texture(instance, wgpu::TextureFormat::Rgba8UnormSrgb, width, height),
+            emission: render_texture(instance, EMISSION_FORMAT, width, height),
+            bloom: render_texture(instance, EMISSION_FORMAT, width, height),
             light: render_texture(instance, wgpu::TextureFormat::Rgba8UnormSrgb, width, height),
+            reflection: render_texture(instance, EMISSION_FORMAT, width, height),
+            id: Arc::new(id_texture.create_view(&Default::default())),
+            id_texture,
+            ids,
+            ids_view,
         }
     }
 }
 
+fn id_texture(instance: &Instance, width: u32, height: u32) -> wgpu::Texture {
+    instance.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("id texture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        dimension: wgpu::TextureDimension::D2,
+        format: ID_FORMAT,
+        mip_level_count: 1,
+        sample_count: 1,
+        usage: wgpu::TextureUsage::RENDER_ATTACHMENT
+            | wgpu::TextureUsage::COPY_SRC
+            | wgpu::TextureUsage::COPY_DST,
+    })
+}
+
 pub struct RenderPipelines {
     pub shadow: wgpu::RenderPipeline,
     pub separate: wgpu::RenderPipeline,
+    // missing when the device can't draw lines from triangles
+    pub wireframe: Option<wgpu::RenderPipeline>,
     pub light: wgpu::RenderPipeline,
+    pub ssr: wgpu::RenderPipeline,
     pub bloom: wgpu::RenderPipeline,
     pub combine: wgpu::RenderPipeline,
+    pub grid: wgpu::RenderPipeline,
+    pub parallax: wgpu::RenderPipeline,
+    pub debug_line: wgpu::RenderPipeline,
     pub ui: wgpu::RenderPipeline,
+    pub irradiance: wgpu::RenderPipeline,
+    pub prefilter: wgpu::RenderPipeline,
+    pub brdf: wgpu::RenderPipeline,
 }
 
 impl RenderPipelines {
     pub fn new(instance: &Instance, sc_format: wgpu::TextureFormat) -> Self {
         Self {
             shadow: shadow_pipeline(instance),
-            separate: separate_pipeline(instance),
+            separate: separate_pipeline(instance, false),
+            wireframe: wireframe_pipeline(instance),
             light: light_pipeline(instance),
+            ssr: ssr_pipeline(instance),
             bloom: bloom_pipeline(instance),
             combine: combine_pipeline(instance, sc_format),
+            grid: grid_pipeline(instance, sc_format),
+            parallax: parallax_pipeline(instance),
+            debug_line: debug_line_pipeline(instance, sc_format),
             ui: ui_pipeline(instance, sc_format),
+            irradiance: irradiance_pipeline(instance),
+            prefilter: prefilter_pipeline(instance),
+            brdf: brdf_pipeline(instance),
         }
     }
 }
 
+// `InstanceInput` in separate.wgsl
 #[repr(C)]
-#[derive(Default, Clone, Copy, Zeroable, Pod, Serialize, Deserialize)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct MeshInstance {
+    model: Mat4,
+    normal: Mat4,
+    id: u32,
+    _pad: [u32; 3],
+}
+
+// a line or point drawn over the scene, `width` is in logical pixels
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+pub struct DebugLine {
+    pub start: Vec3,
+    pub width: f32,
+    pub end: Vec3,
+    pub _pad0: f32,
+    pub color: Vec4,
+}
+
+// fields missing from older worlds fall back to `Default`, so new fields can be added safely
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
 pub struct DirectionalLight {
     pub direction: Vec3,
-    #[serde(skip)]
-    pub _pad0: f32,
     pub color: Vec3,
     pub strength: f32,
+    // gets one of the `MAX_SHADOW_MAPS` shadow maps, in the order lights were added
+    pub casts_shadow: bool,
+}
+
+impl Default for DirectionalLight {
+    fn default() -> Self {
+        Self {
+            direction: -Vec3::Y,
+            color: Vec3::ONE,
+            strength: 1.0,
+            casts_shadow: true,
+        }
+    }
+}
+
+// `DirectionalLight` in light.wgsl, `shadow_map` is -1 for unshadowed lights
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct DirectionalLightUniform {
+    direction: Vec3,
+    shadow_map: i32,
+    color: Vec3,
+    strength: f32,
+}
+
+// lights everything within `radius` of `position`, fading out towards the edge
+#[derive(Clone, Copy)]
+pub struct PointLight {
+    pub position: Vec3,
+    pub color: Vec3,
+    pub strength: f32,
+    pub radius: f32,
+}
+
+// `PointLight` in light.wgsl
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct PointLightUniform {
+    position: Vec3,
+    radius: f32,
+    color: Vec3,
+    strength: f32,
+}
+
+// a cone of light from `position` along `direction`, full strength within `inner_angle` of it
+// and fading out by `outer_angle`, both in radians from the center of the cone
+#[derive(Clone, Copy)]
+pub struct SpotLight {
+    pub position: Vec3,
+    pub direction: Vec3,
+    pub color: Vec3,
+    pub strength: f32,
+    pub range: f32,
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+}
+
+// `SpotLight` in light.wgsl, the angles are passed as cosines
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct SpotLightUniform {
+    position: Vec3,
+    range: f32,
+    direction: Vec3,
+    cos_inner: f32,
+    color: Vec3,
+    strength: f32,
+    cos_outer: f32,
+    _pad0: [f32; 3],
 }
 
 #[repr(C)]
 #[derive(Clone, Copy, Zeroable, Pod, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PbrMaterial {
     pub albedo: Vec3,
+    // non-zero when lighting is baked into the vertex colors, the light pass then skips it
     #[serde(skip)]
-    pub _pad0: f32,
+    pub lighting_baked: u32,
     pub emission: Vec3,
     pub specular_bloom: f32,
+    // driven by `Component::UvScroll`, not saved
+    #[serde(skip)]
+    pub uv_offset: Vec2,
+    pub emission_strength: f32,
+    // added to the mip level textures are sampled at, positive is blurrier
+    pub mip_bias: f32,
+    // picks one of the `Renderer::mesh_samplers`, rounded down to a power of two up to 16
+    pub max_anisotropy: u32,
+    // share of the surface showing the mirror reflection, driven by `Component::MirrorPlane`
+    #[serde(skip)]
+    pub reflection: f32,
+    // non-zero to leave the mesh out of the shadow maps, driven by `Component::Mesh`
+    #[serde(skip)]
+    pub skip_shadow_cast: u32,
+    // non-zero to light the mesh as if nothing shadowed it, driven by `Component::Mesh`
+    #[serde(skip)]
+    pub skip_shadow_receive: u32,
+    // blurs environment reflections, picks the mip of `Ibl::specular`
+    pub roughness: f32,
+    #[serde(skip)]
+    pub _pad0: [f32; 3],
+    // multiplies the albedo, alpha dithers the mesh out, driven by `Component::FadeAnim`
+    #[serde(skip)]
+    pub tint: Vec4,
 }
 
 impl Default for PbrMaterial {
     fn default() -> Self {
         Self {
             albedo: Vec3::ONE,
-            _pad0: 0.0,
+            lighting_baked: 0,
             emission: Vec3::ZERO,
             specular_bloom: 0.02,
+            uv_offset: Vec2::ZERO,
+            emission_strength: 1.0,
+            mip_bias: 0.0,
+            max_anisotropy: 1,
+            reflection: 0.0,
+            skip_shadow_cast: 0,
+            skip_shadow_receive: 0,
+            roughness: 0.5,
+            _pad0: [0.0; 3],
+            tint: Vec4::ONE,
+        }
+    }
+}
+
+impl PbrMaterial {
+    // unlit and emissive, so wireframe overlays read the same under any lighting
+    pub const WIREFRAME: Self = Self {
+        albedo: Vec3::ZERO,
+        lighting_baked: 1,
+        emission: const_vec3!([1.0, 0.6, 0.1]),
+        specular_bloom: 0.0,
+        uv_offset: Vec2::ZERO,
+        emission_strength: 1.0,
+        mip_bias: 0.0,
+        max_anisotropy: 1,
+        reflection: 0.0,
+        skip_shadow_cast: 1,
+        skip_shadow_receive: 1,
+        roughness: 1.0,
+        _pad0: [0.0; 3],
+        tint: const_vec4!([1.0; 4]),
+    };
+
+    // valid anisotropy clamps, `mesh_samplers` has one sampler per level
+    pub const ANISOTROPY_LEVELS: [u32; 5] = [1, 2, 4, 8, 16];
+
+    // index into `ANISOTROPY_LEVELS` of the largest level not above `max_anisotropy`
+    #[inline]
+    pub fn anisotropy_index(&self) -> usize {
+        let clamped = self.max_anisotropy.clamp(1, 16);
+        (31 - clamped.leading_zeros()) as usize
+    }
+}
+
+// matches the array length in light.wgsl
+pub const MAX_DIRECTIONAL_LIGHTS: usize = 8;
+
+// matches the point light array length in light.wgsl
+pub const MAX_POINT_LIGHTS: usize = 32;
+
+// matches the spot light array length in light.wgsl
+pub const MAX_SPOT_LIGHTS: usize = 16;
+
+// matches the shadow map count in light.wgsl, flagged lights past it are unshadowed
+pub const MAX_SHADOW_MAPS: usize = 4;
+
+pub const SHADOW_MAP_SIZE: u32 = 2048;
+
+// region of the target a camera renders to, normalized to 0..1
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+            height: 1.0,
+        }
+    }
+}
+
+impl Viewport {
+    // x, y, width and height in pixels of a `width` by `height` target, clamped to it
+    #[inline]
+    pub fn pixels(&self, width: u32, height: u32) -> [f32; 4] {
+        let x = self.x.clamp(0.0, 1.0);
+        let y = self.y.clamp(0.0, 1.0);
+        let w = self.width.clamp(0.0, 1.0 - x) * width as f32;
+        let h = self.height.clamp(0.0, 1.0 - y) * height as f32;
+
+        [x * width as f32, y * height as f32, w.max(1.0), h.max(1.0)]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GridPlane {
+    XY,
+    XZ,
+    YZ,
+}
+
+impl GridPlane {
+    // index of the axis the plane is perpendicular to
+    #[inline]
+    pub fn normal_axis(self) -> u32 {
+        match self {
+            Self::XY => 2,
+            Self::XZ => 1,
+            Self::YZ => 0,
+        }
+    }
+}
+
+// operator mapping hdr color to the displayable range in combine.wgsl
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Tonemap {
+    None,
+    Reinhard,
+    Aces,
+    Uncharted2,
+}
+
+impl Default for Tonemap {
+    #[inline]
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl Tonemap {
+    pub const ALL: [Self; 4] = [Self::None, Self::Reinhard, Self::Aces, Self::Uncharted2];
+
+    #[inline]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::None => "None",
+            Self::Reinhard => "Reinhard",
+            Self::Aces => "ACES",
+            Self::Uncharted2 => "Uncharted 2",
         }
     }
 }
 
+// editor grid drawn over the scene, occluded by geometry
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Grid {
+    pub plane: GridPlane,
+    pub origin: Vec3,
+    pub spacing: f32,
+}
+
+// a background layer, `offset` shifts its texture in screen heights
+pub struct ParallaxLayer<'a> {
+    pub texture: &'a Arc<wgpu::TextureView>,
+    pub depth: f32,
+    pub offset: Vec2,
+}
+
+#[derive(Clone)]
 pub enum Renderable<'a> {
     Mesh {
         vertex_buffer: &'a wgpu::Buffer,
@@ -137,29 +484,179 @@ pub enum Renderable<'a> {
         indices: u32,
         albedo: Option<&'a Arc<wgpu::TextureView>>,
         emission: Option<&'a Arc<wgpu::TextureView>>,
+        normal: Option<&'a Arc<wgpu::TextureView>>,
         material: &'a PbrMaterial,
-        transform: Mat4,
+        transforms: Vec<Mat4>,
+        // id buffer value of each instance
+        ids: Vec<u32>,
+        wireframe: bool,
+        // local bounds of the mesh, renderables without are never culled
+        bounds: Option<(Aabb, BoundingSphere)>,
     },
 }
 
-pub enum UiRenderable<'a> {
+// `T` is only ever something other than a texture view in tests
+pub enum UiRenderable<'a, T = wgpu::TextureView> {
     Mesh {
         vertices: &'a [UiVertex],
         indices: &'a [u32],
-        texture: &'a Arc<wgpu::TextureView>,
+        texture: &'a Arc<T>,
+        clip_rect: Vec4,
+        layer: i32,
     },
 }
 
+impl<T> UiRenderable<'_, T> {
+    #[inline]
+    fn layer(&self) -> i32 {
+        match self {
+            Self::Mesh { layer, .. } => *layer,
+        }
+    }
+}
+
+// consecutive ui meshes sharing a texture, merged into one set of buffers
+struct UiBatch<'a, T = wgpu::TextureView> {
+    texture: &'a Arc<T>,
+    vertices: Vec<UiVertex>,
+    indices: Vec<u32>,
+    // index ranges and the scissor rect they're drawn with
+    draws: Vec<(std::ops::Range<u32>, [u32; 4])>,
+}
+
+impl UiBatch<'_> {
+    // the scissor rect as x, y, width and height for a clip rect of min and max corners,
+    // clamped to the target, `None` when nothing of it is left
+    fn scissor(clip_rect: Vec4, width: u32, height: u32) -> Option<[u32; 4]> {
+        let min_x = clip_rect.x.clamp(0.0, width as f32).round() as u32;
+        let min_y = clip_rect.y.clamp(0.0, height as f32).round() as u32;
+        let max_x = clip_rect.z.clamp(0.0, width as f32).round() as u32;
+        let max_y = clip_rect.w.clamp(0.0, height as f32).round() as u32;
+
+        if max_x <= min_x || max_y <= min_y {
+            return None;
+        }
+
+        Some([min_x, min_y, max_x - min_x, max_y - min_y])
+    }
+
+    // indices of `layers` in draw order, lowest layer first, stable so meshes within a layer
+    // keep the order they were submitted in
+    fn draw_order(layers: impl Iterator<Item = i32>) -> Vec<usize> {
+        let mut order = layers.enumerate().collect::<Vec<_>>();
+        order.sort_by_key(|&(_, layer)| layer);
+
+        order.into_iter().map(|(i, _)| i).collect()
+    }
+}
+
+impl<'a, T> UiBatch<'a, T> {
+    fn batch(renderables: &[UiRenderable<'a, T>], width: u32, height: u32) -> Vec<Self> {
+        let mut batches: Vec<Self> = Vec::new();
+
+        let order = UiBatch::draw_order(renderables.iter().map(UiRenderable::layer));
+
+        for renderable in order.into_iter().map(|i| &renderables[i]) {
+            match *renderable {
+                UiRenderable::Mesh {
+                    vertices,
+                    indices,
+                    texture,
+                    clip_rect,
+                    ..
+                } => {
+                    let scissor = match UiBatch::scissor(clip_rect, width, height) {
+                        Some(scissor) if !indices.is_empty() => scissor,
+                        _ => continue,
+                    };
+
+                    let batch = match batches.last_mut() {
+                        Some(batch) if Arc::ptr_eq(batch.texture, texture) => batch,
+                        _ => {
+                            batches.push(Self {
+                                texture,
+                                vertices: Vec::new(),
+                                indices: Vec::new(),
+                                draws: Vec::new(),
+                            });
+
+                            batches.last_mut().unwrap()
+                        }
+                    };
+
+                    let base_vertex = batch.vertices.len() as u32;
+                    let start = batch.indices.len() as u32;
+
+                    batch.vertices.extend_from_slice(vertices);
+                    batch
+                        .indices
+                        .extend(indices.iter().map(|index| index + base_vertex));
+
+                    let end = batch.indices.len() as u32;
+
+                    // meshes with the same clip rect share a draw
+                    match batch.draws.last_mut() {
+                        Some((range, last)) if *last == scissor && range.end == start => {
+                            range.end = end;
+                        }
+                        _ => batch.draws.push((start..end, scissor)),
+                    }
+                }
+            }
+        }
+
+        batches
+    }
+}
+
 pub struct Frame<'a> {
     renderables: Vec<Renderable<'a>>,
     ui_renderables: Vec<UiRenderable<'a>>,
     directional_lights: Vec<DirectionalLight>,
+    point_lights: Vec<PointLight>,
+    spot_lights: Vec<SpotLight>,
+    parallax_layers: Vec<ParallaxLayer<'a>>,
     pub aspect: f32,
+    pub viewport: Viewport,
     pub camera_matrix: Mat4,
     pub camera_position: Vec3,
     pub bloom: f32,
     pub ambient_color: Vec3,
     pub ambient_strength: f32,
+    pub environment: Option<&'a Arc<wgpu::TextureView>>,
+    pub environment_strength: f32,
+    // render meshes with baked lighting instead of lighting them
+    pub baked_lighting: bool,
+    // screen space reflections, marched in `ssr_steps` steps up to `ssr_max_distance`
+    pub ssr: bool,
+    pub ssr_steps: u32,
+    pub ssr_max_distance: f32,
+    pub grid: Option<Grid>,
+    pub debug_lines: Vec<DebugLine>,
+    // physical pixels per logical pixel, debug line widths are scaled by it
+    pub scale_factor: f32,
+    // blurs by distance from `focus_distance`, `aperture` scales the blur
+    pub dof: bool,
+    pub focus_distance: f32,
+    pub aperture: f32,
+    pub tonemap: Tonemap,
+    pub exposure: f32,
+    // contact hardening shadows, see `RenderSettings`
+    pub pcss: bool,
+    pub shadow_softness: f32,
+    pub light_size: f32,
+    // false when no shadow caster or light changed since the last frame, the renderer then
+    // keeps its previous shadow map
+    pub shadows_changed: bool,
+    // combined bounds of the scene, used by cameras picking their own clip planes
+    pub scene_bounds: Option<Aabb>,
+    // plane of the scene's mirror as (normal, distance), rendered before this frame
+    pub mirror: Option<Vec4>,
+    // world space plane meshes are clipped against, points with a negative distance are
+    // dropped, zero clips nothing
+    pub clip_plane: Vec4,
+    // written to the id buffer by meshes drawn after it is set, see `NodeId::pick_id`
+    pub node_id: u32,
 }
 
 impl<'a> Frame<'a> {
@@ -169,12 +666,80 @@ impl<'a> Frame<'a> {
             renderables: Vec::new(),
             ui_renderables: Vec::new(),
             directional_lights: Vec::new(),
+            point_lights: Vec::new(),
+            spot_lights: Vec::new(),
+            parallax_layers: Vec::new(),
             aspect: 0.0,
+            viewport: Viewport::default(),
             camera_matrix: Mat4::ZERO,
             camera_position: Vec3::ZERO,
             bloom: 0.0,
             ambient_color: Vec3::ONE,
             ambient_strength: 0.0,
+            environment: None,
+            environment_strength: 1.0,
+            baked_lighting: false,
+            ssr: false,
+            ssr_steps: 32,
+            ssr_max_distance: 10.0,
+            grid: None,
+            debug_lines: Vec::new(),
+            scale_factor: 1.0,
+            dof: false,
+            focus_distance: 10.0,
+            aperture: 0.5,
+            tonemap: Tonemap::None,
+            exposure: 1.0,
+            pcss: false,
+            shadow_softness: 1.0,
+            light_size: 0.5,
+            shadows_changed: true,
+            scene_bounds: None,
+            mirror: None,
+            clip_plane: Vec4::ZERO,
+            node_id: 0,
+        }
+    }
+
+    // the frame seen in a mirror along `plane`, only what's on the camera's side of it is drawn
+    // and the mirror itself is left out, as are overlays and post effects
+    pub fn reflected(&self, plane: Vec4) -> Self {
+        let reflection = reflection_matrix(plane);
+
+        let side = plane.truncate().dot(self.camera_position) + plane.w;
+        let clip_plane = if side < 0.0 { -plane } else { plane };
+
+        let renderables = self
+            .renderables
+            .iter()
+            .filter(|renderable| match renderable {
+                Renderable::Mesh { material, .. } => material.reflection <= 0.0,
+            })
+            .cloned()
+            .collect();
+
+        Self {
+            renderables,
+            directional_lights: self.directional_lights.clone(),
+            point_lights: self.point_lights.clone(),
+            spot_lights: self.spot_lights.clone(),
+            aspect: self.aspect,
+            viewport: self.viewport,
+            camera_matrix: self.camera_matrix * reflection,
+            camera_position: reflection.transform_point3(self.camera_position),
+            ambient_color: self.ambient_color,
+            ambient_strength: self.ambient_strength,
+            environment: self.environment,
+            environment_strength: self.environment_strength,
+            baked_lighting: self.baked_lighting,
+            scale_factor: self.scale_factor,
+            pcss: self.pcss,
+            shadow_softness: self.shadow_softness,
+            light_size: self.light_size,
+            shadows_changed: self.shadows_changed,
+            scene_bounds: self.scene_bounds,
+            clip_plane,
+            ..Self::new()
         }
     }
 
@@ -183,12 +748,34 @@ impl<'a> Frame<'a> {
         self.directional_lights.push(light);
     }
 
+    #[inline]
+    pub fn add_point_light(&mut self, light: PointLight) {
+        self.point_lights.push(light);
+    }
+
+    #[inline]
+    pub fn add_spot_light(&mut self, light: SpotLight) {
+        self.spot_lights.push(light);
+    }
+
+    #[inline]
+    pub fn add_parallax_layer(&mut self, layer: ParallaxLayer<'a>) {
+        self.parallax_layers.push(layer);
+    }
+
+    #[inline]
+    pub fn directional_lights(&self) -> &[DirectionalLight] {
+        &self.directional_lights
+    }
+
     #[inline]
     pub fn render_ui_mesh(&mut self, mesh: &'a UiMesh, texture: &'a Arc<wgpu::TextureView>) {
         self.ui_renderables.push(UiRenderable::Mesh {
             vertices: &mesh.vertices,
             indices: &mesh.indices,
             texture,
+            clip_rect: mesh.clip_rect,
+            layer: mesh.layer,
         });
     }
 
@@ -199,8 +786,44 @@ impl<'a> Frame<'a> {
         material: &'a PbrMaterial,
         albedo: Option<&'a Arc<wgpu::TextureView>>,
         emission: Option<&'a Arc<wgpu::TextureView>>,
+        normal: Option<&'a Arc<wgpu::TextureView>>,
         transform: Mat4,
     ) {
+        self.render_mesh_instanced(mesh, material, albedo, emission, normal, vec![transform]);
+    }
+
+    // draws `mesh` once per transform in a single instanced draw
+    #[inline]
+    pub fn render_mesh_instanced(
+        &mut self,
+        mesh: &'a Mesh,
+        material: &'a PbrMaterial,
+        albedo: Option<&'a Arc<wgpu::TextureView>>,
+        emission: Option<&'a Arc<wgpu::TextureView>>,
+        normal: Option<&'a Arc<wgpu::TextureView>>,
+        transforms: Vec<Mat4>,
+    ) {
+        let ids = vec![self.node_id; transforms.len()];
+
+        self.render_mesh_batch(mesh, material, albedo, emission, normal, transforms, ids);
+    }
+
+    // like `render_mesh_instanced`, with instances of different nodes given their own ids
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_mesh_batch(
+        &mut self,
+        mesh: &'a Mesh,
+        material: &'a PbrMaterial,
+        albedo: Option<&'a Arc<wgpu::TextureView>>,
+        emission: Option<&'a Arc<wgpu::TextureView>>,
+        normal: Option<&'a Arc<wgpu::TextureView>>,
+        transforms: Vec<Mat4>,
+        ids: Vec<u32>,
+    ) {
+        if transforms.is_empty() {
+            return;
+        }
+
         let indices = mesh.len_indices();
         let (vertex_buffer, index_buffer) = mesh.get_buffers().expect("mesh buffers don't exist");
 
@@ -210,19 +833,200 @@ impl<'a> Frame<'a> {
             indices,
             albedo,
             emission,
+            normal,
             material,
-            transform,
+            transforms,
+            ids,
+            wireframe: false,
+            bounds: mesh.culling_bounds(),
+        });
+    }
+
+    #[inline]
+    pub fn draw_line(&mut self, start: Vec3, end: Vec3, color: Vec4, width: f32) {
+        self.debug_lines.push(DebugLine {
+            start,
+            width,
+            end,
+            _pad0: 0.0,
+            color,
+        });
+    }
+
+    // a square marker `size` logical pixels across
+    #[inline]
+    pub fn draw_point(&mut self, position: Vec3, color: Vec4, size: f32) {
+        self.draw_line(position, position, color, size);
+    }
+
+    // draws the edges of `mesh` on top of it
+    #[inline]
+    pub fn render_wireframe(&mut self, mesh: &'a Mesh, transform: Mat4) {
+        let indices = mesh.len_indices();
+        let (vertex_buffer, index_buffer) = mesh.get_buffers().expect("mesh buffers don't exist");
+
+        self.renderables.push(Renderable::Mesh {
+            vertex_buffer,
+            index_buffer,
+            indices,
+            albedo: None,
+            emission: None,
+            normal: None,
+            material: &PbrMaterial::WIREFRAME,
+            transforms: vec![transform],
+            ids: vec![self.node_id],
+            wireframe: true,
+            bounds: mesh.culling_bounds(),
         });
     }
 }
 
+// mirrors points across `plane`, given as (normal, distance) with a unit normal
+fn reflection_matrix(plane: Vec4) -> Mat4 {
+    let normal = plane.truncate();
+
+    let linear = Mat3::IDENTITY
+        - Mat3::from_cols(normal * normal.x, normal * normal.y, normal * normal.z) * 2.0;
+
+    let mut matrix = Mat4::from_mat3(linear);
+    matrix.w_axis = (normal * -2.0 * plane.w).extend(1.0);
+
+    matrix
+}
+
+// orthographic projection looking along `direction` that covers all of `bounds`
+fn light_matrix(direction: Vec3, bounds: &Aabb) -> Mat4 {
+    let direction = direction.normalize();
+    let center = (bounds.min + bounds.max) / 2.0;
+    let radius = (bounds.size().length() / 2.0).max(0.01);
+
+    let up = if direction.y.abs() > 0.99 {
+        Vec3::Z
+    } else {
+        Vec3::Y
+    };
+
+    let view = Mat4::look_at_rh(center - direction * radius, center, up);
+    let projection = Mat4::orthographic_rh(-radius, radius, -radius, radius, 0.0, radius * 2.0);
+
+    projection * view
+}
+
+// level 1 is the plain sampler, higher levels filter linearly since anisotropy needs it,
+// wgpu ignores the clamp on devices without anisotropic filtering
+fn mesh_sampler(instance: &Instance, anisotropy: u32) -> wgpu::Sampler {
+    let filter = if anisotropy > 1 {
+        wgpu::FilterMode::Linear
+    } else {
+        wgpu::FilterMode::Nearest
+    };
+
+    instance.device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("mesh sampler"),
+        address_mode_u: wgpu::AddressMode::Repeat,
+        address_mode_v: wgpu::AddressMode::Repeat,
+        mag_filter: filter,
+        min_filter: filter,
+        mipmap_filter: filter,
+        anisotropy_clamp: std::num::NonZeroU8::new(anisotropy as u8).filter(|_| anisotropy > 1),
+        ..Default::default()
+    })
+}
+
+// inverse transpose of `model`, only the upper 3x3 is meaningful, singular models are kept
+#[inline]
+pub fn normal_matrix(model: Mat4) -> Mat4 {
+    if model.determinant().abs() <= f32::EPSILON * f32::EPSILON {
+        return model;
+    }
+
+    model.inverse().transpose()
+}
+
+fn wireframe_pipeline(instance: &Instance) -> Option<wgpu::RenderPipeline> {
+    if instance
+        .device
+        .features()
+        .contains(wgpu::Features::NON_FILL_POLYGON_MODE)
+    {
+        Some(separate_pipeline(instance, true))
+    } else {
+        log::warn!("line polygon mode isn't supported, wireframe overlays are disabled");
+        None
+    }
+}
+
+// a 1x1 srgb texture filled with `pixel`
+fn pixel_texture(
+    instance: &Instance,
+    label: &str,
+    format: wgpu::TextureFormat,
+    pixel: [u8; 4],
+) -> Arc<wgpu::TextureView> {
+    let texture = instance.device.create_texture_with_data(
+        &instance.queue,
+        &wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            format,
+            dimension: wgpu::TextureDimension::D2,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: wgpu::TextureUsage::COPY_DST | wgpu::TextureUsage::SAMPLED,
+        },
+        &pixel,
+    );
+
+    Arc::new(texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some(label),
+        aspect: wgpu::TextureAspect::All,
+        format: None,
+        dimension: None,
+        base_mip_level: 0,
+        mip_level_count: None,
+        base_array_layer: 0,
+        array_layer_count: None,
+    }))
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderStats {
+    pub draw_calls: u32,
+    pub triangles: u32,
+    pub culled: u32,
+    pub ui_draws: u32,
+    // bind groups created while rendering, zero in steady state frames
+    pub bind_groups: usize,
+}
+
+impl std::ops::AddAssign for RenderStats {
+    #[inline]
+    fn add_assign(&mut self, other: Self) {
+        self.draw_calls += other.draw_calls;
+        self.triangles += other.triangles;
+        self.culled += other.culled;
+        self.ui_draws += other.ui_draws;
+        self.bind_groups += other.bind_groups;
+    }
+}
+
 pub struct UiData {
     pub bindings: BindGroup,
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
+    // lengths the buffers have room for, they're only recreated when a frame doesn't fit
+    vertex_capacity: usize,
+    index_capacity: usize,
 }
 
 impl UiData {
+    // smallest capacity a buffer is created with, most frames of a small ui then fit right away
+    const MIN_CAPACITY: usize = 1024;
+
     pub fn new(
         instance: &Instance,
         vertices: &[UiVertex],
@@ -231,38 +1035,51 @@ impl UiData {
         width: u32,
         height: u32,
     ) -> Self {
-        let mut bindings = BindGroup::new(instance);
-
-        bindings.bind_uniform(0, &Vec2::new(width as f32, height as f32));
-        bindings.bind_texture(
-            1,
-            texture,
-            wgpu::TextureSampleType::Float { filterable: true },
-        );
-
-        bindings.generate();
+        let vertex_capacity = Self::grow(vertices.len());
+        let index_capacity = Self::grow(indices.len());
+
+        let mut data = Self {
+            bindings: BindGroup::new(instance),
+            vertex_buffer: Self::buffer::<UiVertex>(
+                instance,
+                "ui vertex buffer",
+                wgpu::BufferUsage::VERTEX,
+                vertex_capacity,
+            ),
+            index_buffer: Self::buffer::<u32>(
+                instance,
+                "ui index buffer",
+                wgpu::BufferUsage::INDEX,
+                index_capacity,
+            ),
+            vertex_capacity,
+            index_capacity,
+        };
 
-        let vertex_buffer = instance
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("ui vertex buffer"),
-                contents: cast_slice(vertices),
-                usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
-            });
+        data.update(instance, vertices, indices, texture, width, height);
 
-        let index_buffer = instance
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("ui vertex buffer"),
-                contents: cast_slice(indices),
-                usage: wgpu::BufferUsage::INDEX | wgpu::BufferUsage::COPY_DST,
-            });
+        data
+    }
 
-        Self {
-            bindings,
-            vertex_buffer,
-            index_buffer,
-        }
+    // capacity for `len` elements with headroom, so a slowly growing ui doesn't reallocate
+    // every frame
+    #[inline]
+    fn grow(len: usize) -> usize {
+        (len + len / 2).max(Self::MIN_CAPACITY)
+    }
+
+    fn buffer<T>(
+        instance: &Instance,
+        label: &str,
+        usage: wgpu::BufferUsage,
+        capacity: usize,
+    ) -> wgpu::Buffer {
+        instance.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: (capacity * std::mem::size_of::<T>()) as u64,
+            usage: usage | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        })
     }
 
     pub fn update(
@@ -284,22 +1101,202 @@ impl UiData {
 
         self.bindings.generate();
 
-        self.vertex_buffer =
-            instance
-                .device
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("ui vertex buffer"),
-                    contents: cast_slice(vertices),
-                    usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
-                });
+        if vertices.len() > self.vertex_capacity {
+            self.vertex_capacity = Self::grow(vertices.len());
+            self.vertex_buffer = Self::buffer::<UiVertex>(
+                instance,
+                "ui vertex buffer",
+                wgpu::BufferUsage::VERTEX,
+                self.vertex_capacity,
+            );
+        }
 
-        self.index_buffer = instance
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("ui vertex buffer"),
-                contents: cast_slice(indices),
-                usage: wgpu::BufferUsage::INDEX | wgpu::BufferUsage::COPY_DST,
-            });
+        if indices.len() > self.index_capacity {
+            self.index_capacity = Self::grow(indices.len());
+            self.index_buffer = Self::buffer::<u32>(
+                instance,
+                "ui index buffer",
+                wgpu::BufferUsage::INDEX,
+                self.index_capacity,
+            );
+        }
+
+        instance
+            .queue
+            .write_buffer(&self.vertex_buffer, 0, cast_slice(vertices));
+        instance
+            .queue
+            .write_buffer(&self.index_buffer, 0, cast_slice(indices));
+    }
+}
+
+type FenceFuture = Pin<Box<dyn Future<Output = Result<(), wgpu::BufferAsyncError>> + Send>>;
+
+// a buffer kept across frames and written in place, only recreated when the data outgrows it
+pub struct GrowingBuffer {
+    label: &'static str,
+    usage: wgpu::BufferUsage,
+    buffer: Option<wgpu::Buffer>,
+    capacity: wgpu::BufferAddress,
+}
+
+impl GrowingBuffer {
+    const MIN_CAPACITY: wgpu::BufferAddress = 1024;
+
+    #[inline]
+    pub fn new(label: &'static str, usage: wgpu::BufferUsage) -> Self {
+        Self {
+            label,
+            usage,
+            buffer: None,
+            capacity: 0,
+        }
+    }
+
+    // writes `data` to the start of the buffer, growing it with headroom when it doesn't fit
+    pub fn write(&mut self, instance: &Instance, data: &[u8]) -> &wgpu::Buffer {
+        let len = data.len() as wgpu::BufferAddress;
+
+        if self.buffer.is_none() || len > self.capacity {
+            let align = wgpu::COPY_BUFFER_ALIGNMENT;
+            self.capacity = (len + len / 2).max(Self::MIN_CAPACITY).div_ceil(align) * align;
+
+            self.buffer = Some(instance.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(self.label),
+                size: self.capacity,
+                usage: self.usage | wgpu::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+
+        let buffer = self.buffer.as_ref().unwrap();
+
+        if !data.is_empty() {
+            instance.queue.write_buffer(buffer, 0, data);
+        }
+
+        buffer
+    }
+
+    // the buffer as last written, `None` before the first write
+    #[inline]
+    pub fn buffer(&self) -> Option<&wgpu::Buffer> {
+        self.buffer.as_ref()
+    }
+}
+
+// uniform and buffer sets for one view of a frame, uniform writes all land before the
+// submission runs so views rendered together can't share them
+pub struct ViewData {
+    pub light_uniform_bindings: BindGroup,
+    pub bloom_uniform_bindings: BindGroup,
+    pub bloom_h_uniform_bindings: BindGroup,
+    pub ssr_uniform_bindings: BindGroup,
+    pub grid_uniform_bindings: BindGroup,
+    pub debug_line_uniform_bindings: BindGroup,
+    pub debug_line_buffer: GrowingBuffer,
+    // models and normal matrices of every mesh instance, shared by the separate and shadow passes
+    pub instance_buffer: GrowingBuffer,
+    pub combine_uniform_bindings: BindGroup,
+    pub mesh_bindings: Vec<(BindGroup, BindGroup)>,
+    // light space matrix of each shadow map
+    pub shadow_bindings: Vec<BindGroup>,
+    pub parallax_bindings: Vec<(BindGroup, BindGroup)>,
+    pub ui_data: Vec<UiData>,
+}
+
+impl ViewData {
+    pub fn new(instance: &Instance) -> Self {
+        Self {
+            light_uniform_bindings: BindGroup::new(instance),
+            bloom_uniform_bindings: BindGroup::new(instance),
+            bloom_h_uniform_bindings: BindGroup::new(instance),
+            ssr_uniform_bindings: BindGroup::new(instance),
+            grid_uniform_bindings: BindGroup::new(instance),
+            debug_line_uniform_bindings: BindGroup::new(instance),
+            debug_line_buffer: GrowingBuffer::new("debug line buffer", wgpu::BufferUsage::VERTEX),
+            instance_buffer: GrowingBuffer::new("instance buffer", wgpu::BufferUsage::VERTEX),
+            combine_uniform_bindings: BindGroup::new(instance),
+            mesh_bindings: Vec::new(),
+            shadow_bindings: (0..MAX_SHADOW_MAPS)
+                .map(|_| BindGroup::new(instance))
+                .collect(),
+            parallax_bindings: Vec::new(),
+            ui_data: Vec::new(),
+        }
+    }
+}
+
+// per frame view sets, rotated so a frame never writes buffers the gpu might still be reading
+// from the previous frames in flight
+pub struct FrameData {
+    pub fence: wgpu::Buffer,
+    fence_future: Option<FenceFuture>,
+    pub views: Vec<ViewData>,
+}
+
+impl FrameData {
+    pub fn new(instance: &Instance) -> Self {
+        let fence = instance.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("frame fence"),
+            size: 4,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            fence,
+            fence_future: None,
+            views: Vec::new(),
+        }
+    }
+
+    // blocks until the gpu has finished the last submission that used this frame
+    pub fn wait(&mut self, instance: &Instance) {
+        if let Some(mut future) = self.fence_future.take() {
+            instance.device.poll(wgpu::Maintain::Poll);
+
+            // usually the frame finished while the later ones were recorded, only block when
+            // it hasn't
+            let result = match (&mut future).now_or_never() {
+                Some(result) => result,
+                None => {
+                    instance.device.poll(wgpu::Maintain::Wait);
+                    futures::executor::block_on(future)
+                }
+            };
+
+            if let Err(err) = result {
+                log::warn!("failed to map frame fence: {:?}", err);
+            }
+
+            self.fence.unmap();
+        }
+    }
+
+    #[inline]
+    pub fn signal(&mut self) {
+        let future = self.fence.slice(..).map_async(wgpu::MapMode::Read);
+        self.fence_future = Some(Box::pin(future));
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RenderPasses {
+    pub shadow: bool,
+    pub light: bool,
+    pub bloom: bool,
+    pub combine: bool,
+}
+
+impl Default for RenderPasses {
+    fn default() -> Self {
+        Self {
+            shadow: true,
+            light: true,
+            bloom: true,
+            combine: true,
+        }
     }
 }
 
@@ -307,100 +1304,579 @@ pub struct Renderer {
     pub g_buffer: GBuffer,
     pub pipelines: RenderPipelines,
     pub default_texture: Arc<wgpu::TextureView>,
-    pub light_uniform_bindings: BindGroup,
+    pub default_normal_texture: Arc<wgpu::TextureView>,
+    pub fence_source: wgpu::Buffer,
+    pub frames: Vec<FrameData>,
+    pub frame_index: usize,
+    pub passes: RenderPasses,
+    pub ibl: Ibl,
+    // outputs premultiplied alpha for a transparent window, empty areas stay see-through
+    pub transparent: bool,
+    srgb_target: bool,
+    // float targets keep values above 1 when no tonemap is applied
+    hdr_target: bool,
+    // the shadow map has to be redrawn whatever the frame says, it was recreated or skipped
+    shadows_stale: bool,
     pub light_texture_bindings: BindGroup,
-    pub bloom_uniform_bindings: BindGroup,
-    pub bloom_h_uniform_bindings: BindGroup,
     pub bloom_texture_bindings: BindGroup,
     pub bloom_h_texture_bindings: BindGroup,
+    pub ssr_texture_bindings: BindGroup,
+    pub grid_texture_bindings: BindGroup,
     pub combine_texture_bindings: BindGroup,
     pub sampler_bindings: BindGroup,
-    pub mesh_bindings: Vec<(BindGroup, BindGroup)>,
-    pub ui_data: Vec<UiData>,
+    // repeating samplers for mesh textures, one per anisotropy level, so scrolled uvs wrap
+    pub mesh_samplers: Vec<Arc<wgpu::Sampler>>,
+    pub mesh_sampler_bindings: Vec<BindGroup>,
+    // reflection drawn for a frame's mirror, created when first needed
+    pub mirror: Option<Arc<wgpu::TextureView>>,
+    sc_format: wgpu::TextureFormat,
     pub width: u32,
     pub height: u32,
 }
 
 impl Renderer {
+    pub const DEFAULT_FRAMES_IN_FLIGHT: usize = 2;
+
     pub fn new(
         instance: &Instance,
         sc_format: wgpu::TextureFormat,
         width: u32,
         height: u32,
     ) -> Self {
-        let texture = instance.device.create_texture_with_data(
-            &instance.queue,
-            &wgpu::TextureDescriptor {
-                label: Some("default texture"),
-                size: wgpu::Extent3d {
-                    width: 1,
-                    height: 1,
-                    depth_or_array_layers: 1,
-                },
-                format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                dimension: wgpu::TextureDimension::D2,
-                mip_level_count: 1,
-                sample_count: 1,
-                usage: wgpu::TextureUsage::COPY_DST | wgpu::TextureUsage::SAMPLED,
-            },
-            &[255u8; 4],
-        );
+        let fence_source = instance
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("fence source"),
+                contents: &[0u8; 4],
+                usage: wgpu::BufferUsage::COPY_SRC,
+            });
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor {
-            label: Some("default texture view"),
-            aspect: wgpu::TextureAspect::All,
-            format: None,
-            dimension: None,
-            base_mip_level: 0,
-            mip_level_count: None,
-            base_array_layer: 0,
-            array_layer_count: None,
-        });
+        let pipelines = RenderPipelines::new(instance, sc_format);
 
-        Self {
+        let mut renderer = Self {
             g_buffer: GBuffer::new(instance, width, height),
-            pipelines: RenderPipelines::new(instance, sc_format),
-            default_texture: Arc::new(view),
-            light_uniform_bindings: BindGroup::new(instance),
+            ibl: Ibl::new(instance, &pipelines),
+            pipelines,
+            default_texture: pixel_texture(
+                instance,
+                "default texture",
+                wgpu::TextureFormat::Rgba8UnormSrgb,
+                [255; 4],
+            ),
+            // flat tangent space normal, linear like the normal maps
+            default_normal_texture: pixel_texture(
+                instance,
+                "default normal texture",
+                wgpu::TextureFormat::Rgba8Unorm,
+                [128, 128, 255, 255],
+            ),
+            fence_source,
+            frames: (0..Self::DEFAULT_FRAMES_IN_FLIGHT)
+                .map(|_| FrameData::new(instance))
+                .collect(),
+            frame_index: 0,
+            passes: RenderPasses::default(),
+            transparent: false,
+            srgb_target: sc_format.describe().srgb,
+            hdr_target: sc_format == Swapchain::HDR_FORMAT,
+            shadows_stale: true,
             light_texture_bindings: BindGroup::new(instance),
-            bloom_uniform_bindings: BindGroup::new(instance),
-            bloom_h_uniform_bindings: BindGroup::new(instance),
             bloom_texture_bindings: BindGroup::new(instance),
             bloom_h_texture_bindings: BindGroup::new(instance),
+            ssr_texture_bindings: BindGroup::new(instance),
+            grid_texture_bindings: BindGroup::new(instance),
             combine_texture_bindings: BindGroup::new(instance),
             sampler_bindings: BindGroup::new(instance),
-            mesh_bindings: Vec::new(),
-            ui_data: Vec::new(),
+            mesh_samplers: PbrMaterial::ANISOTROPY_LEVELS
+                .iter()
+                .map(|&level| Arc::new(mesh_sampler(instance, level)))
+                .collect(),
+            mesh_sampler_bindings: PbrMaterial::ANISOTROPY_LEVELS
+                .iter()
+                .map(|_| BindGroup::new(instance))
+                .collect(),
+            mirror: None,
+            sc_format,
             width,
             height,
+        };
+
+        renderer.bind_g_buffer();
+
+        renderer
+    }
+
+    // binds everything that only changes with the g buffer, so frames reuse the bind groups
+    fn bind_g_buffer(&mut self) {
+        self.sampler_bindings
+            .bind_sampler(0, &self.g_buffer.sampler);
+        self.sampler_bindings.generate();
+
+        for (bindings, sampler) in self
+            .mesh_sampler_bindings
+            .iter_mut()
+            .zip(&self.mesh_samplers)
+        {
+            bindings.bind_sampler(0, sampler);
+            bindings.generate();
+        }
+
+        self.light_texture_bindings.bind_texture(
+            0,
+            &self.g_buffer.depth,
+            wgpu::TextureSampleType::Depth,
+        );
+        self.light_texture_bindings.bind_texture(
+            1,
+            &self.g_buffer.position,
+            wgpu::TextureSampleType::Float { filterable: false },
+        );
+        self.light_texture_bindings.bind_texture(
+            2,
+            &self.g_buffer.normal,
+            wgpu::TextureSampleType::Float { filterable: false },
+        );
+
+        // bindings 4 and up, after the irradiance
+        for (i, shadow) in self.g_buffer.shadows.iter().enumerate() {
+            self.light_texture_bindings.bind_texture(
+                4 + i as u32,
+                shadow,
+                wgpu::TextureSampleType::Depth,
+            );
         }
+
+        self.light_texture_bindings
+            .bind_comparison_sampler(8, &self.g_buffer.shadow_sampler);
+
+        // the cubes are refiltered in place, so the views never change
+        self.light_texture_bindings
+            .bind_cube_texture(3, &self.ibl.irradiance);
+        self.light_texture_bindings
+            .bind_cube_texture(9, &self.ibl.specular);
+        self.light_texture_bindings.bind_texture(
+            10,
+            &self.ibl.brdf,
+            wgpu::TextureSampleType::Float { filterable: true },
+        );
+        // roughness is in the albedo's alpha
+        self.light_texture_bindings.bind_texture(
+            11,
+            &self.g_buffer.albedo,
+            wgpu::TextureSampleType::Float { filterable: true },
+        );
+        self.light_texture_bindings
+            .bind_sampler(12, &self.ibl.sampler);
+
+        self.ssr_texture_bindings.bind_texture(
+            0,
+            &self.g_buffer.position,
+            wgpu::TextureSampleType::Float { filterable: false },
+        );
+        self.ssr_texture_bindings.bind_texture(
+            1,
+            &self.g_buffer.normal,
+            wgpu::TextureSampleType::Float { filterable: false },
+        );
+        self.ssr_texture_bindings.bind_texture(
+            2,
+            &self.g_buffer.albedo,
+            wgpu::TextureSampleType::Float { filterable: true },
+        );
+        self.ssr_texture_bindings.bind_texture(
+            3,
+            &self.g_buffer.emission,
+            wgpu::TextureSampleType::Float { filterable: true },
+        );
+        self.ssr_texture_bindings.bind_texture(
+            4,
+            &self.g_buffer.light,
+            wgpu::TextureSampleType::Float { filterable: true },
+        );
+
+        self.bloom_texture_bindings.bind_texture(
+            0,
+            &self.g_buffer.emission,
+            wgpu::TextureSampleType::Float { filterable: true },
+        );
+
+        self.bloom_h_texture_bindings.bind_texture(
+            0,
+            &self.g_buffer.bloom,
+            wgpu::TextureSampleType::Float { filterable: true },
+        );
+
+        self.combine_texture_bindings.bind_texture(
+            0,
+            &self.g_buffer.depth,
+            wgpu::TextureSampleType::Depth,
+        );
+        self.combine_texture_bindings.bind_texture(
+            1,
+            &self.g_buffer.position,
+            wgpu::TextureSampleType::Float { filterable: false },
+        );
+        self.combine_texture_bindings.bind_texture(
+            2,
+            &self.g_buffer.normal,
+            wgpu::TextureSampleType::Float { filterable: false },
+        );
+        self.combine_texture_bindings.bind_texture(
+            3,
+            &self.g_buffer.albedo,
+            wgpu::TextureSampleType::Float { filterable: true },
+        );
+        self.combine_texture_bindings.bind_texture(
+            4,
+            &self.g_buffer.emission,
+            wgpu::TextureSampleType::Float { filterable: true },
+        );
+        self.combine_texture_bindings.bind_texture(
+            5,
+            &self.g_buffer.light,
+            wgpu::TextureSampleType::Float { filterable: true },
+        );
+        self.combine_texture_bindings.bind_texture(
+            6,
+            &self.g_buffer.reflection,
+            wgpu::TextureSampleType::Float { filterable: true },
+        );
+
+        self.grid_texture_bindings.bind_texture(
+            0,
+            &self.g_buffer.depth,
+            wgpu::TextureSampleType::Depth,
+        );
+        self.grid_texture_bindings.bind_texture(
+            1,
+            &self.g_buffer.position,
+            wgpu::TextureSampleType::Float { filterable: false },
+        );
+    }
+
+    #[inline]
+    pub fn frames_in_flight(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn set_frames_in_flight(&mut self, instance: &Instance, frames_in_flight: usize) {
+        let frames_in_flight = frames_in_flight.max(1);
+
+        for frame in &mut self.frames {
+            frame.wait(instance);
+        }
+
+        self.frames
+            .resize_with(frames_in_flight, || FrameData::new(instance));
+        self.frame_index %= frames_in_flight;
     }
 
     pub fn resize(&mut self, instance: &Instance, width: u32, height: u32) {
         self.g_buffer = GBuffer::new(instance, width, height);
+        self.bind_g_buffer();
+        self.shadows_stale = true;
+        self.mirror = None;
         self.width = width;
         self.height = height;
     }
 
-    pub fn render_frame(
+    // the id buffer value at `pixel` in the last rendered frames, waits for the gpu to finish them
+    pub fn read_id(&self, instance: &Instance, pixel: UVec2) -> Option<u32> {
+        if pixel.x >= self.width || pixel.y >= self.height {
+            return None;
+        }
+
+        let buffer = instance.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("id read buffer"),
+            size: 4,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = instance
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("id read encoder"),
+            });
+
+        // a single row needs no row alignment
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &self.g_buffer.ids,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: pixel.x,
+                    y: pixel.y,
+                    z: 0,
+                },
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: None,
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        instance.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let future = slice.map_async(wgpu::MapMode::Read);
+        instance.device.poll(wgpu::Maintain::Wait);
+        futures::executor::block_on(future).ok()?;
+
+        let id = cast_slice::<_, u32>(&slice.get_mapped_range())[0];
+        buffer.unmap();
+
+        Some(id)
+    }
+
+    // renders `frames` like `render_frames` into an offscreen texture at the renderer's size and
+    // reads it back, only 8 bit output formats can be captured
+    pub fn capture_frames(
         &mut self,
         instance: &Instance,
-        target: &wgpu::TextureView,
-        frame: Frame<'_>,
-    ) {
-        let shadow_pass = wgpu::RenderPassDescriptor {
-            label: Some("shadow pass"),
-            color_attachments: &[],
-            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: &self.g_buffer.shadow,
-                depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
-                    store: true,
-                }),
-                stencil_ops: None,
-            }),
+        frames: Vec<Frame<'_>>,
+    ) -> anyhow::Result<image::RgbaImage> {
+        let bgra = match self.sc_format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            format => anyhow::bail!("can't capture {:?} output", format),
+        };
+
+        let (width, height) = (self.width, self.height);
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
         };
 
+        let texture = instance.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("capture texture"),
+            size,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.sc_format,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+        });
+        let view = texture.create_view(&Default::default());
+
+        let views = frames
+            .into_iter()
+            .map(|frame| (&view, frame))
+            .collect::<Vec<_>>();
+
+        self.render_frames(instance, &views);
+
+        // buffer rows have to be a multiple of 256 bytes, the padding is dropped when unpacking
+        let row_len = width as usize * 4;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize;
+        let padded_row_len = row_len.div_ceil(align) * align;
+
+        let buffer = instance.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("capture buffer"),
+            size: (padded_row_len * height as usize) as u64,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = instance
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("capture encoder"),
+            });
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(padded_row_len as u32),
+                    rows_per_image: None,
+                },
+            },
+            size,
+        );
+
+        instance.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let future = slice.map_async(wgpu::MapMode::Read);
+        instance.device.poll(wgpu::Maintain::Wait);
+        futures::executor::block_on(future)?;
+
+        let mut pixels = Vec::with_capacity(row_len * height as usize);
+
+        for row in slice.get_mapped_range().chunks_exact(padded_row_len) {
+            pixels.extend_from_slice(&row[..row_len]);
+        }
+
+        buffer.unmap();
+
+        if bgra {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        image::RgbaImage::from_raw(width, height, pixels)
+            .ok_or_else(|| anyhow::anyhow!("captured {}x{} pixels don't fit", width, height))
+    }
+
+    // renders every view in order with one command encoder and submission, the g buffer is
+    // shared between them, so all views render at the renderer's size
+    pub fn render_frames(
+        &mut self,
+        instance: &Instance,
+        views: &[(&wgpu::TextureView, Frame<'_>)],
+    ) -> RenderStats {
+        let mut stats = RenderStats::default();
+
+        let frame_data = &mut self.frames[self.frame_index];
+        frame_data.wait(instance);
+
+        // mirrors are rendered as views of their own
+        let view_count = views.len() + views.iter().filter(|(_, f)| f.mirror.is_some()).count();
+
+        if frame_data.views.len() < view_count {
+            frame_data
+                .views
+                .resize_with(view_count, || ViewData::new(instance));
+        }
+
+        let bind_groups = bindings::generated_bind_groups();
+
+        let mut encoder = instance
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("render encoder"),
+            });
+
+        // pixels outside of every view's viewport hold no node
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("id clear pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: &self.g_buffer.ids_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+
+        let mut view_index = 0;
+
+        for (i, (target, frame)) in views.iter().enumerate() {
+            if let Some(plane) = frame.mirror {
+                let mirror = self.mirror_target(instance);
+                let reflected = frame.reflected(plane);
+
+                stats += self.render_view(
+                    instance,
+                    &mut encoder,
+                    view_index,
+                    &mirror,
+                    &reflected,
+                    true,
+                );
+                view_index += 1;
+            }
+
+            // views drawn onto a target an earlier view already drew to keep its contents,
+            // for split screen
+            let clear = !views[..i]
+                .iter()
+                .any(|(other, _)| std::ptr::eq(*other, *target));
+
+            stats += self.render_view(instance, &mut encoder, view_index, target, frame, clear);
+            self.copy_view_ids(&mut encoder, frame.viewport);
+            view_index += 1;
+        }
+
+        let frame_data = &mut self.frames[self.frame_index];
+
+        encoder.copy_buffer_to_buffer(&self.fence_source, 0, &frame_data.fence, 0, 4);
+
+        instance.queue.submit(std::iter::once(encoder.finish()));
+
+        frame_data.signal();
+        self.frame_index = (self.frame_index + 1) % self.frames.len();
+
+        stats.bind_groups = bindings::generated_bind_groups() - bind_groups;
+
+        stats
+    }
+
+    // keeps the view's own part of the id buffer, so every view of a split screen can be picked
+    fn copy_view_ids(&self, encoder: &mut wgpu::CommandEncoder, viewport: Viewport) {
+        let [x, y, width, height] = viewport.pixels(self.width, self.height);
+        let (x, y) = (x as u32, y as u32);
+        let width = (width as u32).min(self.width.saturating_sub(x));
+        let height = (height as u32).min(self.height.saturating_sub(y));
+
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let origin = wgpu::Origin3d { x, y, z: 0 };
+
+        encoder.copy_texture_to_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.g_buffer.id_texture,
+                mip_level: 0,
+                origin,
+            },
+            wgpu::ImageCopyTexture {
+                texture: &self.g_buffer.ids,
+                mip_level: 0,
+                origin,
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    #[inline]
+    fn mirror_target(&mut self, instance: &Instance) -> Arc<wgpu::TextureView> {
+        let (format, width, height) = (self.sc_format, self.width, self.height);
+
+        self.mirror
+            .get_or_insert_with(|| render_texture(instance, format, width, height))
+            .clone()
+    }
+
+    fn render_view(
+        &mut self,
+        instance: &Instance,
+        encoder: &mut wgpu::CommandEncoder,
+        view_index: usize,
+        target: &wgpu::TextureView,
+        frame: &Frame<'_>,
+        clear: bool,
+    ) -> RenderStats {
+        let mut stats = RenderStats::default();
+
+        let view_data = &mut self.frames[self.frame_index].views[view_index];
+
         let separate_pass = wgpu::RenderPassDescriptor {
             label: Some("separate pass"),
             color_attachments: &[
@@ -436,6 +1912,14 @@ impl Renderer {
                         store: true,
                     },
                 },
+                wgpu::RenderPassColorAttachment {
+                    view: &self.g_buffer.id,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                },
             ],
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                 view: &self.g_buffer.depth,
@@ -496,13 +1980,30 @@ impl Renderer {
             depth_stencil_attachment: None,
         };
 
+        let ssr_pass = wgpu::RenderPassDescriptor {
+            label: Some("ssr pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: &self.g_buffer.reflection,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        };
+
         let combine_pass = wgpu::RenderPassDescriptor {
             label: Some("combine pass"),
             color_attachments: &[wgpu::RenderPassColorAttachment {
                 view: target,
                 resolve_target: None,
                 ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    load: if clear {
+                        wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT)
+                    } else {
+                        wgpu::LoadOp::Load
+                    },
                     store: true,
                 },
             }],
@@ -522,17 +2023,31 @@ impl Renderer {
             depth_stencil_attachment: None,
         };
 
-        self.sampler_bindings
-            .bind_sampler(0, &self.g_buffer.sampler);
-        self.sampler_bindings.generate();
+        // separate pass
 
-        let mut encoder = instance
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("render encoder"),
-            });
+        // each mesh's instances are a range of the instance buffer starting at its offset
+        let mut instance_offsets = Vec::with_capacity(frame.renderables.len());
+        let mut instances = Vec::new();
 
-        // separate pass
+        for renderable in &frame.renderables {
+            match renderable {
+                Renderable::Mesh {
+                    transforms, ids, ..
+                } => {
+                    instance_offsets.push(instances.len() as wgpu::BufferAddress * INSTANCE_STRIDE);
+                    instances.extend(transforms.iter().zip(ids).map(|(model, id)| MeshInstance {
+                        model: *model,
+                        normal: normal_matrix(*model),
+                        id: *id,
+                        _pad: [0; 3],
+                    }));
+                }
+            }
+        }
+
+        view_data
+            .instance_buffer
+            .write(instance, cast_slice(&instances));
 
         let mut render_pass = encoder.begin_render_pass(&separate_pass);
 
@@ -545,21 +2060,26 @@ impl Renderer {
             match *renderable {
                 Renderable::Mesh {
                     material,
-                    ref transform,
                     albedo,
                     emission,
+                    normal,
                     ..
                 } => {
-                    if mesh_index >= self.mesh_bindings.len() {
-                        self.mesh_bindings
+                    if mesh_index >= view_data.mesh_bindings.len() {
+                        view_data
+                            .mesh_bindings
                             .push((BindGroup::new(instance), BindGroup::new(instance)));
                     }
 
-                    let (uniforms, textures) = &mut self.mesh_bindings[mesh_index];
+                    let (uniforms, textures) = &mut view_data.mesh_bindings[mesh_index];
 
-                    uniforms.bind_uniform(0, &frame.camera_matrix);
-                    uniforms.bind_uniform(1, transform);
-                    uniforms.bind_uniform(2, material);
+                    // `Camera` in separate.wgsl
+                    let mut camera_block = UniformBlock::new();
+                    camera_block.write(&frame.camera_matrix);
+                    camera_block.write(&frame.clip_plane);
+
+                    uniforms.bind_uniform_block(0, camera_block);
+                    uniforms.bind_uniform(1, material);
 
                     textures.bind_texture(
                         0,
@@ -571,6 +2091,19 @@ impl Renderer {
                         emission.unwrap_or(&self.default_texture),
                         wgpu::TextureSampleType::Float { filterable: true },
                     );
+                    textures.bind_texture(
+                        2,
+                        normal.unwrap_or(&self.default_normal_texture),
+                        wgpu::TextureSampleType::Float { filterable: true },
+                    );
+                    textures.bind_texture(
+                        3,
+                        frame
+                            .mirror
+                            .and(self.mirror.as_ref())
+                            .unwrap_or(&self.default_texture),
+                        wgpu::TextureSampleType::Float { filterable: true },
+                    );
 
                     uniforms.generate();
                     textures.generate();
@@ -580,262 +2113,832 @@ impl Renderer {
             }
         }
 
-        mesh_index = 0;
+        let frustum = Frustum::new(frame.camera_matrix);
 
-        // render Renderables
-        for renderable in frame.renderables {
-            match renderable {
-                Renderable::Mesh {
-                    vertex_buffer,
-                    index_buffer,
-                    indices,
-                    ..
-                } => {
-                    let (uniforms, textures) = &self.mesh_bindings[mesh_index];
+        // render Renderables, wireframes last so solid meshes drawn after them can't cover them
+        for &wireframe_pass in &[false, true] {
+            if wireframe_pass {
+                match &self.pipelines.wireframe {
+                    Some(pipeline) => render_pass.set_pipeline(pipeline),
+                    None => break,
+                }
+            }
 
-                    render_pass.set_bind_group(0, uniforms.inner().unwrap(), &[]);
-                    render_pass.set_bind_group(1, textures.inner().unwrap(), &[]);
-                    render_pass.set_bind_group(2, self.sampler_bindings.inner().unwrap(), &[]);
+            for (mesh_index, renderable) in frame.renderables.iter().enumerate() {
+                match *renderable {
+                    Renderable::Mesh {
+                        vertex_buffer,
+                        index_buffer,
+                        indices,
+                        ref transforms,
+                        wireframe,
+                        material,
+                        ref bounds,
+                        ..
+                    } => {
+                        if wireframe != wireframe_pass {
+                            continue;
+                        }
+
+                        // instances are drawn together, so only skipped when all are outside
+                        if let (Some(frustum), Some((aabb, sphere))) = (&frustum, bounds) {
+                            if !transforms
+                                .iter()
+                                .any(|matrix| frustum.is_visible(aabb, sphere, *matrix))
+                            {
+                                stats.culled += transforms.len() as u32;
+                                continue;
+                            }
+                        }
+
+                        let (uniforms, textures) = &view_data.mesh_bindings[mesh_index];
+
+                        render_pass.set_bind_group(0, uniforms.inner().unwrap(), &[]);
+                        render_pass.set_bind_group(1, textures.inner().unwrap(), &[]);
+                        render_pass.set_bind_group(
+                            2,
+                            self.mesh_sampler_bindings[material.anisotropy_index()]
+                                .inner()
+                                .unwrap(),
+                            &[],
+                        );
 
-                    render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-                    render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                        let offset = instance_offsets[mesh_index];
+                        let instance_buffer = view_data.instance_buffer.buffer().unwrap();
 
-                    render_pass.draw_indexed(0..indices, 0, 0..1);
+                        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                        render_pass.set_vertex_buffer(1, instance_buffer.slice(offset..));
+                        render_pass
+                            .set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
 
-                    mesh_index += 1;
+                        render_pass.draw_indexed(0..indices, 0, 0..transforms.len() as u32);
+
+                        stats.draw_calls += 1;
+                        stats.triangles += indices / 3 * transforms.len() as u32;
+                    }
                 }
             }
         }
 
         drop(render_pass);
 
+        // parallax pass
+
+        if !frame.parallax_layers.is_empty() {
+            let [_, _, width, height] = frame.viewport.pixels(self.width, self.height);
+
+            // furthest first, so nearer layers blend over them
+            let mut layers = frame.parallax_layers.iter().collect::<Vec<_>>();
+            layers.sort_by(|a, b| b.depth.total_cmp(&a.depth));
+
+            for (i, layer) in layers.iter().enumerate() {
+                if i >= view_data.parallax_bindings.len() {
+                    view_data
+                        .parallax_bindings
+                        .push((BindGroup::new(instance), BindGroup::new(instance)));
+                }
+
+                let (uniforms, textures) = &mut view_data.parallax_bindings[i];
+
+                // `Uniforms` in parallax.wgsl
+                let mut block = UniformBlock::with_size(16);
+                block.write(&layer.offset);
+                block.write(&(width / height));
+                block.pad_to(16);
+
+                uniforms.bind_uniform_block(0, block);
+                textures.bind_texture(
+                    0,
+                    layer.texture,
+                    wgpu::TextureSampleType::Float { filterable: true },
+                );
+
+                uniforms.generate();
+                textures.generate();
+            }
+
+            let parallax_pass = wgpu::RenderPassDescriptor {
+                label: Some("parallax pass"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: &self.g_buffer.emission,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.g_buffer.depth,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            };
+
+            let mut render_pass = encoder.begin_render_pass(&parallax_pass);
+
+            render_pass.set_pipeline(&self.pipelines.parallax);
+
+            for (uniforms, textures) in &view_data.parallax_bindings[..layers.len()] {
+                render_pass.set_bind_group(0, uniforms.inner().unwrap(), &[]);
+                render_pass.set_bind_group(1, textures.inner().unwrap(), &[]);
+                render_pass.set_bind_group(2, self.mesh_sampler_bindings[0].inner().unwrap(), &[]);
+
+                render_pass.draw(0..3, 0..1);
+
+                stats.draw_calls += 1;
+            }
+
+            drop(render_pass);
+        }
+
         // shadow pass
 
-        let mut render_pass = encoder.begin_render_pass(&shadow_pass);
+        let lights_len = frame.directional_lights.len().min(MAX_DIRECTIONAL_LIGHTS);
+
+        // shadow maps go to flagged lights in order, each rendered over the whole scene
+        let mut shadow_maps = vec![None; lights_len];
+        let mut light_matrices = [Mat4::ZERO; MAX_SHADOW_MAPS];
+        let mut shadow_count = 0;
+
+        if let (true, Some(bounds)) = (self.passes.shadow, frame.scene_bounds) {
+            let casters = frame.directional_lights[..lights_len]
+                .iter()
+                .enumerate()
+                .filter(|(_, light)| light.casts_shadow && light.direction != Vec3::ZERO)
+                .collect::<Vec<_>>();
+
+            if casters.len() > MAX_SHADOW_MAPS && (frame.shadows_changed || self.shadows_stale) {
+                log::warn!(
+                    "{} lights cast shadows, only the first {} are shadowed",
+                    casters.len(),
+                    MAX_SHADOW_MAPS
+                );
+            }
 
-        render_pass.set_pipeline(&self.pipelines.shadow);
+            for (i, light) in casters.into_iter().take(MAX_SHADOW_MAPS) {
+                shadow_maps[i] = Some(shadow_count);
+                light_matrices[shadow_count] = light_matrix(light.direction, &bounds);
+                shadow_count += 1;
+            }
+        }
 
-        drop(render_pass);
+        if !self.passes.shadow {
+            self.shadows_stale = true;
+        } else if frame.shadows_changed || self.shadows_stale {
+            for (bindings, matrix) in view_data
+                .shadow_bindings
+                .iter_mut()
+                .zip(&light_matrices[..shadow_count])
+            {
+                bindings.bind_uniform(0, matrix);
+                bindings.generate();
+            }
+
+            for (bindings, shadow) in view_data
+                .shadow_bindings
+                .iter()
+                .zip(&self.g_buffer.shadows)
+                .take(shadow_count)
+            {
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("shadow pass"),
+                    color_attachments: &[],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: shadow,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: true,
+                        }),
+                        stencil_ops: None,
+                    }),
+                });
+
+                render_pass.set_pipeline(&self.pipelines.shadow);
+                render_pass.set_bind_group(0, bindings.inner().unwrap(), &[]);
+
+                for (mesh_index, renderable) in frame.renderables.iter().enumerate() {
+                    match *renderable {
+                        Renderable::Mesh {
+                            vertex_buffer,
+                            index_buffer,
+                            indices,
+                            ref transforms,
+                            wireframe,
+                            material,
+                            ..
+                        } => {
+                            if wireframe || material.skip_shadow_cast != 0 {
+                                continue;
+                            }
+
+                            let offset = instance_offsets[mesh_index];
+                            let instance_buffer = view_data.instance_buffer.buffer().unwrap();
+
+                            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                            render_pass.set_vertex_buffer(1, instance_buffer.slice(offset..));
+                            render_pass.set_index_buffer(
+                                index_buffer.slice(..),
+                                wgpu::IndexFormat::Uint32,
+                            );
+
+                            render_pass.draw_indexed(0..indices, 0, 0..transforms.len() as u32);
+
+                            stats.draw_calls += 1;
+                        }
+                    }
+                }
+            }
+
+            self.shadows_stale = false;
+        }
 
         // light pass
 
-        let mut directional_lights = UniformBlock::new();
+        if self.passes.light {
+            if frame.directional_lights.len() > MAX_DIRECTIONAL_LIGHTS {
+                log::warn!(
+                    "{} directional lights, only the first {} are used",
+                    frame.directional_lights.len(),
+                    MAX_DIRECTIONAL_LIGHTS
+                );
+            }
 
-        directional_lights.write(&(frame.directional_lights.len() as u32));
-        directional_lights.pad(12);
-        directional_lights.write_slice(&frame.directional_lights);
+            // `DirectionalLights` in light.wgsl, a count followed by a fixed size array
+            let lights_size =
+                16 + MAX_DIRECTIONAL_LIGHTS * std::mem::size_of::<DirectionalLightUniform>();
+            let mut directional_lights = UniformBlock::with_size(lights_size);
 
-        let mut uniforms = UniformBlock::new();
+            directional_lights.write(&(lights_len as u32));
+            directional_lights.pad(12);
 
-        uniforms.write(&frame.ambient_color);
-        uniforms.write(&frame.ambient_strength);
+            for (light, shadow_map) in frame.directional_lights.iter().zip(&shadow_maps) {
+                directional_lights.write(&DirectionalLightUniform {
+                    direction: light.direction,
+                    shadow_map: shadow_map.map_or(-1, |map| map as i32),
+                    color: light.color,
+                    strength: light.strength,
+                });
+            }
 
-        self.light_uniform_bindings
-            .bind_uniform_block(0, directional_lights);
-        self.light_uniform_bindings
-            .bind_uniform(1, &frame.camera_position);
-        self.light_uniform_bindings.bind_uniform_block(2, uniforms);
+            directional_lights.pad_to(lights_size);
 
-        self.light_texture_bindings.bind_texture(
-            0,
-            &self.g_buffer.depth,
-            wgpu::TextureSampleType::Depth,
-        );
-        self.light_texture_bindings.bind_texture(
-            1,
-            &self.g_buffer.position,
-            wgpu::TextureSampleType::Float { filterable: false },
-        );
-        self.light_texture_bindings.bind_texture(
-            2,
-            &self.g_buffer.normal,
-            wgpu::TextureSampleType::Float { filterable: false },
-        );
+            if frame.point_lights.len() > MAX_POINT_LIGHTS {
+                log::warn!(
+                    "{} point lights, only the first {} are used",
+                    frame.point_lights.len(),
+                    MAX_POINT_LIGHTS
+                );
+            }
+
+            // `PointLights` in light.wgsl, laid out like `DirectionalLights`
+            let point_lights_len = frame.point_lights.len().min(MAX_POINT_LIGHTS);
+            let point_lights_size =
+                16 + MAX_POINT_LIGHTS * std::mem::size_of::<PointLightUniform>();
+            let mut point_lights = UniformBlock::with_size(point_lights_size);
+
+            point_lights.write(&(point_lights_len as u32));
+            point_lights.pad(12);
+            point_lights.write_slice(
+                &frame.point_lights[..point_lights_len]
+                    .iter()
+                    .map(|light| PointLightUniform {
+                        position: light.position,
+                        radius: light.radius,
+                        color: light.color,
+                        strength: light.strength,
+                    })
+                    .collect::<Vec<_>>(),
+            );
+            point_lights.pad_to(point_lights_size);
+
+            if frame.spot_lights.len() > MAX_SPOT_LIGHTS {
+                log::warn!(
+                    "{} spot lights, only the first {} are used",
+                    frame.spot_lights.len(),
+                    MAX_SPOT_LIGHTS
+                );
+            }
+
+            // `SpotLights` in light.wgsl
+            let spot_lights_len = frame.spot_lights.len().min(MAX_SPOT_LIGHTS);
+            let spot_lights_size = 16 + MAX_SPOT_LIGHTS * std::mem::size_of::<SpotLightUniform>();
+            let mut spot_lights = UniformBlock::with_size(spot_lights_size);
+
+            spot_lights.write(&(spot_lights_len as u32));
+            spot_lights.pad(12);
+            spot_lights.write_slice(
+                &frame.spot_lights[..spot_lights_len]
+                    .iter()
+                    .map(|light| {
+                        let outer_angle = light.outer_angle.max(light.inner_angle);
+
+                        SpotLightUniform {
+                            position: light.position,
+                            range: light.range,
+                            direction: light.direction.normalize_or_zero(),
+                            cos_inner: light.inner_angle.cos(),
+                            color: light.color,
+                            strength: light.strength,
+                            cos_outer: outer_angle.cos(),
+                            _pad0: [0.0; 3],
+                        }
+                    })
+                    .collect::<Vec<_>>(),
+            );
+            spot_lights.pad_to(spot_lights_size);
+
+            // `Uniforms` in light.wgsl
+            let mut uniforms = UniformBlock::with_size(48);
+
+            uniforms.write(&frame.ambient_color);
+            uniforms.write(&frame.ambient_strength);
+            uniforms.write(&frame.environment_strength);
+            uniforms.write(&(frame.environment.is_some() as u32));
+            uniforms.write(&(frame.pcss as u32));
+            uniforms.write(&frame.shadow_softness);
+            uniforms.write(&frame.light_size);
+            uniforms.pad_to(48);
+
+            view_data
+                .light_uniform_bindings
+                .bind_uniform_block(0, directional_lights);
+            view_data
+                .light_uniform_bindings
+                .bind_uniform(1, &frame.camera_position);
+            view_data
+                .light_uniform_bindings
+                .bind_uniform_block(2, uniforms);
+            view_data
+                .light_uniform_bindings
+                .bind_uniform(3, &light_matrices);
+            view_data
+                .light_uniform_bindings
+                .bind_uniform_block(4, point_lights);
+            view_data
+                .light_uniform_bindings
+                .bind_uniform_block(5, spot_lights);
+
+            self.ibl
+                .prefilter(encoder, &self.pipelines, frame.environment);
+
+            let mut render_pass = encoder.begin_render_pass(&light_pass);
+
+            render_pass.set_pipeline(&self.pipelines.light);
+
+            render_pass.set_bind_group(0, view_data.light_uniform_bindings.generate(), &[]);
+            render_pass.set_bind_group(1, self.light_texture_bindings.generate(), &[]);
+            render_pass.set_bind_group(2, self.sampler_bindings.generate(), &[]);
+
+            render_pass.draw(0..3, 0..1);
+
+            stats.draw_calls += 1;
+
+            drop(render_pass);
+        } else {
+            // without lighting the combine pass shows unlit albedo
+            let render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("unlit pass"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: &self.g_buffer.light,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+
+            drop(render_pass);
+        }
+
+        // ssr pass
+
+        // cleared even when disabled, so combine adds no stale reflections
+        let mut render_pass = encoder.begin_render_pass(&ssr_pass);
+
+        if frame.ssr && self.passes.light {
+            // `Uniforms` in ssr.wgsl
+            let mut uniforms = UniformBlock::with_size(96);
 
-        let mut render_pass = encoder.begin_render_pass(&light_pass);
+            uniforms.write(&frame.camera_matrix);
+            uniforms.write(&frame.camera_position);
+            uniforms.write(&frame.ssr_steps);
+            uniforms.write(&frame.ssr_max_distance);
+            // depth a ray may pass behind a surface and still hit it
+            uniforms.write(&(frame.ssr_max_distance / frame.ssr_steps.max(1) as f32 * 2.0));
+            uniforms.pad_to(96);
 
-        render_pass.set_pipeline(&self.pipelines.light);
+            view_data
+                .ssr_uniform_bindings
+                .bind_uniform_block(0, uniforms);
 
-        render_pass.set_bind_group(0, self.light_uniform_bindings.generate(), &[]);
-        render_pass.set_bind_group(1, self.light_texture_bindings.generate(), &[]);
-        render_pass.set_bind_group(2, self.sampler_bindings.generate(), &[]);
+            render_pass.set_pipeline(&self.pipelines.ssr);
 
-        render_pass.draw(0..3, 0..1);
+            render_pass.set_bind_group(0, view_data.ssr_uniform_bindings.generate(), &[]);
+            render_pass.set_bind_group(1, self.ssr_texture_bindings.generate(), &[]);
+            render_pass.set_bind_group(2, self.sampler_bindings.generate(), &[]);
+
+            render_pass.draw(0..3, 0..1);
+
+            stats.draw_calls += 1;
+        }
 
         drop(render_pass);
 
         // bloom pass
 
-        let mut uniforms = UniformBlock::new();
+        if self.passes.bloom {
+            let mut uniforms = UniformBlock::with_size(16);
 
-        let iterations = (self.width.min(self.height) as f32 * frame.bloom).round() as u32;
+            let iterations = (self.width.min(self.height) as f32 * frame.bloom).round() as u32;
 
-        uniforms.write(&(false as i32));
-        uniforms.write(&iterations);
+            uniforms.write(&(false as i32));
+            uniforms.write(&iterations);
+            uniforms.pad(8);
 
-        self.bloom_uniform_bindings.bind_uniform_block(0, uniforms);
+            view_data
+                .bloom_uniform_bindings
+                .bind_uniform_block(0, uniforms);
 
-        self.bloom_texture_bindings.bind_texture(
-            0,
-            &self.g_buffer.emission,
-            wgpu::TextureSampleType::Float { filterable: true },
-        );
+            let mut render_pass = encoder.begin_render_pass(&bloom_pass);
 
-        let mut render_pass = encoder.begin_render_pass(&bloom_pass);
+            render_pass.set_pipeline(&self.pipelines.bloom);
 
-        render_pass.set_pipeline(&self.pipelines.bloom);
+            render_pass.set_bind_group(0, view_data.bloom_uniform_bindings.generate(), &[]);
+            render_pass.set_bind_group(1, self.bloom_texture_bindings.generate(), &[]);
+            render_pass.set_bind_group(2, self.sampler_bindings.generate(), &[]);
 
-        render_pass.set_bind_group(0, self.bloom_uniform_bindings.generate(), &[]);
-        render_pass.set_bind_group(1, self.bloom_texture_bindings.generate(), &[]);
-        render_pass.set_bind_group(2, self.sampler_bindings.generate(), &[]);
+            render_pass.draw(0..3, 0..1);
 
-        render_pass.draw(0..3, 0..1);
+            stats.draw_calls += 1;
 
-        drop(render_pass);
+            drop(render_pass);
 
-        // bloom h pass
+            // bloom h pass
 
-        let mut uniforms = UniformBlock::new();
+            let mut uniforms = UniformBlock::with_size(16);
 
-        uniforms.write(&(true as i32));
-        uniforms.write(&iterations);
+            uniforms.write(&(true as i32));
+            uniforms.write(&iterations);
+            uniforms.pad(8);
 
-        self.bloom_h_uniform_bindings
-            .bind_uniform_block(0, uniforms);
+            view_data
+                .bloom_h_uniform_bindings
+                .bind_uniform_block(0, uniforms);
 
-        self.bloom_h_texture_bindings.bind_texture(
-            0,
-            &self.g_buffer.bloom,
-            wgpu::TextureSampleType::Float { filterable: true },
-        );
+            let mut render_pass = encoder.begin_render_pass(&bloom_h_pass);
 
-        let mut render_pass = encoder.begin_render_pass(&bloom_h_pass);
+            render_pass.set_pipeline(&self.pipelines.bloom);
 
-        render_pass.set_pipeline(&self.pipelines.bloom);
+            render_pass.set_bind_group(0, view_data.bloom_h_uniform_bindings.generate(), &[]);
+            render_pass.set_bind_group(1, self.bloom_h_texture_bindings.generate(), &[]);
+            render_pass.set_bind_group(2, self.sampler_bindings.generate(), &[]);
 
-        render_pass.set_bind_group(0, self.bloom_h_uniform_bindings.generate(), &[]);
-        render_pass.set_bind_group(1, self.bloom_h_texture_bindings.generate(), &[]);
-        render_pass.set_bind_group(2, self.sampler_bindings.generate(), &[]);
+            render_pass.draw(0..3, 0..1);
 
-        render_pass.draw(0..3, 0..1);
+            stats.draw_calls += 1;
 
-        drop(render_pass);
+            drop(render_pass);
+        }
 
         // combine pass
 
-        self.combine_texture_bindings.bind_texture(
-            0,
-            &self.g_buffer.depth,
-            wgpu::TextureSampleType::Depth,
-        );
-        self.combine_texture_bindings.bind_texture(
-            1,
-            &self.g_buffer.position,
-            wgpu::TextureSampleType::Float { filterable: false },
-        );
-        self.combine_texture_bindings.bind_texture(
-            2,
-            &self.g_buffer.normal,
-            wgpu::TextureSampleType::Float { filterable: false },
-        );
-        self.combine_texture_bindings.bind_texture(
-            3,
-            &self.g_buffer.albedo,
-            wgpu::TextureSampleType::Float { filterable: true },
-        );
-        self.combine_texture_bindings.bind_texture(
-            4,
-            &self.g_buffer.emission,
-            wgpu::TextureSampleType::Float { filterable: true },
-        );
-        self.combine_texture_bindings.bind_texture(
-            5,
-            &self.g_buffer.light,
-            wgpu::TextureSampleType::Float { filterable: true },
-        );
+        // `Uniforms` in combine.wgsl
+        let mut uniforms = UniformBlock::with_size(64);
+
+        uniforms.write(&(self.transparent as u32));
+        uniforms.write(&(self.srgb_target as u32));
+        uniforms.write(&(frame.dof as u32));
+        uniforms.pad(4);
+        uniforms.write(&frame.camera_position);
+        uniforms.write(&frame.focus_distance);
+        uniforms.write(&frame.aperture);
+        // full blur covers the same share of the screen at any resolution
+        uniforms.write(&(self.height as f32 / 80.0));
+        uniforms.write(&(frame.tonemap as u32));
+        uniforms.write(&frame.exposure);
+        uniforms.write(&(self.hdr_target as u32));
+        uniforms.pad_to(64);
+
+        view_data
+            .combine_uniform_bindings
+            .bind_uniform_block(0, uniforms);
 
+        // the target is still cleared when combining is disabled
         let mut render_pass = encoder.begin_render_pass(&combine_pass);
 
-        render_pass.set_pipeline(&self.pipelines.combine);
+        if self.passes.combine {
+            render_pass.set_pipeline(&self.pipelines.combine);
 
-        render_pass.set_bind_group(0, self.combine_texture_bindings.generate(), &[]);
-        render_pass.set_bind_group(1, self.sampler_bindings.generate(), &[]);
+            let [x, y, width, height] = frame.viewport.pixels(self.width, self.height);
+            render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
 
-        render_pass.draw(0..3, 0..1);
+            render_pass.set_bind_group(0, self.combine_texture_bindings.generate(), &[]);
+            render_pass.set_bind_group(1, self.sampler_bindings.generate(), &[]);
+            render_pass.set_bind_group(2, view_data.combine_uniform_bindings.generate(), &[]);
+
+            render_pass.draw(0..3, 0..1);
+
+            stats.draw_calls += 1;
+        }
 
         drop(render_pass);
 
+        // grid pass
+
+        if let (Some(grid), true) = (frame.grid, self.passes.combine) {
+            // `Uniforms` in grid.wgsl
+            let mut uniforms = UniformBlock::with_size(112);
+
+            uniforms.write(&frame.camera_matrix.inverse());
+            uniforms.write(&frame.camera_position);
+            uniforms.write(&grid.plane.normal_axis());
+            uniforms.write(&grid.origin);
+            uniforms.write(&grid.spacing.max(f32::EPSILON));
+            uniforms.write(&(self.srgb_target as u32));
+            uniforms.pad_to(112);
+
+            view_data
+                .grid_uniform_bindings
+                .bind_uniform_block(0, uniforms);
+
+            let mut render_pass = encoder.begin_render_pass(&ui_pass);
+
+            render_pass.set_pipeline(&self.pipelines.grid);
+
+            let [x, y, width, height] = frame.viewport.pixels(self.width, self.height);
+            render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+
+            render_pass.set_bind_group(0, view_data.grid_uniform_bindings.generate(), &[]);
+            render_pass.set_bind_group(1, self.grid_texture_bindings.generate(), &[]);
+            render_pass.set_bind_group(2, self.sampler_bindings.generate(), &[]);
+
+            render_pass.draw(0..3, 0..1);
+
+            stats.draw_calls += 1;
+
+            drop(render_pass);
+        }
+
+        // debug line pass
+
+        if !frame.debug_lines.is_empty() {
+            let [x, y, width, height] = frame.viewport.pixels(self.width, self.height);
+
+            // `Uniforms` in debug_line.wgsl
+            let mut uniforms = UniformBlock::with_size(80);
+
+            uniforms.write(&frame.camera_matrix);
+            uniforms.write(&Vec2::new(width, height));
+            uniforms.write(&frame.scale_factor);
+            uniforms.write(&(self.srgb_target as u32));
+            uniforms.pad_to(80);
+
+            view_data
+                .debug_line_uniform_bindings
+                .bind_uniform_block(0, uniforms);
+
+            let buffer = view_data
+                .debug_line_buffer
+                .write(instance, cast_slice(&frame.debug_lines));
+
+            let mut render_pass = encoder.begin_render_pass(&ui_pass);
+
+            render_pass.set_pipeline(&self.pipelines.debug_line);
+
+            render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+
+            render_pass.set_bind_group(0, view_data.debug_line_uniform_bindings.generate(), &[]);
+            render_pass.set_vertex_buffer(0, buffer.slice(..));
+
+            render_pass.draw(0..6, 0..frame.debug_lines.len() as u32);
+
+            stats.draw_calls += 1;
+
+            drop(render_pass);
+        }
+
         let mut render_pass = encoder.begin_render_pass(&ui_pass);
 
         render_pass.set_pipeline(&self.pipelines.ui);
 
-        let mut mesh_index = 0;
+        let batches = UiBatch::batch(&frame.ui_renderables, self.width, self.height);
 
         // prepare bindings
-        for renderable in &frame.ui_renderables {
-            match *renderable {
-                UiRenderable::Mesh {
-                    vertices,
-                    indices,
-                    texture,
-                    ..
-                } => {
-                    if mesh_index >= self.ui_data.len() {
-                        self.ui_data.push(UiData::new(
-                            instance,
-                            vertices,
-                            indices,
-                            texture,
-                            self.width,
-                            self.height,
-                        ));
-                    } else {
-                        let ui_data = &mut self.ui_data[mesh_index];
+        for (i, batch) in batches.iter().enumerate() {
+            if i >= view_data.ui_data.len() {
+                view_data.ui_data.push(UiData::new(
+                    instance,
+                    &batch.vertices,
+                    &batch.indices,
+                    batch.texture,
+                    self.width,
+                    self.height,
+                ));
+            } else {
+                view_data.ui_data[i].update(
+                    instance,
+                    &batch.vertices,
+                    &batch.indices,
+                    batch.texture,
+                    self.width,
+                    self.height,
+                );
+            }
+        }
 
-                        ui_data.update(
-                            instance,
-                            vertices,
-                            indices,
-                            texture,
-                            self.width,
-                            self.height,
-                        );
-                    }
+        // render UiRenderables
+        for (batch, ui_data) in batches.iter().zip(&view_data.ui_data) {
+            render_pass.set_bind_group(0, ui_data.bindings.inner().unwrap(), &[]);
+            render_pass.set_bind_group(1, self.sampler_bindings.inner().unwrap(), &[]);
 
-                    mesh_index += 1;
-                }
+            render_pass.set_vertex_buffer(0, ui_data.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(ui_data.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+
+            for (range, [x, y, width, height]) in &batch.draws {
+                render_pass.set_scissor_rect(*x, *y, *width, *height);
+                render_pass.draw_indexed(range.clone(), 0, 0..1);
+
+                stats.draw_calls += 1;
+                stats.ui_draws += 1;
             }
         }
 
-        mesh_index = 0;
+        drop(render_pass);
 
-        // render UiRenderables
-        for renderable in frame.ui_renderables {
-            match renderable {
-                UiRenderable::Mesh { indices, .. } => {
-                    let ui_data = &self.ui_data[mesh_index];
+        stats
+    }
+}
 
-                    // TODO
-                    //render_pass.set_scissor_rect();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-                    render_pass.set_bind_group(0, ui_data.bindings.inner().unwrap(), &[]);
-                    render_pass.set_bind_group(1, self.sampler_bindings.inner().unwrap(), &[]);
+    #[test]
+    fn ui_scissor_clamps_clip_rects() {
+        // rects reaching past the target are clamped to it
+        let clamped = Vec4::new(-20.0, 10.0, 900.0, f32::INFINITY);
+        assert_eq!(UiBatch::scissor(clamped, 800, 600), Some([0, 10, 800, 590]));
 
-                    render_pass.set_vertex_buffer(0, ui_data.vertex_buffer.slice(..));
-                    render_pass.set_index_buffer(
-                        ui_data.index_buffer.slice(..),
-                        wgpu::IndexFormat::Uint32,
-                    );
+        // a rect entirely outside is clamped down to nothing
+        let outside = Vec4::new(850.0, 0.0, 900.0, 100.0);
+        assert_eq!(UiBatch::scissor(outside, 800, 600), None);
 
-                    render_pass.draw_indexed(0..indices.len() as u32, 0, 0..1);
+        let zero_area = Vec4::new(100.0, 100.0, 100.0, 200.0);
+        assert_eq!(UiBatch::scissor(zero_area, 800, 600), None);
 
-                    mesh_index += 1;
-                }
+        let inverted = Vec4::new(300.0, 200.0, 100.0, 50.0);
+        assert_eq!(UiBatch::scissor(inverted, 800, 600), None);
+    }
+
+    #[test]
+    fn ui_draws_lower_layers_first() {
+        // egui windows over a hud over world-space labels, submitted interleaved
+        let layers = [0, UiMesh::EGUI_LAYER, -1, 0, UiMesh::EGUI_LAYER, -1];
+        let order = UiBatch::draw_order(layers.iter().copied());
+
+        assert_eq!(order, [2, 5, 0, 3, 1, 4]);
+    }
+
+    // the ui meshes of a busy inspector: windows full of headers, sliders and scroll areas,
+    // each clipped
+    fn inspector_ui_meshes() -> Vec<UiMesh> {
+        let mut ctx = egui::CtxRef::default();
+        let mut values = [0.5f32; 64];
+
+        let mut clipped_meshes = Vec::new();
+
+        // the second frame, once windows know their sizes
+        for _ in 0..2 {
+            ctx.begin_frame(egui::RawInput {
+                screen_rect: Some(egui::Rect::from_min_size(
+                    egui::Pos2::ZERO,
+                    egui::vec2(1280.0, 720.0),
+                )),
+                ..Default::default()
+            });
+
+            for (window, values) in values.chunks_mut(16).enumerate() {
+                egui::Window::new(format!("Inspector {}", window))
+                    .default_pos(egui::pos2(window as f32 * 300.0, 20.0))
+                    .show(&ctx, |ui| {
+                        egui::ScrollArea::auto_sized().show(ui, |ui| {
+                            for (i, value) in values.iter_mut().enumerate() {
+                                egui::CollapsingHeader::new(format!("Component {}", i))
+                                    .default_open(true)
+                                    .show(ui, |ui| {
+                                        ui.label("Strength");
+                                        ui.add(egui::Slider::new(value, 0.0..=1.0));
+                                        ui.checkbox(&mut true, "Cast shadow");
+                                    });
+                            }
+                        });
+                    });
             }
+
+            let (_, shapes) = ctx.end_frame();
+            clipped_meshes = ctx.tessellate(shapes);
         }
 
-        drop(render_pass);
+        clipped_meshes
+            .iter()
+            .map(|egui::ClippedMesh(clip_rect, mesh)| UiMesh {
+                vertices: vec![UiVertex::default(); mesh.vertices.len()],
+                indices: mesh.indices.clone(),
+                clip_rect: Vec4::new(
+                    clip_rect.min.x,
+                    clip_rect.min.y,
+                    clip_rect.max.x,
+                    clip_rect.max.y,
+                ),
+                layer: UiMesh::EGUI_LAYER,
+            })
+            .collect()
+    }
 
-        instance.queue.submit(std::iter::once(encoder.finish()));
+    #[test]
+    fn ui_batching_merges_egui_buffers() {
+        let meshes = inspector_ui_meshes();
+
+        // egui draws everything with its font texture
+        let texture = Arc::new(());
+        let renderables = meshes
+            .iter()
+            .map(|mesh| UiRenderable::Mesh {
+                vertices: &mesh.vertices,
+                indices: &mesh.indices,
+                texture: &texture,
+                clip_rect: mesh.clip_rect,
+                layer: mesh.layer,
+            })
+            .collect::<Vec<_>>();
+
+        let batches = UiBatch::batch(&renderables, 1280, 720);
+
+        // the buffers all collapse into one set, and nothing is lost on the way
+        assert_eq!(batches.len(), 1);
+
+        let indices = meshes.iter().map(|mesh| mesh.indices.len()).sum::<usize>();
+        assert_eq!(batches[0].indices.len(), indices);
+    }
+
+    #[test]
+    #[ignore = "measures rather than checks, run with `-- --ignored --nocapture`"]
+    fn ui_batching_draws_and_buffers() {
+        let meshes = inspector_ui_meshes();
+
+        let texture = Arc::new(());
+        let renderables = meshes
+            .iter()
+            .map(|mesh| UiRenderable::Mesh {
+                vertices: &mesh.vertices,
+                indices: &mesh.indices,
+                texture: &texture,
+                clip_rect: mesh.clip_rect,
+                layer: mesh.layer,
+            })
+            .collect::<Vec<_>>();
+
+        let batches = UiBatch::batch(&renderables, 1280, 720);
+        let draws = batches.iter().map(|batch| batch.draws.len()).sum::<usize>();
+
+        // unbatched, every mesh gets its own buffers, bind group and draw, egui already merges
+        // neighbouring shapes with the same clip rect so draws don't drop much
+        println!(
+            "{} ui meshes batched into {} buffer sets and {} draws",
+            meshes.len(),
+            batches.len(),
+            draws,
+        );
+    }
+
+    #[test]
+    fn normal_matrix_keeps_scaled_normals_perpendicular() {
+        // a plane sloped at 45 degrees, stretched along x
+        let (tangent, bitangent) = (Vec3::new(1.0, 1.0, 0.0), Vec3::Z);
+        let normal = bitangent.cross(tangent).normalize();
+        let model = Mat4::from_scale(Vec3::new(4.0, 1.0, 1.0));
+
+        let surface = [
+            model.transform_vector3(tangent),
+            model.transform_vector3(bitangent),
+        ];
+
+        let lit = normal_matrix(model).transform_vector3(normal).normalize();
+        for direction in surface.iter() {
+            assert!(lit.dot(direction.normalize()).abs() < 1e-5);
+        }
+
+        // the model matrix itself would tilt it
+        let skewed = model.transform_vector3(normal).normalize();
+        assert!(skewed.dot(surface[0].normalize()).abs() > 0.1);
+
+        // flattened models keep their matrix instead of going to nan
+        let flat = Mat4::from_scale(Vec3::new(1.0, 0.0, 1.0));
+        assert_eq!(normal_matrix(flat), flat);
     }
 }
@@ -0,0 +1,48 @@
+use crate::rng::Rng;
+use glam::*;
+
+pub const MIN_SCALE: f32 = 0.8;
+pub const MAX_SCALE: f32 = 1.2;
+
+// `count` transforms inside `area` centered on the origin, each with a random yaw and scale
+pub fn place(count: u32, area: Vec3, seed: u64) -> Vec<Mat4> {
+    let mut rng = Rng::new(seed);
+
+    (0..count)
+        .map(|_| {
+            let position = (Vec3::new(rng.next_f32(), rng.next_f32(), rng.next_f32()) - 0.5) * area;
+            let yaw = rng.next_f32() * std::f32::consts::TAU;
+            let scale = rng.range(MIN_SCALE, MAX_SCALE);
+
+            Mat4::from_scale_rotation_translation(
+                Vec3::splat(scale),
+                Quat::from_rotation_y(yaw),
+                position,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_seed_places_the_same_instances() {
+        let area = Vec3::new(20.0, 0.0, 10.0);
+        let placed = place(64, area, 42);
+
+        assert_eq!(placed.len(), 64);
+        assert_eq!(placed, place(64, area, 42));
+        // more instances keep the first ones where they were
+        assert_eq!(placed[..], place(80, area, 42)[..64]);
+        assert_ne!(placed, place(64, area, 43));
+
+        for transform in &placed {
+            let (scale, _, position) = transform.to_scale_rotation_translation();
+
+            assert!(position.abs().cmple(area / 2.0).all());
+            assert!(scale.x >= MIN_SCALE - 1e-5 && scale.x <= MAX_SCALE + 1e-5);
+        }
+    }
+}
@@ -34,25 +34,170 @@ var t_emission: texture_2d<f32>;
 [[group(0), binding(5)]]
 var t_light: texture_2d<f32>;
 
+[[group(0), binding(6)]]
+var t_reflection: texture_2d<f32>;
+
 [[group(1), binding(0)]]
 var sampler: sampler;
 
+[[block]]
+struct Uniforms {
+	transparent: u32;
+	srgb_target: u32;
+	dof: u32;
+	camera_position: vec3<f32>;
+	focus_distance: f32;
+	aperture: f32;
+	// circle of confusion radius in pixels at full blur
+	max_blur: f32;
+	// `Tonemap` in renderer.rs
+	tonemap: u32;
+	exposure: f32;
+	hdr_target: u32;
+};
+
+[[group(2), binding(0)]]
+var<uniform> uniforms: Uniforms;
+
+fn pixel(uv: vec2<f32>) -> vec2<i32> {
+	return vec2<i32>(uv * vec2<f32>(textureDimensions(t_depth)));
+}
+
+// lit color of the g-buffer at `uv`
+fn shade(uv: vec2<f32>) -> vec3<f32> {
+	let depth = textureLoad(t_depth, pixel(uv), 0);
+	let albedo = textureSampleLevel(t_albedo, sampler, uv, 0.0).rgb;
+	let emission = textureSampleLevel(t_emission, sampler, uv, 0.0).rgb;
+	let light = textureSampleLevel(t_light, sampler, uv, 0.0).rgb;
+	let reflection = textureSampleLevel(t_reflection, sampler, uv, 0.0);
+
+	var color: vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
+
+	if (depth < 1.0) {
+		color = albedo * light + reflection.rgb * reflection.a;
+	}
+
+	return color + emission;
+}
+
+// circle of confusion radius in pixels, empty areas are treated as infinitely far
+fn coc(uv: vec2<f32>) -> f32 {
+	let depth = textureLoad(t_depth, pixel(uv), 0);
+
+	if (depth >= 1.0) {
+		return clamp(uniforms.aperture, 0.0, 1.0) * uniforms.max_blur;
+	}
+
+	let position = textureSampleLevel(t_position, sampler, uv, 0.0).xyz;
+	let view_distance = max(distance(uniforms.camera_position, position), 0.0001);
+	let blur = uniforms.aperture * abs(view_distance - uniforms.focus_distance) / view_distance;
+
+	return clamp(blur, 0.0, 1.0) * uniforms.max_blur;
+}
+
+let DOF_SAMPLES: u32 = 24u;
+let GOLDEN_ANGLE: f32 = 2.39996323;
+
+// gathers a disc the size of the pixel's circle of confusion, taps only count as far as their
+// own blur reaches so sharp pixels don't smear into their neighbours
+fn depth_of_field(uv: vec2<f32>, center: vec3<f32>) -> vec3<f32> {
+	let radius = coc(uv);
+	let texel = 1.0 / vec2<f32>(textureDimensions(t_depth));
+
+	var sum: vec3<f32> = center;
+	var weight: f32 = 1.0;
+
+	var i: u32 = 0u;
+	loop {
+		if (i >= DOF_SAMPLES) { break; }
+
+		let r = sqrt((f32(i) + 0.5) / f32(DOF_SAMPLES)) * radius;
+		let angle = f32(i) * GOLDEN_ANGLE;
+		let offset = vec2<f32>(cos(angle), sin(angle)) * r;
+		let tap = uv + offset * texel;
+
+		let w = clamp(coc(tap) - r + 1.0, 0.0, 1.0);
+
+		sum = sum + shade(tap) * w;
+		weight = weight + w;
+
+		i = i + 1u;
+	}
+
+	return sum / weight;
+}
+
+fn aces(c: vec3<f32>) -> vec3<f32> {
+	return clamp((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn uncharted2_curve(x: vec3<f32>) -> vec3<f32> {
+	let a = 0.15;
+	let b = 0.50;
+	let c = 0.10;
+	let d = 0.20;
+	let e = 0.02;
+	let f = 0.30;
+
+	return (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f;
+}
+
+// the float hdr swap chain is scrgb, linear with 1.0 at 80 nits, sdr white is put at 200 nits
+let HDR_PAPER_WHITE: f32 = 2.5;
+
+fn tonemap(color: vec3<f32>) -> vec3<f32> {
+	let c = max(color * uniforms.exposure, vec3<f32>(0.0));
+
+	// scene referred, the display maps it to its own range
+	if (uniforms.hdr_target != 0u) {
+		return c * HDR_PAPER_WHITE;
+	}
+
+	if (uniforms.tonemap == 1u) {
+		return c / (c + 1.0);
+	}
+
+	if (uniforms.tonemap == 2u) {
+		return aces(c);
+	}
+
+	if (uniforms.tonemap == 3u) {
+		// white point 11.2, with the usual exposure bias of 2
+		return uncharted2_curve(c * 2.0) / uncharted2_curve(vec3<f32>(11.2));
+	}
+
+	return min(c, vec3<f32>(1.0));
+}
+
 [[stage(fragment)]]
 fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
 	let depth = textureSample(t_depth, sampler, in.uv);
-	let position = textureSample(t_position, sampler, in.uv).xyz;
-	let normal = textureSample(t_normal, sampler, in.uv).xyz;
-	let albedo = textureSample(t_albedo, sampler, in.uv).rgb;
-	let emission = textureSample(t_emission, sampler, in.uv).rgb;
-	let light = textureSample(t_light, sampler, in.uv).rgb;
 
-	var color: vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
+	var color: vec3<f32> = shade(in.uv);
 
-	if (depth < 1.0) {
-		color = albedo * light;
+	if (uniforms.dof != 0u) {
+		color = depth_of_field(in.uv, color);
+	}
+
+	color = tonemap(color);
+
+	if (uniforms.transparent == 0u || depth < 1.0) {
+		return vec4<f32>(color, 1.0);
 	}
 
-	color = color + emission;
+	// only glow covers empty areas, its brightness becomes coverage
+	let alpha = clamp(max(color.r, max(color.g, color.b)), 0.0, 1.0);
+
+	if (alpha <= 0.0) {
+		return vec4<f32>(0.0, 0.0, 0.0, 0.0);
+	}
+
+	var premultiplied: vec3<f32> = min(color / alpha, vec3<f32>(1.0)) * alpha;
+
+	// the target encodes to srgb after blending, premultiply in encoded space instead
+	if (uniforms.srgb_target != 0u) {
+		premultiplied = min(color / alpha, vec3<f32>(1.0)) * pow(alpha, 2.2);
+	}
 
-	return vec4<f32>(color, 1.0);
+	return vec4<f32>(premultiplied, alpha);
 }
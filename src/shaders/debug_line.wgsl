@@ -0,0 +1,116 @@
+[[block]]
+struct Uniforms {
+	view_proj: mat4x4<f32>;
+	// size of the viewport in pixels
+	viewport_size: vec2<f32>;
+	// physical pixels per logical pixel
+	scale_factor: f32;
+	srgb_target: u32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> uniforms: Uniforms;
+
+struct VertexInput {
+	[[location(0)]] start: vec3<f32>;
+	[[location(1)]] width: f32;
+	[[location(2)]] end: vec3<f32>;
+	[[location(3)]] color: vec4<f32>;
+};
+
+struct VertexOutput {
+	[[builtin(position)]] position: vec4<f32>;
+	[[location(0)]] color: vec4<f32>;
+	// pixel offset from the center of the segment, along and across it
+	[[location(1)]] local: vec2<f32>;
+	// half the segment's length and half its width, in pixels
+	[[location(2)]] half_size: vec2<f32>;
+};
+
+// the part of a segment in front of the camera is kept, the rest would project mirrored
+let NEAR_W: f32 = 0.0001;
+
+fn clip_to_near(a: vec4<f32>, b: vec4<f32>) -> vec4<f32> {
+	if (a.w >= NEAR_W) {
+		return a;
+	}
+
+	let t = (NEAR_W - a.w) / (b.w - a.w);
+	return a + (b - a) * t;
+}
+
+// six vertices per line, two triangles spanning a screen space quad
+[[stage(vertex)]]
+fn main([[builtin(vertex_index)]] index: u32, input: VertexInput) -> VertexOutput {
+	var out: VertexOutput;
+
+	let start_clip = uniforms.view_proj * vec4<f32>(input.start, 1.0);
+	let end_clip = uniforms.view_proj * vec4<f32>(input.end, 1.0);
+
+	let a = clip_to_near(start_clip, end_clip);
+	let b = clip_to_near(end_clip, start_clip);
+
+	let half_viewport = uniforms.viewport_size * 0.5;
+	let a_pixels = a.xy / a.w * half_viewport;
+	let b_pixels = b.xy / b.w * half_viewport;
+
+	let segment = b_pixels - a_pixels;
+	let segment_length = sqrt(dot(segment, segment));
+
+	// points have no direction, any axis gives a square
+	var direction: vec2<f32> = vec2<f32>(1.0, 0.0);
+
+	if (segment_length > 0.0001) {
+		direction = segment / segment_length;
+	}
+
+	let normal = vec2<f32>(-direction.y, direction.x);
+
+	let half_width = input.width * uniforms.scale_factor * 0.5;
+	// one pixel past the edge for the antialiased falloff
+	let extent = half_width + 1.0;
+
+	let corner = index % 6u;
+	var along: f32 = -1.0;
+	var across: f32 = -1.0;
+
+	if (corner == 1u || corner == 2u || corner == 4u) {
+		along = 1.0;
+	}
+
+	if (corner == 2u || corner == 4u || corner == 5u) {
+		across = 1.0;
+	}
+
+	var endpoint_pixels: vec2<f32> = a_pixels;
+
+	if (along > 0.0) {
+		endpoint_pixels = b_pixels;
+	}
+
+	let pixels = endpoint_pixels + direction * along * extent + normal * across * extent;
+
+	// lines are drawn over the scene without depth, and a w of 1 interpolates the pixel
+	// offsets linearly in screen space
+	out.position = vec4<f32>(pixels / half_viewport, 0.0, 1.0);
+	out.color = input.color;
+	out.local = vec2<f32>(along * (segment_length * 0.5 + extent), across * extent);
+	out.half_size = vec2<f32>(segment_length * 0.5 + half_width, half_width);
+
+	return out;
+}
+
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+	// covered share of the pixel, in both directions so caps and points are smooth too
+	let coverage = clamp(in.half_size + 0.5 - abs(in.local), vec2<f32>(0.0), vec2<f32>(1.0));
+	let alpha = in.color.a * coverage.x * coverage.y;
+
+	var color: vec3<f32> = in.color.rgb;
+
+	if (uniforms.srgb_target == 0u) {
+		color = pow(color, vec3<f32>(1.0 / 2.2));
+	}
+
+	return vec4<f32>(color * alpha, alpha);
+}
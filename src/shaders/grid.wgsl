@@ -0,0 +1,110 @@
+struct VertexOutput {
+	[[builtin(position)]] position: vec4<f32>;
+	[[location(0)]] uv: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn main([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+	var out: VertexOutput;
+
+	let x = -1.0 + f32((index & 1u) << 2u);
+	let y = -1.0 + f32((index & 2u) << 1u);
+	out.position = vec4<f32>(x, y, 0.0, 1.0);
+	out.uv = (vec2<f32>(x, y) + 1.0) / 2.0;
+	out.uv.y = 1.0 - out.uv.y;
+	
+	return out;
+}
+
+[[block]]
+struct Uniforms {
+	inverse_view_proj: mat4x4<f32>;
+	camera_position: vec3<f32>;
+	// axis the plane is perpendicular to, 0 x, 1 y, 2 z
+	normal_axis: u32;
+	origin: vec3<f32>;
+	spacing: f32;
+	srgb_target: u32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> uniforms: Uniforms;
+
+[[group(1), binding(0)]]
+var t_depth: texture_depth_2d;
+
+[[group(1), binding(1)]]
+var t_position: texture_2d<f32>;
+
+[[group(2), binding(0)]]
+var sampler: sampler;
+
+fn unproject(ndc: vec3<f32>) -> vec3<f32> {
+	let p = uniforms.inverse_view_proj * vec4<f32>(ndc, 1.0);
+	return p.xyz / p.w;
+}
+
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+	let depth = textureSample(t_depth, sampler, in.uv);
+	let scene = textureSample(t_position, sampler, in.uv).xyz;
+
+	let ndc = vec2<f32>(in.uv.x * 2.0 - 1.0, 1.0 - in.uv.y * 2.0);
+	let near = unproject(vec3<f32>(ndc, 0.0));
+	let far = unproject(vec3<f32>(ndc, 1.0));
+	let dir = far - near;
+
+	// plane coordinates of the ray hit, `a` and `b` span the plane and `n` is its normal
+	var n: f32 = dir.y;
+	var n_origin: f32 = uniforms.origin.y - near.y;
+	var plane_axes: vec2<u32> = vec2<u32>(0u, 2u);
+
+	if (uniforms.normal_axis == 0u) {
+		n = dir.x;
+		n_origin = uniforms.origin.x - near.x;
+		plane_axes = vec2<u32>(1u, 2u);
+	}
+
+	if (uniforms.normal_axis == 2u) {
+		n = dir.z;
+		n_origin = uniforms.origin.z - near.z;
+		plane_axes = vec2<u32>(0u, 1u);
+	}
+
+	let t = n_origin / n;
+	let hit = near + dir * t;
+	let local = (hit - uniforms.origin) / uniforms.spacing;
+
+	let coords = vec2<f32>(local[plane_axes.x], local[plane_axes.y]);
+
+	// anti-aliased lines one pixel wide
+	let grid = abs(fract(coords - 0.5) - 0.5) / fwidth(coords);
+	let line = 1.0 - min(min(grid.x, grid.y), 1.0);
+
+	// the two axes through the origin are drawn brighter
+	let axis = abs(coords) / fwidth(coords);
+	let axis_line = 1.0 - min(min(axis.x, axis.y), 1.0);
+
+	let hit_distance = distance(uniforms.camera_position, hit);
+	let fade = clamp(1.0 - hit_distance / (uniforms.spacing * 100.0), 0.0, 1.0);
+
+	var alpha: f32 = max(line * 0.35, axis_line * 0.8) * fade;
+
+	// behind the camera, or parallel to the view
+	if (t <= 0.0 || abs(n) < 0.000001) {
+		alpha = 0.0;
+	}
+
+	// hidden by geometry in front of it
+	if (depth < 1.0 && distance(uniforms.camera_position, scene) < hit_distance) {
+		alpha = 0.0;
+	}
+
+	var color: vec3<f32> = vec3<f32>(0.5, 0.5, 0.5);
+
+	if (uniforms.srgb_target == 0u) {
+		color = pow(color, vec3<f32>(1.0 / 2.2));
+	}
+
+	return vec4<f32>(color * alpha, alpha);
+}
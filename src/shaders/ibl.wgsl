@@ -0,0 +1,222 @@
+struct VertexOutput {
+	[[builtin(position)]] position: vec4<f32>;
+	[[location(0)]] uv: vec2<f32>;
+	// cube face and mip being drawn, from the instance index
+	[[location(1), interpolate(flat)]] face: u32;
+	[[location(2), interpolate(flat)]] mip: u32;
+};
+
+[[stage(vertex)]]
+fn main(
+	[[builtin(vertex_index)]] index: u32,
+	[[builtin(instance_index)]] instance: u32,
+) -> VertexOutput {
+	var out: VertexOutput;
+
+	let x = -1.0 + f32((index & 1u) << 2u);
+	let y = -1.0 + f32((index & 2u) << 1u);
+	out.position = vec4<f32>(x, y, 0.0, 1.0);
+	out.uv = (vec2<f32>(x, y) + 1.0) / 2.0;
+	out.uv.y = 1.0 - out.uv.y;
+	out.face = instance % 6u;
+	out.mip = instance / 6u;
+
+	return out;
+}
+
+// the equirectangular environment being filtered
+[[group(0), binding(0)]]
+var t_environment: texture_2d<f32>;
+
+[[group(0), binding(1)]]
+var sampler: sampler;
+
+let PI: f32 = 3.14159265359;
+
+// matches `SPECULAR_MIPS` in ibl.rs, roughness goes from 0 at mip 0 to 1 at the last mip
+let SPECULAR_MIPS: u32 = 5u;
+let PREFILTER_SAMPLES: u32 = 128u;
+let BRDF_SAMPLES: u32 = 256u;
+// angle between irradiance samples in radians
+let IRRADIANCE_STEP: f32 = 0.05;
+
+fn sample_environment(dir: vec3<f32>) -> vec3<f32> {
+	let u = atan2(dir.z, dir.x) / (2.0 * PI) + 0.5;
+	let v = acos(clamp(dir.y, -1.0, 1.0)) / PI;
+
+	return textureSampleLevel(t_environment, sampler, vec2<f32>(u, v), 0.0).rgb;
+}
+
+// direction through `uv` of cube face `face`, faces are ordered +x, -x, +y, -y, +z, -z
+fn face_direction(face: u32, uv: vec2<f32>) -> vec3<f32> {
+	let u = uv.x * 2.0 - 1.0;
+	let v = uv.y * 2.0 - 1.0;
+
+	var dir: vec3<f32> = vec3<f32>(-u, -v, -1.0);
+
+	if (face == 0u) {
+		dir = vec3<f32>(1.0, -v, -u);
+	} elseif (face == 1u) {
+		dir = vec3<f32>(-1.0, -v, u);
+	} elseif (face == 2u) {
+		dir = vec3<f32>(u, 1.0, v);
+	} elseif (face == 3u) {
+		dir = vec3<f32>(u, -1.0, -v);
+	} elseif (face == 4u) {
+		dir = vec3<f32>(u, -v, 1.0);
+	}
+
+	return normalize(dir);
+}
+
+// `v` from tangent space around `n` to world space
+fn to_world(v: vec3<f32>, n: vec3<f32>) -> vec3<f32> {
+	var up: vec3<f32> = vec3<f32>(0.0, 1.0, 0.0);
+	if (abs(n.y) > 0.999) {
+		up = vec3<f32>(0.0, 0.0, 1.0);
+	}
+
+	let tangent = normalize(cross(up, n));
+	let bitangent = cross(n, tangent);
+
+	return tangent * v.x + bitangent * v.y + n * v.z;
+}
+
+// van der corput sequence, the second coordinate of the hammersley set
+fn radical_inverse(i: u32) -> f32 {
+	var bits: u32 = i;
+	var scale: f32 = 0.5;
+	var result: f32 = 0.0;
+
+	loop {
+		if (bits == 0u) { break; }
+
+		if ((bits & 1u) == 1u) {
+			result = result + scale;
+		}
+
+		scale = scale * 0.5;
+		bits = bits >> 1u;
+	}
+
+	return result;
+}
+
+// half vector around `n` distributed like the ggx lobe of `roughness`
+fn importance_sample_ggx(i: u32, count: u32, n: vec3<f32>, roughness: f32) -> vec3<f32> {
+	let a = roughness * roughness;
+
+	let phi = 2.0 * PI * f32(i) / f32(count);
+	let xi = radical_inverse(i);
+	let cos_theta = sqrt((1.0 - xi) / (1.0 + (a * a - 1.0) * xi));
+	let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+
+	return to_world(vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta), n);
+}
+
+// cosine weighted average of the environment over the hemisphere around each direction
+[[stage(fragment)]]
+fn irradiance(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+	let n = face_direction(in.face, in.uv);
+
+	var sum: vec3<f32> = vec3<f32>(0.0);
+	var count: f32 = 0.0;
+
+	var phi: f32 = 0.0;
+	loop {
+		if (phi >= 2.0 * PI) { break; }
+
+		var theta: f32 = 0.0;
+		loop {
+			if (theta >= 0.5 * PI) { break; }
+
+			let dir = vec3<f32>(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+
+			// sin theta makes up for the samples bunching up at the pole
+			sum = sum + sample_environment(to_world(dir, n)) * cos(theta) * sin(theta);
+			count = count + 1.0;
+
+			theta = theta + IRRADIANCE_STEP;
+		}
+
+		phi = phi + IRRADIANCE_STEP;
+	}
+
+	return vec4<f32>(PI * sum / count, 1.0);
+}
+
+// the environment blurred by the ggx lobe of the mip's roughness, seen along the normal
+[[stage(fragment)]]
+fn prefilter(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+	let n = face_direction(in.face, in.uv);
+	let roughness = f32(in.mip) / f32(SPECULAR_MIPS - 1u);
+
+	var sum: vec3<f32> = vec3<f32>(0.0);
+	var weight: f32 = 0.0;
+
+	var i: u32 = 0u;
+	loop {
+		if (i >= PREFILTER_SAMPLES) { break; }
+
+		let h = importance_sample_ggx(i, PREFILTER_SAMPLES, n, roughness);
+		let l = normalize(2.0 * dot(n, h) * h - n);
+		let n_dot_l = dot(n, l);
+
+		if (n_dot_l > 0.0) {
+			sum = sum + sample_environment(l) * n_dot_l;
+			weight = weight + n_dot_l;
+		}
+
+		i = i + 1u;
+	}
+
+	return vec4<f32>(sum / max(weight, 0.0001), 1.0);
+}
+
+// schlick-ggx with the k used for image based lighting
+fn geometry(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+	let k = roughness * roughness / 2.0;
+
+	let g_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+	let g_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+
+	return g_v * g_l;
+}
+
+// scale and bias applied to f0 by the specular brdf integrated over the hemisphere, indexed by
+// n dot v along u and roughness along v
+[[stage(fragment)]]
+fn brdf(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+	let n_dot_v = max(in.uv.x, 0.001);
+	let roughness = in.uv.y;
+
+	let v = vec3<f32>(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+	let n = vec3<f32>(0.0, 0.0, 1.0);
+
+	var scale: f32 = 0.0;
+	var bias: f32 = 0.0;
+
+	var i: u32 = 0u;
+	loop {
+		if (i >= BRDF_SAMPLES) { break; }
+
+		let h = importance_sample_ggx(i, BRDF_SAMPLES, n, roughness);
+		let l = normalize(2.0 * dot(v, h) * h - v);
+
+		let n_dot_l = max(l.z, 0.0);
+		let n_dot_h = max(h.z, 0.0);
+		let v_dot_h = max(dot(v, h), 0.0);
+
+		if (n_dot_l > 0.0) {
+			let visibility = geometry(n_dot_v, n_dot_l, roughness) * v_dot_h / max(n_dot_h * n_dot_v, 0.0001);
+			let fresnel = pow(1.0 - v_dot_h, 5.0);
+
+			scale = scale + (1.0 - fresnel) * visibility;
+			bias = bias + fresnel * visibility;
+		}
+
+		i = i + 1u;
+	}
+
+	return vec4<f32>(scale, bias, 0.0, 0.0) / f32(BRDF_SAMPLES);
+}
@@ -18,6 +18,8 @@ fn main([[builtin(vertex_index)]] index: u32) -> VertexOutput {
 
 struct DirectionalLight {
 	direction: vec3<f32>;
+	// index into the shadow maps, -1 when the light is unshadowed
+	shadow_map: i32;
 	color: vec3<f32>;
 	strength: f32;
 };
@@ -43,11 +45,60 @@ var<uniform> camera: Camera;
 struct Uniforms {
 	ambient_color: vec3<f32>;
 	ambient_strength: f32;
+	environment_strength: f32;
+	has_environment: u32;
+	// `RenderSettings` in world.rs, filtered with pcf alone when `pcss` is zero
+	pcss: u32;
+	shadow_softness: f32;
+	light_size: f32;
 };
 
 [[group(0), binding(2)]]
 var<uniform> uniforms: Uniforms;
 
+[[block]]
+struct ShadowMaps {
+	view_projs: [[stride(64)]] array<mat4x4<f32>, 4>;
+};
+
+[[group(0), binding(3)]]
+var<uniform> shadow_maps: ShadowMaps;
+
+struct PointLight {
+	position: vec3<f32>;
+	radius: f32;
+	color: vec3<f32>;
+	strength: f32;
+};
+
+[[block]]
+struct PointLights {
+	len: u32;
+	lights: array<PointLight, 32>;
+};
+
+[[group(0), binding(4)]]
+var<uniform> point_lights: PointLights;
+
+struct SpotLight {
+	position: vec3<f32>;
+	range: f32;
+	direction: vec3<f32>;
+	cos_inner: f32;
+	color: vec3<f32>;
+	strength: f32;
+	cos_outer: f32;
+};
+
+[[block]]
+struct SpotLights {
+	len: u32;
+	lights: [[stride(64)]] array<SpotLight, 16>;
+};
+
+[[group(0), binding(5)]]
+var<uniform> spot_lights: SpotLights;
+
 [[group(1), binding(0)]]
 var t_depth: texture_depth_2d;
 
@@ -57,9 +108,202 @@ var t_position: texture_2d<f32>;
 [[group(1), binding(2)]]
 var t_normal: texture_2d<f32>;
 
+// the environment prefiltered by ibl.rs
+[[group(1), binding(3)]]
+var t_irradiance: texture_cube<f32>;
+
+[[group(1), binding(4)]]
+var t_shadow0: texture_depth_2d;
+
+[[group(1), binding(5)]]
+var t_shadow1: texture_depth_2d;
+
+[[group(1), binding(6)]]
+var t_shadow2: texture_depth_2d;
+
+[[group(1), binding(7)]]
+var t_shadow3: texture_depth_2d;
+
+[[group(1), binding(8)]]
+var shadow_sampler: sampler_comparison;
+
+[[group(1), binding(9)]]
+var t_specular: texture_cube<f32>;
+
+[[group(1), binding(10)]]
+var t_brdf: texture_2d<f32>;
+
+// roughness in alpha
+[[group(1), binding(11)]]
+var t_albedo: texture_2d<f32>;
+
+// linear, the g buffer sampler doesn't filter
+[[group(1), binding(12)]]
+var environment_sampler: sampler;
+
 [[group(2), binding(0)]]
 var sampler: sampler;
 
+let PI: f32 = 3.14159265359;
+
+// matches `SPECULAR_MIPS` in ibl.rs
+let SPECULAR_MIPS: u32 = 5u;
+
+// ambient diffuse and reflections from the prefiltered environment, split sum approximation
+// with the f0 of a dielectric
+fn environment_light(normal: vec3<f32>, view_dir: vec3<f32>, roughness: f32) -> vec3<f32> {
+	let n_dot_v = clamp(dot(normal, view_dir), 0.0, 1.0);
+	let reflect_dir = reflect(-view_dir, normal);
+
+	let irradiance = textureSampleLevel(t_irradiance, environment_sampler, normal, 0.0).rgb;
+
+	let lod = roughness * f32(SPECULAR_MIPS - 1u);
+	let prefiltered = textureSampleLevel(t_specular, environment_sampler, reflect_dir, lod).rgb;
+	let brdf = textureSampleLevel(t_brdf, environment_sampler, vec2<f32>(n_dot_v, roughness), 0.0).rg;
+
+	return irradiance + prefiltered * (0.04 * brdf.x + brdf.y);
+}
+
+let SHADOW_MAP_SIZE: f32 = 2048.0;
+
+// 1 where `depth` is in front of the shadow map, blended across the neighbouring texels
+fn sample_shadow_map(index: i32, uv: vec2<f32>, depth: f32) -> f32 {
+	if (index == 0) {
+		return textureSampleCompareLevel(t_shadow0, shadow_sampler, uv, depth);
+	}
+	if (index == 1) {
+		return textureSampleCompareLevel(t_shadow1, shadow_sampler, uv, depth);
+	}
+	if (index == 2) {
+		return textureSampleCompareLevel(t_shadow2, shadow_sampler, uv, depth);
+	}
+	return textureSampleCompareLevel(t_shadow3, shadow_sampler, uv, depth);
+}
+
+// depth stored in the shadow map at `uv`, unfiltered
+fn shadow_map_depth(index: i32, uv: vec2<f32>) -> f32 {
+	let texel = vec2<i32>(clamp(uv, vec2<f32>(0.0), vec2<f32>(1.0)) * (SHADOW_MAP_SIZE - 1.0));
+
+	if (index == 0) {
+		return textureLoad(t_shadow0, texel, 0);
+	}
+	if (index == 1) {
+		return textureLoad(t_shadow1, texel, 0);
+	}
+	if (index == 2) {
+		return textureLoad(t_shadow2, texel, 0);
+	}
+	return textureLoad(t_shadow3, texel, 0);
+}
+
+let SHADOW_SAMPLES: i32 = 16;
+let GOLDEN_ANGLE: f32 = 2.39996323;
+// widest filter in texels, wider penumbras would need more samples to stay smooth
+let MAX_SHADOW_RADIUS: f32 = 32.0;
+
+// point `i` of a unit disc evenly covered by `SHADOW_SAMPLES` points, turned by `angle`
+fn shadow_disc(i: i32, angle: f32) -> vec2<f32> {
+	let r = sqrt((f32(i) + 0.5) / f32(SHADOW_SAMPLES));
+	let theta = f32(i) * GOLDEN_ANGLE + angle;
+
+	return vec2<f32>(cos(theta), sin(theta)) * r;
+}
+
+// average depth of the occluders within `radius` texels of `uv`, -1 when there are none
+fn blocker_depth(index: i32, uv: vec2<f32>, depth: f32, radius: f32, angle: f32) -> f32 {
+	var sum: f32 = 0.0;
+	var count: f32 = 0.0;
+
+	var i: i32 = 0;
+	loop {
+		if (i >= SHADOW_SAMPLES) { break; }
+
+		let occluder = shadow_map_depth(index, uv + shadow_disc(i, angle) * radius / SHADOW_MAP_SIZE);
+
+		if (occluder < depth) {
+			sum = sum + occluder;
+			count = count + 1.0;
+		}
+
+		i = i + 1;
+	}
+
+	if (count == 0.0) {
+		return -1.0;
+	}
+
+	return sum / count;
+}
+
+// percentage closer soft shadows, the filter widens with the distance between the receiver and
+// its occluders, shadow maps are orthographic so depth and uv distances share a scale
+fn soft_shadow(index: i32, uv: vec2<f32>, depth: f32, pixel: vec2<f32>) -> f32 {
+	// interleaved gradient noise turns the disc per pixel, trading banding for noise
+	let angle = fract(52.9829189 * fract(dot(pixel, vec2<f32>(0.06711056, 0.00583715)))) * 2.0 * PI;
+
+	// occluders at the light would cast the widest penumbra, so that's as far as they're searched
+	let search = clamp(uniforms.light_size * depth * SHADOW_MAP_SIZE, 1.0, MAX_SHADOW_RADIUS);
+	let blocker = blocker_depth(index, uv, depth, search, angle);
+
+	if (blocker < 0.0) {
+		return 1.0;
+	}
+
+	let penumbra = uniforms.light_size * (depth - blocker) * SHADOW_MAP_SIZE;
+	let radius = clamp(uniforms.shadow_softness + penumbra, 1.0, MAX_SHADOW_RADIUS);
+
+	var lit: f32 = 0.0;
+
+	var i: i32 = 0;
+	loop {
+		if (i >= SHADOW_SAMPLES) { break; }
+
+		lit = lit + sample_shadow_map(index, uv + shadow_disc(i, angle) * radius / SHADOW_MAP_SIZE, depth);
+
+		i = i + 1;
+	}
+
+	return lit / f32(SHADOW_SAMPLES);
+}
+
+// share of the light reaching `position`, seen at `pixel` of the target
+fn shadow(index: i32, position: vec3<f32>, pixel: vec2<f32>) -> f32 {
+	let clip = shadow_maps.view_projs[index] * vec4<f32>(position, 1.0);
+	let uv = vec2<f32>(clip.x * 0.5 + 0.5, 0.5 - clip.y * 0.5);
+
+	// outside the map nothing was rendered that could occlude it
+	if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0 || clip.z > 1.0) {
+		return 1.0;
+	}
+
+	if (uniforms.pcss != 0u) {
+		return soft_shadow(index, uv, clip.z, pixel);
+	}
+
+	// filtered over 3x3 texels
+	var lit: f32 = 0.0;
+
+	var y: i32 = -1;
+	loop {
+		if (y > 1) { break; }
+
+		var x: i32 = -1;
+		loop {
+			if (x > 1) { break; }
+
+			let offset = vec2<f32>(f32(x), f32(y)) / SHADOW_MAP_SIZE;
+
+			lit = lit + sample_shadow_map(index, uv + offset, clip.z);
+
+			x = x + 1;
+		}
+
+		y = y + 1;
+	}
+
+	return lit / 9.0;
+}
+
 struct FragmentOutput {
 	[[location(0)]] light: vec4<f32>;	
 	[[location(1)]] emission: vec4<f32>;	
@@ -72,10 +316,26 @@ fn main(in: VertexOutput) -> FragmentOutput {
 	let depth = textureSample(t_depth, sampler, in.uv);
 	let p = textureSample(t_position, sampler, in.uv);
 	let position = p.xyz;
-	let normal = textureSample(t_normal, sampler, in.uv).xyz;
+	let n = textureSample(t_normal, sampler, in.uv);
+	let normal = n.xyz;
+	let roughness = textureSample(t_albedo, sampler, in.uv).a;
+
+	// lighting is baked into the albedo
+	if (n.w == 0.0) {
+		out.light = vec4<f32>(1.0, 1.0, 1.0, 0.0);
+		out.emission = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+		return out;
+	}
 
 	var light: vec3<f32> = uniforms.ambient_color * uniforms.ambient_strength;
 
+	// flat ambient without an environment
+	if (uniforms.has_environment != 0u) {
+		let view_dir = normalize(camera.pos - position);
+
+		light = environment_light(normal, view_dir, roughness) * uniforms.environment_strength;
+	}
+
 	var i: u32 = 0u;
 	loop {
 		if (i >= directional_lights.len) { break; }
@@ -85,7 +345,13 @@ fn main(in: VertexOutput) -> FragmentOutput {
 		let half_dir = normalize(view_dir + light_dir);
 
 		let color = directional_lights.lights[i].color;
-		let strength = directional_lights.lights[i].strength;
+		var strength: f32 = directional_lights.lights[i].strength;
+
+		let shadow_map = directional_lights.lights[i].shadow_map;
+
+		if (shadow_map >= 0 && n.w < 1.5) {
+			strength = strength * shadow(shadow_map, position, in.position.xy);
+		}
 
 		let diffuse_strength = max(dot(light_dir, normal), 0.0) * strength;
 		let diffuse_color = color * diffuse_strength;
@@ -98,6 +364,72 @@ fn main(in: VertexOutput) -> FragmentOutput {
 		i = i + 1u;
 	}
 
+	var j: u32 = 0u;
+	loop {
+		if (j >= point_lights.len) { break; }
+
+		let to_light = point_lights.lights[j].position - position;
+		let light_distance = length(to_light);
+		let radius = point_lights.lights[j].radius;
+
+		if (light_distance < radius) {
+			let light_dir = to_light / max(light_distance, 0.0001);
+			let view_dir = normalize(camera.pos - position);
+			let half_dir = normalize(view_dir + light_dir);
+
+			// inverse square, windowed so the light reaches exactly zero at its radius
+			let ratio = light_distance / radius;
+			let window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+			let attenuation = window * window / (light_distance * light_distance + 1.0);
+
+			let color = point_lights.lights[j].color;
+			let strength = point_lights.lights[j].strength * attenuation;
+
+			let diffuse_strength = max(dot(light_dir, normal), 0.0) * strength;
+			let specular_strength = pow(max(dot(half_dir, normal), 0.0), 32.0) * strength;
+
+			light = light + color * (diffuse_strength + specular_strength);
+		}
+
+		j = j + 1u;
+	}
+
+	var k: u32 = 0u;
+	loop {
+		if (k >= spot_lights.len) { break; }
+
+		let to_light = spot_lights.lights[k].position - position;
+		let light_distance = length(to_light);
+		let range = spot_lights.lights[k].range;
+
+		if (light_distance < range) {
+			let light_dir = to_light / max(light_distance, 0.0001);
+			let view_dir = normalize(camera.pos - position);
+			let half_dir = normalize(view_dir + light_dir);
+
+			// same falloff as point lights, then faded from the inner to the outer cone
+			let ratio = light_distance / range;
+			let window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+			let attenuation = window * window / (light_distance * light_distance + 1.0);
+
+			let cos_angle = dot(-light_dir, spot_lights.lights[k].direction);
+			let cos_inner = spot_lights.lights[k].cos_inner;
+			let cos_outer = spot_lights.lights[k].cos_outer;
+			let t = clamp((cos_angle - cos_outer) / max(cos_inner - cos_outer, 0.0001), 0.0, 1.0);
+			let cone = t * t * (3.0 - 2.0 * t);
+
+			let color = spot_lights.lights[k].color;
+			let strength = spot_lights.lights[k].strength * attenuation * cone;
+
+			let diffuse_strength = max(dot(light_dir, normal), 0.0) * strength;
+			let specular_strength = pow(max(dot(half_dir, normal), 0.0), 32.0) * strength;
+
+			light = light + color * (diffuse_strength + specular_strength);
+		}
+
+		k = k + 1u;
+	}
+
 	out.light = vec4<f32>(light, 0.0);
 	out.emission = vec4<f32>(light - 1.0, 0.0) * p.w;
 
@@ -0,0 +1,41 @@
+struct VertexOutput {
+	[[builtin(position)]] position: vec4<f32>;
+	[[location(0)]] uv: vec2<f32>;
+};
+
+// drawn at the far plane, so the depth test keeps it behind everything
+[[stage(vertex)]]
+fn main([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+	var out: VertexOutput;
+
+	let x = -1.0 + f32((index & 1u) << 2u);
+	let y = -1.0 + f32((index & 2u) << 1u);
+	out.position = vec4<f32>(x, y, 1.0, 1.0);
+	out.uv = (vec2<f32>(x, y) + 1.0) / 2.0;
+	out.uv.y = 1.0 - out.uv.y;
+
+	return out;
+}
+
+[[block]]
+struct Uniforms {
+	offset: vec2<f32>;
+	// width over height of the screen, the layer spans the screen's height once
+	aspect: f32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> uniforms: Uniforms;
+
+[[group(1), binding(0)]]
+var t_layer: texture_2d<f32>;
+
+[[group(2), binding(0)]]
+var sampler: sampler;
+
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+	let uv = in.uv * vec2<f32>(uniforms.aspect, 1.0) + uniforms.offset;
+
+	return textureSample(t_layer, sampler, uv);
+}
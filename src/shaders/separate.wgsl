@@ -2,6 +2,22 @@ struct VertexInput {
 	[[location(0)]] position: vec3<f32>;
 	[[location(1)]] normal: vec3<f32>;
 	[[location(2)]] uv: vec2<f32>;
+	[[location(3)]] color: vec4<f32>;
+	[[location(4)]] tangent: vec4<f32>;
+};
+
+// columns of the model matrix and of its inverse transpose, which keeps normals perpendicular
+// under non-uniform scale
+struct InstanceInput {
+	[[location(5)]] model_0: vec4<f32>;
+	[[location(6)]] model_1: vec4<f32>;
+	[[location(7)]] model_2: vec4<f32>;
+	[[location(8)]] model_3: vec4<f32>;
+	[[location(9)]] normal_0: vec4<f32>;
+	[[location(10)]] normal_1: vec4<f32>;
+	[[location(11)]] normal_2: vec4<f32>;
+	[[location(12)]] normal_3: vec4<f32>;
+	[[location(13)]] id: u32;
 };
 
 struct VertexOutput {
@@ -9,32 +25,35 @@ struct VertexOutput {
 	[[location(0)]] w_position: vec4<f32>;
 	[[location(1)]] w_normal: vec4<f32>;
 	[[location(2)]] uv: vec2<f32>;
+	[[location(3)]] color: vec4<f32>;
+	[[location(4)]] w_tangent: vec4<f32>;
+	[[location(5), interpolate(flat)]] id: u32;
 };
 
 [[block]]
 struct Camera {
 	view_proj: mat4x4<f32>;
+	// world space plane, fragments behind it are discarded, zero when there is none
+	clip_plane: vec4<f32>;
 };
 
 [[group(0), binding(0)]]
 var<uniform> camera: Camera;
 
-[[block]]
-struct Transform {
-	model: mat4x4<f32>;
-};
-
-[[group(0), binding(1)]]
-var<uniform> transform: Transform;
-
 [[stage(vertex)]]
-fn main(in: VertexInput) -> VertexOutput {
+fn main(in: VertexInput, instance: InstanceInput) -> VertexOutput {
 	var out: VertexOutput;
 
-	out.w_position = transform.model * vec4<f32>(in.position, 1.0);
-	out.w_normal = transform.model * vec4<f32>(in.normal, 0.0);
+	let model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+	let normal = mat4x4<f32>(instance.normal_0, instance.normal_1, instance.normal_2, instance.normal_3);
+
+	out.w_position = model * vec4<f32>(in.position, 1.0);
+	out.w_normal = vec4<f32>((normal * vec4<f32>(in.normal, 0.0)).xyz, 0.0);
 	out.position = camera.view_proj * out.w_position;
 	out.uv = in.uv;
+	out.color = in.color;
+	out.w_tangent = vec4<f32>((model * vec4<f32>(in.tangent.xyz, 0.0)).xyz, in.tangent.w);
+	out.id = instance.id;
 
 	return out;
 }
@@ -42,11 +61,21 @@ fn main(in: VertexInput) -> VertexOutput {
 [[block]]
 struct PbrMaterial {
 	albedo: vec3<f32>;
+	lighting_baked: u32;
 	emission: vec3<f32>;
 	specular_bloom: f32;
+	uv_offset: vec2<f32>;
+	emission_strength: f32;
+	mip_bias: f32;
+	max_anisotropy: u32;
+	reflection: f32;
+	skip_shadow_cast: u32;
+	skip_shadow_receive: u32;
+	roughness: f32;
+	tint: vec4<f32>;
 };
 
-[[group(0), binding(2)]]
+[[group(0), binding(1)]]
 var<uniform> material: PbrMaterial;
 
 [[group(1), binding(0)]]
@@ -55,6 +84,13 @@ var t_albedo: texture_2d<f32>;
 [[group(1), binding(1)]]
 var t_emission: texture_2d<f32>;
 
+[[group(1), binding(2)]]
+var t_normal: texture_2d<f32>;
+
+// the mirror's reflection, rendered at screen size
+[[group(1), binding(3)]]
+var t_reflection: texture_2d<f32>;
+
 [[group(2), binding(0)]]
 var sampler: sampler;
 
@@ -63,19 +99,72 @@ struct FragmentOutput {
 	[[location(1)]] normal: vec4<f32>;
 	[[location(2)]] albedo: vec4<f32>;
 	[[location(3)]] emission: vec4<f32>;
+	// node id for picking, zero where there is none
+	[[location(4)]] id: u32;
 };
 
 [[stage(fragment)]]
 fn main(in: VertexOutput) -> FragmentOutput {
 	var out: FragmentOutput;
 
-	let albedo = textureSample(t_albedo, sampler, in.uv).rgb;
-	let emission = textureSample(t_emission, sampler, in.uv).rgb;
+	let uv = in.uv + material.uv_offset;
+
+	let albedo = textureSampleBias(t_albedo, sampler, uv, material.mip_bias).rgb;
+	let emission = textureSampleBias(t_emission, sampler, uv, material.mip_bias).rgb;
+
+	let tangent_normal = textureSampleBias(t_normal, sampler, uv, material.mip_bias).rgb * 2.0 - 1.0;
+
+	let n = normalize(in.w_normal.xyz);
+
+	// tangent frame from screen space derivatives for vertices without a tangent, computed
+	// outside the branch since derivatives need uniform control flow
+	let dp1 = dpdx(in.w_position.xyz);
+	let dp2 = dpdy(in.w_position.xyz);
+	let duv1 = dpdx(in.uv);
+	let duv2 = dpdy(in.uv);
+	let dp2perp = cross(dp2, n);
+	let dp1perp = cross(n, dp1);
+	let dt = dp2perp * duv1.x + dp1perp * duv2.x;
+	let db = dp2perp * duv1.y + dp1perp * duv2.y;
+	let inv_max = inverseSqrt(max(dot(dt, dt), dot(db, db)) + 0.00000001);
+
+	var t: vec3<f32> = dt * inv_max;
+	var b: vec3<f32> = db * inv_max;
+
+	if (abs(in.w_tangent.w) > 0.5) {
+		// the vertex tangent, made perpendicular again after interpolation
+		t = normalize(in.w_tangent.xyz - n * dot(n, in.w_tangent.xyz));
+		b = cross(n, t) * in.w_tangent.w;
+	}
+
+	let normal = normalize(t * tangent_normal.x + b * tangent_normal.y + n * tangent_normal.z);
 
 	out.position = vec4<f32>(in.w_position.xyz, material.specular_bloom);
-	out.normal = vec4<f32>(in.w_normal.xyz, 1.0);
-	out.albedo = vec4<f32>(material.albedo * albedo, 0.0);
-	out.emission = vec4<f32>(material.emission * emission, 0.0);
+	// w marks the pixel as lit by the light pass, 2 lights it without shadows
+	out.normal = vec4<f32>(normal, 1.0);
+	if (material.lighting_baked != 0u) {
+		out.normal.w = 0.0;
+	} elseif (material.skip_shadow_receive != 0u) {
+		out.normal.w = 2.0;
+	}
+	// the reflection was rendered with the mirrored camera, so points on the mirror line up
+	// with the same pixel in it
+	let reflection_uv = in.position.xy / vec2<f32>(textureDimensions(t_reflection));
+	let reflection = textureSample(t_reflection, sampler, reflection_uv).rgb * material.reflection;
+
+	// alpha is read by the light pass as roughness
+	out.albedo = vec4<f32>(material.albedo * material.tint.rgb * albedo * in.color.rgb * (1.0 - material.reflection), material.roughness);
+	out.emission = vec4<f32>(material.emission * emission * material.emission_strength + reflection, 0.0);
+	out.id = in.id;
+
+	// there is no transparent pass, so faded meshes drop a share of their pixels instead,
+	// picked by interleaved gradient noise
+	let dither = fract(52.9829189 * fract(dot(in.position.xy, vec2<f32>(0.06711056, 0.00583715))));
+
+	// after sampling, which has to happen in uniform control flow
+	if (dot(vec4<f32>(in.w_position.xyz, 1.0), camera.clip_plane) < 0.0 || material.tint.a <= dither) {
+		discard;
+	}
 
 	return out;
 }
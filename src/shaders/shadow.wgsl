@@ -6,27 +6,29 @@ struct VertexOutput {
 	[[builtin(position)]] position: vec4<f32>;
 };
 
-[[block]]
-struct Camera {
-	view_proj: mat4x4<f32>;
+// same as in separate.wgsl, so meshes draw from the same instance buffer, only the model is read
+struct InstanceInput {
+	[[location(5)]] model_0: vec4<f32>;
+	[[location(6)]] model_1: vec4<f32>;
+	[[location(7)]] model_2: vec4<f32>;
+	[[location(8)]] model_3: vec4<f32>;
 };
 
-[[group(0), binding(0)]]
-var<uniform> camera: Camera;
-
 [[block]]
-struct Transform {
-	model: mat4x4<f32>;
+struct Light {
+	view_proj: mat4x4<f32>;
 };
 
-[[group(0), binding(1)]]
-var<uniform> transform: Transform;
+[[group(0), binding(0)]]
+var<uniform> light: Light;
 
 [[stage(vertex)]]
-fn main(in: VertexInput) -> VertexOutput {
+fn main(in: VertexInput, instance: InstanceInput) -> VertexOutput {
 	var out: VertexOutput;
 
-	out.position = camera.view_proj * transform.model * out.w_position;
+	let model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+
+	out.position = light.view_proj * model * vec4<f32>(in.position, 1.0);
 
 	return out;
 }
@@ -0,0 +1,104 @@
+struct VertexOutput {
+	[[builtin(position)]] position: vec4<f32>;
+	[[location(0)]] uv: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn main([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+	var out: VertexOutput;
+
+	let x = -1.0 + f32((index & 1u) << 2u);
+	let y = -1.0 + f32((index & 2u) << 1u);
+	out.position = vec4<f32>(x, y, 0.0, 1.0);
+	out.uv = (vec2<f32>(x, y) + 1.0) / 2.0;
+	out.uv.y = 1.0 - out.uv.y;
+	
+	return out;
+}
+
+[[block]]
+struct Uniforms {
+	view_proj: mat4x4<f32>;
+	camera_position: vec3<f32>;
+	steps: u32;
+	max_distance: f32;
+	thickness: f32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> uniforms: Uniforms;
+
+[[group(1), binding(0)]]
+var t_position: texture_2d<f32>;
+
+[[group(1), binding(1)]]
+var t_normal: texture_2d<f32>;
+
+[[group(1), binding(2)]]
+var t_albedo: texture_2d<f32>;
+
+[[group(1), binding(3)]]
+var t_emission: texture_2d<f32>;
+
+[[group(1), binding(4)]]
+var t_light: texture_2d<f32>;
+
+[[group(2), binding(0)]]
+var sampler: sampler;
+
+// rgb is the reflected color, a how much of it to add, misses are left to the
+// environment reflection of the light pass
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+	let p = textureSampleLevel(t_position, sampler, in.uv, 0.0);
+	let n = textureSampleLevel(t_normal, sampler, in.uv, 0.0);
+
+	// specular strength stands in for smoothness, unlit and empty pixels don't reflect
+	let specular = p.w;
+	if (n.w == 0.0 || specular <= 0.0) {
+		return vec4<f32>(0.0, 0.0, 0.0, 0.0);
+	}
+
+	let view_dir = normalize(p.xyz - uniforms.camera_position);
+	let reflect_dir = reflect(view_dir, normalize(n.xyz));
+
+	let step_length = uniforms.max_distance / f32(max(uniforms.steps, 1u));
+
+	var i: u32 = 1u;
+	loop {
+		if (i > uniforms.steps) { break; }
+
+		let world = p.xyz + reflect_dir * step_length * f32(i);
+		let clip = uniforms.view_proj * vec4<f32>(world, 1.0);
+
+		if (clip.w <= 0.0) { break; }
+
+		let ndc = clip.xy / clip.w;
+		let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+
+		if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) { break; }
+
+		let scene_normal = textureSampleLevel(t_normal, sampler, uv, 0.0);
+		let scene = textureSampleLevel(t_position, sampler, uv, 0.0).xyz;
+
+		let ray_distance = distance(uniforms.camera_position, world);
+		let scene_distance = distance(uniforms.camera_position, scene);
+
+		// the ray went behind a surface close enough to count as hitting it
+		if (scene_normal.w != 0.0 && ray_distance > scene_distance && ray_distance - scene_distance < uniforms.thickness) {
+			let albedo = textureSampleLevel(t_albedo, sampler, uv, 0.0).rgb;
+			let emission = textureSampleLevel(t_emission, sampler, uv, 0.0).rgb;
+			let light = textureSampleLevel(t_light, sampler, uv, 0.0).rgb;
+
+			// fade out towards the screen edges and the end of the ray, where hits pop in and out
+			let edge = min(min(uv.x, 1.0 - uv.x), min(uv.y, 1.0 - uv.y));
+			let fade = clamp(edge * 10.0, 0.0, 1.0) * (1.0 - f32(i) / f32(uniforms.steps + 1u));
+
+			return vec4<f32>(albedo * light + emission, specular * fade);
+		}
+
+		i = i + 1u;
+	}
+
+	return vec4<f32>(0.0, 0.0, 0.0, 0.0);
+}
@@ -0,0 +1,125 @@
+use crate::{mesh::Aabb, node::NodeId};
+use glam::*;
+use std::collections::HashMap;
+
+// uniform grid of node bounds, rebuilt every update, for proximity queries
+#[derive(Clone)]
+pub struct SpatialHash {
+    pub cell_size: f32,
+    cells: HashMap<IVec3, Vec<NodeId>>,
+    bounds: HashMap<NodeId, Aabb>,
+}
+
+impl Default for SpatialHash {
+    #[inline]
+    fn default() -> Self {
+        Self::new(4.0)
+    }
+}
+
+impl SpatialHash {
+    #[inline]
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            bounds: HashMap::new(),
+        }
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.bounds.clear();
+    }
+
+    #[inline]
+    fn cell(&self, point: Vec3) -> IVec3 {
+        (point / self.cell_size).floor().as_i32()
+    }
+
+    // calls `f` with every cell overlapping `min..max`
+    #[inline]
+    fn cells(&self, min: Vec3, max: Vec3, mut f: impl FnMut(IVec3)) {
+        let min = self.cell(min);
+        let max = self.cell(max);
+
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    f(IVec3::new(x, y, z));
+                }
+            }
+        }
+    }
+
+    pub fn insert(&mut self, id: NodeId, bounds: Aabb) {
+        let mut cells = Vec::new();
+        self.cells(bounds.min, bounds.max, |cell| cells.push(cell));
+
+        for cell in cells {
+            self.cells.entry(cell).or_default().push(id);
+        }
+
+        self.bounds.insert(id, bounds);
+    }
+
+    // combined bounds of every node
+    #[inline]
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.bounds.values().copied().reduce(|a, b| a.union(&b))
+    }
+
+    // nodes whose bounds are within `radius` of `point`
+    pub fn nearby(&self, point: Vec3, radius: f32) -> Vec<NodeId> {
+        let mut nodes = Vec::new();
+
+        self.cells(point - radius, point + radius, |cell| {
+            if let Some(ids) = self.cells.get(&cell) {
+                for id in ids {
+                    if self.bounds[id].distance_squared(point) <= radius * radius {
+                        nodes.push(*id);
+                    }
+                }
+            }
+        });
+
+        // nodes spanning several cells are found once per cell
+        nodes.sort();
+        nodes.dedup();
+
+        nodes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearby_finds_only_local_nodes() {
+        let mut spatial = SpatialHash::default();
+
+        // a 10x10x10 grid of unit boxes two units apart
+        for i in 0..1000 {
+            let center = Vec3::new((i % 10) as f32, (i / 10 % 10) as f32, (i / 100) as f32) * 2.0;
+
+            spatial.insert(
+                NodeId(i),
+                Aabb {
+                    min: center - 0.5,
+                    max: center + 0.5,
+                },
+            );
+        }
+
+        let nearby = spatial
+            .nearby(Vec3::new(2.0, 2.0, 2.0), 1.5)
+            .iter()
+            .map(|id| id.0)
+            .collect::<Vec<_>>();
+
+        // the box around the point and its six neighbours, whose faces are 1.5 away
+        assert_eq!(nearby, [11, 101, 110, 111, 112, 121, 211]);
+    }
+}
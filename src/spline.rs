@@ -0,0 +1,102 @@
+use glam::*;
+
+#[inline]
+fn segments(len: usize, closed: bool) -> usize {
+    if closed {
+        len
+    } else {
+        len.saturating_sub(1)
+    }
+}
+
+// returns the four control points and local parameter of the segment containing `t`
+fn segment(points: &[Vec3], closed: bool, t: f32) -> Option<([Vec3; 4], f32)> {
+    let len = points.len();
+    let segments = segments(len, closed);
+
+    if segments == 0 {
+        return None;
+    }
+
+    let t = if closed {
+        t.rem_euclid(1.0)
+    } else {
+        t.clamp(0.0, 1.0)
+    } * segments as f32;
+
+    let index = (t.floor() as usize).min(segments - 1);
+    let u = t - index as f32;
+
+    let point = |i: isize| {
+        if closed {
+            points[i.rem_euclid(len as isize) as usize]
+        } else {
+            points[i.max(0).min(len as isize - 1) as usize]
+        }
+    };
+
+    let i = index as isize;
+
+    Some(([point(i - 1), point(i), point(i + 1), point(i + 2)], u))
+}
+
+// samples a catmull-rom spline through `points` at `t` in [0, 1]
+pub fn sample(points: &[Vec3], closed: bool, t: f32) -> Option<Vec3> {
+    if points.len() == 1 {
+        return Some(points[0]);
+    }
+
+    let ([p0, p1, p2, p3], u) = segment(points, closed, t)?;
+
+    let u2 = u * u;
+    let u3 = u2 * u;
+
+    Some(
+        0.5 * (2.0 * p1
+            + (p2 - p0) * u
+            + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * u2
+            + (3.0 * p1 - p0 - 3.0 * p2 + p3) * u3),
+    )
+}
+
+// derivative of the spline at `t`, not normalized
+pub fn tangent(points: &[Vec3], closed: bool, t: f32) -> Option<Vec3> {
+    let ([p0, p1, p2, p3], u) = segment(points, closed, t)?;
+
+    let u2 = u * u;
+
+    Some(
+        0.5 * ((p2 - p0)
+            + 2.0 * (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * u
+            + 3.0 * (3.0 * p1 - p0 - 3.0 * p2 + p3) * u2),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: [Vec3; 4] = [
+        const_vec3!([0.0, 0.0, 0.0]),
+        const_vec3!([1.0, 0.0, 0.0]),
+        const_vec3!([1.0, 1.0, 0.0]),
+        const_vec3!([0.0, 1.0, 0.0]),
+    ];
+
+    #[test]
+    fn samples_open_and_closed_splines() {
+        let sample = |closed, t| sample(&SQUARE, closed, t).unwrap();
+
+        // an open spline runs from the first point to the last, halfway is in the middle of
+        // the second of three segments, bulging out past the straight line
+        assert_eq!(sample(false, 0.0), SQUARE[0]);
+        assert!(sample(false, 0.5).abs_diff_eq(Vec3::new(1.125, 0.5, 0.0), 1e-6));
+        assert!(sample(false, 1.0).abs_diff_eq(SQUARE[3], 1e-6));
+
+        // a closed one has a fourth segment back to the start, putting halfway on the third
+        // point
+        assert_eq!(sample(true, 0.0), SQUARE[0]);
+        assert!(sample(true, 0.5).abs_diff_eq(SQUARE[2], 1e-6));
+        assert!(sample(true, 1.0).abs_diff_eq(SQUARE[0], 1e-6));
+    }
+}
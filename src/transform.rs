@@ -1,7 +1,7 @@
 use glam::*;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Transform {
     pub translation: Vec3,
     pub rotation: Quat,
@@ -43,6 +43,17 @@ impl Transform {
         }
     }
 
+    #[inline]
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
     #[inline]
     pub fn matrix(&self) -> Mat4 {
         Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
@@ -50,8 +61,8 @@ impl Transform {
 
     #[inline]
     pub fn mul_vec3(&self, mut value: Vec3) -> Vec3 {
-        value = self.rotation * value;
         value *= self.scale;
+        value = self.rotation * value;
         value += self.translation;
         value
     }
@@ -69,10 +80,34 @@ impl Transform {
         }
     }
 
+    #[inline]
+    pub fn is_finite(&self) -> bool {
+        self.translation.is_finite() && self.rotation.is_finite() && self.scale.is_finite()
+    }
+
+    // leaves the rotation untouched if `target` is at the translation, when looking along
+    // `up` another up is picked, -z where possible so top-down views have -z at the top
     #[inline]
     pub fn look_at(&mut self, target: Vec3, up: Vec3) {
-        let forward = (self.translation - target).normalize();
-        let right = up.cross(forward).normalize();
+        let forward = (self.translation - target).normalize_or_zero();
+
+        if forward == Vec3::ZERO {
+            return;
+        }
+
+        let mut right = up.cross(forward);
+
+        // nearly parallel vectors give a cross product too short to normalize accurately
+        if right.length_squared() < 1e-6 * up.length_squared().max(f32::MIN_POSITIVE) {
+            let alternate = if forward.z.abs() < 0.9 {
+                -Vec3::Z
+            } else {
+                Vec3::Y
+            };
+            right = alternate.cross(forward);
+        }
+
+        let right = right.normalize();
         let up = forward.cross(right).normalize();
 
         self.rotation = Quat::from_mat3(&Mat3::from_cols(right, up, forward));
@@ -87,3 +122,65 @@ impl Transform {
         }
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct GlobalTransform {
+    pub matrix: Mat4,
+    pub dirty: bool,
+    last_local: Option<Transform>,
+}
+
+impl Default for GlobalTransform {
+    fn default() -> Self {
+        Self {
+            matrix: Mat4::IDENTITY,
+            dirty: true,
+            last_local: None,
+        }
+    }
+}
+
+impl GlobalTransform {
+    #[inline]
+    pub fn translation(&self) -> Vec3 {
+        self.matrix.w_axis.truncate()
+    }
+
+    // marks the transform dirty if `local` has changed since the matrix was last computed
+    #[inline]
+    pub fn check_local(&mut self, local: &Transform) {
+        if self.last_local.as_ref() != Some(local) {
+            self.dirty = true;
+        }
+    }
+
+    #[inline]
+    pub fn recompute(&mut self, parent: Mat4, local: &Transform) {
+        self.matrix = parent * local.matrix();
+        self.last_local = Some(local.clone());
+        self.dirty = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn look_at_straight_down_stays_finite() {
+        let mut transform = Transform::from_xyz(0.0, 10.0, 0.0);
+        transform.look_at(Vec3::ZERO, Vec3::Y);
+
+        assert!(transform.rotation.is_finite());
+        assert!(transform.rotation.is_normalized());
+
+        // looks down the -z axis like any camera, with -z of the world at the top
+        assert!((transform.rotation * -Vec3::Z).abs_diff_eq(-Vec3::Y, 1e-5));
+        assert!((transform.rotation * Vec3::Y).abs_diff_eq(-Vec3::Z, 1e-5));
+
+        // a target on the camera keeps the rotation
+        let rotation = transform.rotation;
+        transform.look_at(transform.translation, Vec3::Y);
+        assert_eq!(transform.rotation, rotation);
+    }
+}
@@ -12,13 +12,22 @@ pub struct UiVertex {
 pub struct UiMesh {
     pub vertices: Vec<UiVertex>,
     pub indices: Vec<u32>,
+    // min and max corners in pixels, nothing outside is drawn
+    pub clip_rect: Vec4,
+    // meshes are drawn in ascending layer order, in submission order within a layer
+    pub layer: i32,
 }
 
 impl UiMesh {
+    // egui's windows are drawn over any game ui
+    pub const EGUI_LAYER: i32 = i32::MAX;
+
     pub fn new() -> Self {
         Self {
             vertices: Vec::new(),
             indices: Vec::new(),
+            clip_rect: Vec4::new(0.0, 0.0, f32::INFINITY, f32::INFINITY),
+            layer: 0,
         }
     }
 }
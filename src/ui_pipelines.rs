@@ -94,7 +94,8 @@ pub fn ui_pipeline(instance: &Instance, sc_format: wgpu::TextureFormat) -> wgpu:
                 module: &shader,
                 targets: &[wgpu::ColorTargetState {
                     format: sc_format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    // egui outputs premultiplied alpha
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
                     write_mask: wgpu::ColorWrite::ALL,
                 }],
                 entry_point: "main",
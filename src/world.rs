@@ -1,15 +1,23 @@
 use crate::{
+    gltf::{load_buffers, ExportMesh},
     instance::Instance,
-    mesh::Mesh,
-    node::{Node, NodeId},
-    renderer::Frame,
+    loader::{AssetLoader, Decoded, Job},
+    mesh::{Aabb, Mesh, RayHit, Vertex},
+    node::{Component, LightKind, Lod, Node, NodeId, Projection},
+    renderer::{normal_matrix, DirectionalLight, Frame, Tonemap, Viewport},
+    rng::Rng,
+    spatial::SpatialHash,
+    transform::{GlobalTransform, Transform},
 };
+use bytemuck::bytes_of;
 use gltf::Gltf;
-use image::{EncodableLayout, GenericImageView};
+use image::EncodableLayout;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    cell::RefCell,
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
     fs::read_to_string,
+    hash::{Hash, Hasher},
     io::BufReader,
     path::{Path, PathBuf},
     sync::Arc,
@@ -20,7 +28,81 @@ pub struct Resources {
     pub instance: Instance,
     pub meshes: HashMap<PathBuf, Mesh>,
     pub textures: HashMap<PathBuf, Arc<wgpu::TextureView>>,
+    // linear copies of the textures meshes use as normal maps, keyed like `textures`, made by
+    // `prepare_normal_maps` since textures are uploaded as srgb
+    pub normal_maps: HashMap<PathBuf, Arc<wgpu::TextureView>>,
+    // the textures behind the views in `textures`, normal maps are copied from them
+    images: HashMap<PathBuf, wgpu::Texture>,
+    // width and height of every loaded texture, views don't know their size
+    pub texture_sizes: HashMap<PathBuf, (u32, u32)>,
     pub worlds: HashMap<PathBuf, World>,
+    // asset directories in search order, assets are keyed by the root's name joined with
+    // their path inside it, e.g. 'assets/desk.gltf'
+    pub roots: Vec<PathBuf>,
+    // asset key to the file it was loaded from
+    pub files: HashMap<PathBuf, PathBuf>,
+    missing_textures: RefCell<HashSet<PathBuf>>,
+    // scale applied to meshes without their own `.import` file
+    pub import_scale: f32,
+    // decodes meshes and images off the main thread, they're loaded synchronously without it
+    loader: Option<AssetLoader>,
+}
+
+// per mesh import options, read from a sidecar next to the mesh, e.g. 'desk.gltf.import'
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImportSettings {
+    pub scale: f32,
+}
+
+impl Default for ImportSettings {
+    #[inline]
+    fn default() -> Self {
+        Self { scale: 1.0 }
+    }
+}
+
+impl ImportSettings {
+    // `None` when `path` has no sidecar
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        let mut sidecar = path.as_os_str().to_owned();
+        sidecar.push(".import");
+
+        match read_to_string(&sidecar) {
+            Ok(ron) => Ok(Some(ron::de::from_str(&ron)?)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[inline]
+fn root_name(root: &Path) -> &Path {
+    root.file_name().map(Path::new).unwrap_or(root)
+}
+
+// the key an asset loaded from `path` is stored under, the first root containing it wins
+fn asset_key(roots: &[PathBuf], path: &Path) -> PathBuf {
+    for root in roots {
+        if let Ok(relative) = path.strip_prefix(root) {
+            return root_name(root).join(relative);
+        }
+    }
+
+    path.to_path_buf()
+}
+
+// looks `path` up as a key, then relative to every root in order
+fn find_asset<'a, T>(
+    roots: &[PathBuf],
+    map: &'a HashMap<PathBuf, T>,
+    path: &Path,
+) -> Option<&'a T> {
+    map.get(path).or_else(|| {
+        roots
+            .iter()
+            .find_map(|root| map.get(&root_name(root).join(path)))
+    })
 }
 
 impl Resources {
@@ -29,27 +111,154 @@ impl Resources {
             instance: instance.clone(),
             meshes: HashMap::new(),
             textures: HashMap::new(),
+            normal_maps: HashMap::new(),
+            images: HashMap::new(),
+            texture_sizes: HashMap::new(),
             worlds: HashMap::new(),
+            roots: Vec::new(),
+            files: HashMap::new(),
+            missing_textures: RefCell::new(HashSet::new()),
+            import_scale: 1.0,
+            loader: None,
+        }
+    }
+
+    pub const DEFAULT_LOADER_THREADS: usize = 2;
+
+    // 0 loads assets on the calling thread, assets still queued on the old loader are dropped
+    pub fn set_loader_threads(&mut self, threads: usize) {
+        self.loader = (threads > 0).then(|| AssetLoader::new(&self.instance, threads));
+    }
+
+    // stops the loader threads and releases every gpu asset, the device must be idle
+    pub fn shutdown(&mut self) {
+        self.loader = None;
+        self.worlds.clear();
+        self.meshes.clear();
+        self.textures.clear();
+        self.normal_maps.clear();
+        self.images.clear();
+        self.texture_sizes.clear();
+    }
+
+    // (done, queued) while assets are still loading in the background
+    #[inline]
+    pub fn loading_progress(&self) -> Option<(usize, usize)> {
+        self.loader.as_ref().and_then(AssetLoader::progress)
+    }
+
+    // uploads the assets the loader finished decoding, called once per frame
+    pub fn poll_loader(&mut self) {
+        let finished = match &mut self.loader {
+            Some(loader) => loader.finished(),
+            None => return,
+        };
+
+        for (key, decoded) in finished {
+            match decoded {
+                Ok(Decoded::Meshes(meshes)) => self.upload_meshes(meshes),
+                Ok(Decoded::Image(image)) => self.upload_image(key, &image),
+                Err(err) => {
+                    log::warn!("failed to load '{}': {}", key.display(), err);
+                    self.files.remove(&key);
+                }
+            }
+        }
+
+        // textures asked for before they finished loading aren't missing anymore
+        let (roots, textures) = (&self.roots, &self.textures);
+        self.missing_textures
+            .get_mut()
+            .retain(|path| find_asset(roots, textures, path).is_none());
+    }
+
+    fn queue_mesh(&mut self, path: PathBuf) -> anyhow::Result<()> {
+        if self.loader.is_none() {
+            return self.load_mesh(path);
+        }
+
+        log::debug!("queueing mesh: '{:?}'", path);
+
+        let key = self.asset_key(&path);
+        let scale = self.mesh_import_scale(&path)?;
+
+        // registered right away, so later roots see it as loaded and don't shadow it
+        self.files.insert(key.clone(), path.clone());
+
+        self.loader
+            .as_mut()
+            .unwrap()
+            .queue(Job::Mesh { path, key, scale });
+
+        Ok(())
+    }
+
+    fn queue_image(&mut self, path: PathBuf) -> anyhow::Result<()> {
+        if self.loader.is_none() {
+            return self.load_image(path);
         }
+
+        log::debug!("queueing image: '{:?}'", path);
+
+        let key = self.asset_key(&path);
+        self.files.insert(key.clone(), path.clone());
+
+        self.loader
+            .as_mut()
+            .unwrap()
+            .queue(Job::Image { path, key });
+
+        Ok(())
+    }
+
+    // adds `root` to the search path and loads everything in it, assets already loaded from
+    // an earlier root are kept
+    pub fn load_assets(&mut self, root: impl Into<PathBuf>) -> anyhow::Result<()> {
+        let root = root.into();
+
+        if !self.roots.contains(&root) {
+            self.roots.push(root.clone());
+        }
+
+        self.load_dir(&root)
+    }
+
+    // loads every root again, assets stay usable until their reloaded versions replace them
+    pub fn reload_assets(&mut self) -> anyhow::Result<()> {
+        self.files.clear();
+        self.missing_textures.borrow_mut().clear();
+
+        for root in self.roots.clone() {
+            self.load_dir(&root)?;
+        }
+
+        Ok(())
     }
 
-    pub fn load_assets(&mut self, path: impl AsRef<Path>) -> anyhow::Result<()> {
-        let dir = std::fs::read_dir(path.as_ref())?;
+    fn load_dir(&mut self, dir: &Path) -> anyhow::Result<()> {
+        let dir = std::fs::read_dir(dir)?;
 
         for entry in dir {
             let entry = entry?;
 
             if entry.path().is_dir() {
-                self.load_assets(entry.path())?;
+                self.load_dir(&entry.path())?;
             } else {
                 let path = entry.path();
+
+                if self.files.contains_key(&self.asset_key(&path)) {
+                    log::debug!("'{:?}' is shadowed by an earlier asset root", path);
+                    continue;
+                }
+
                 if let Some(ext) = path.extension() {
                     match ext.to_str().unwrap().to_lowercase().as_str() {
-                        "gltf" => self.load_mesh(path)?,
+                        "gltf" | "glb" => self.queue_mesh(path)?,
+                        // worlds are small and needed right away, so they're never queued
                         "world" => self.load_world(path)?,
-                        "png" => self.load_image(path)?,
-                        "jpeg" => self.load_image(path)?,
-                        "jpg" => self.load_image(path)?,
+                        "png" => self.queue_image(path)?,
+                        "jpeg" => self.queue_image(path)?,
+                        "jpg" => self.queue_image(path)?,
                         _ => {}
                     }
                 }
@@ -59,8 +268,24 @@ impl Resources {
         Ok(())
     }
 
+    // the key an asset loaded from `path` is stored under
+    #[inline]
+    pub fn asset_key(&self, path: &Path) -> PathBuf {
+        asset_key(&self.roots, path)
+    }
+
+    #[inline]
+    fn find<'a, T>(&self, map: &'a HashMap<PathBuf, T>, path: &Path) -> Option<&'a T> {
+        find_asset(&self.roots, map, path)
+    }
+
+    // the file an asset was loaded from
+    pub fn file_path(&self, path: impl AsRef<Path>) -> Option<&PathBuf> {
+        self.find(&self.files, path.as_ref())
+    }
+
     pub fn get_world(&self, path: impl AsRef<Path>) -> Option<&World> {
-        self.worlds.get(path.as_ref())
+        self.find(&self.worlds, path.as_ref())
     }
 
     pub fn load_world(&mut self, path: impl Into<PathBuf>) -> anyhow::Result<()> {
@@ -69,15 +294,26 @@ impl Resources {
         log::debug!("loading world: '{:?}'", path);
 
         let string = read_to_string(&path)?;
-        let world = ron::from_str(&string)?;
 
-        self.worlds.insert(path, world);
+        let key = self.asset_key(&path);
+        self.load_world_from_str(key.clone(), &string)?;
+        self.files.insert(key, path);
+
+        Ok(())
+    }
+
+    pub fn load_world_from_str(
+        &mut self,
+        key: impl Into<PathBuf>,
+        ron: &str,
+    ) -> anyhow::Result<()> {
+        self.worlds.insert(key.into(), World::from_ron(ron)?);
 
         Ok(())
     }
 
     pub fn get_mesh(&self, path: impl AsRef<Path>) -> Option<&Mesh> {
-        self.meshes.get(path.as_ref())
+        self.find(&self.meshes, path.as_ref())
     }
 
     pub fn load_mesh(&mut self, path: impl Into<PathBuf>) -> anyhow::Result<()> {
@@ -85,19 +321,182 @@ impl Resources {
 
         log::debug!("loading mesh: '{:?}'", path);
 
-        let gltf = Gltf::open(&path)?;
-        let mut mesh = Mesh::new(&self.instance);
-        mesh.load_gltf(&gltf)?;
+        let import_scale = self.mesh_import_scale(&path)?;
+
+        self.load_mesh_scaled(path, import_scale)
+    }
+
+    // the scale `load_mesh` loads the mesh file at `path` with
+    pub fn mesh_import_scale(&self, path: &Path) -> anyhow::Result<f32> {
+        Ok(match ImportSettings::load(path)? {
+            Some(settings) => settings.scale,
+            None => self.import_scale,
+        })
+    }
+
+    // key of a single mesh in a file with several, loaded alongside the flattened mesh
+    #[inline]
+    pub fn gltf_mesh_key(key: &str, index: usize) -> String {
+        format!("{}#mesh{}", key, index)
+    }
+
+    // loads a mesh with its positions multiplied by `scale`, e.g. 0.01 for centimeters
+    pub fn load_mesh_scaled(&mut self, path: impl Into<PathBuf>, scale: f32) -> anyhow::Result<()> {
+        let path = path.into();
 
-        mesh.generate_buffers();
+        let key = self.asset_key(&path);
+        let meshes = Self::decode_mesh(&self.instance, &path, &key, scale)?;
 
-        self.meshes.insert(path, mesh);
+        self.upload_meshes(meshes);
+        self.files.insert(key, path);
 
         Ok(())
     }
 
+    // reads a mesh file into meshes keyed for `meshes`, without creating any gpu buffers
+    pub fn decode_mesh(
+        instance: &Instance,
+        path: &Path,
+        key: &Path,
+        scale: f32,
+    ) -> anyhow::Result<Vec<(PathBuf, Mesh)>> {
+        let gltf = Gltf::open(path)?;
+        let buffer_data = load_buffers(&gltf, path.parent())?;
+
+        let mut mesh = Mesh::new(instance);
+        mesh.load_gltf(&gltf, &buffer_data)?;
+
+        let mut meshes = vec![(key.to_path_buf(), mesh)];
+
+        // each part on its own too, for `World::spawn_gltf`
+        if gltf.meshes().len() > 1 {
+            for gltf_mesh in gltf.meshes() {
+                let mut mesh = Mesh::new(instance);
+                mesh.append_gltf_mesh(&gltf_mesh, &buffer_data)?;
+
+                let part = Self::gltf_mesh_key(&key.to_string_lossy(), gltf_mesh.index());
+                meshes.push((PathBuf::from(part), mesh));
+            }
+        }
+
+        if scale != 1.0 {
+            for (_, mesh) in &mut meshes {
+                mesh.scale(scale);
+            }
+        }
+
+        Ok(meshes)
+    }
+
+    fn upload_meshes(&mut self, meshes: Vec<(PathBuf, Mesh)>) {
+        for (key, mut mesh) in meshes {
+            mesh.generate_buffers();
+
+            self.meshes.insert(key, mesh);
+        }
+    }
+
     pub fn get_texture(&self, path: impl AsRef<Path>) -> Option<&Arc<wgpu::TextureView>> {
-        self.textures.get(path.as_ref())
+        self.find(&self.textures, path.as_ref())
+    }
+
+    #[inline]
+    pub fn texture_size(&self, path: impl AsRef<Path>) -> Option<(u32, u32)> {
+        self.find(&self.texture_sizes, path.as_ref()).copied()
+    }
+
+    // like `get_texture`, but an empty path means no texture and missing paths are logged once,
+    // textures still queued on the loader aren't missing
+    pub fn texture_slot(&self, path: &str) -> Option<&Arc<wgpu::TextureView>> {
+        if path.is_empty() {
+            return None;
+        }
+
+        let texture = self.get_texture(path);
+
+        if texture.is_none()
+            && self.file_path(path).is_none()
+            && self
+                .missing_textures
+                .borrow_mut()
+                .insert(PathBuf::from(path))
+        {
+            log::warn!("texture '{}' not found, using flat color", path);
+        }
+
+        texture
+    }
+
+    // like `texture_slot`, but the linear copy made by `prepare_normal_maps`
+    pub fn normal_slot(&self, path: &str) -> Option<&Arc<wgpu::TextureView>> {
+        self.texture_slot(path)?;
+        self.find(&self.normal_maps, Path::new(path))
+    }
+
+    // copies the textures `world` uses as normal maps into linear textures, so the shader reads
+    // the stored values rather than srgb decoded ones, called once per frame
+    pub fn prepare_normal_maps(&mut self, world: &World) {
+        let mut keys = HashSet::new();
+
+        for node in world.nodes.values() {
+            for component in &node.components {
+                if let Component::Mesh { normal, .. } = component {
+                    if let Some(key) = self.find_key(&self.textures, Path::new(normal)) {
+                        if !self.normal_maps.contains_key(&key) {
+                            keys.insert(key);
+                        }
+                    }
+                }
+            }
+        }
+
+        if keys.is_empty() {
+            return;
+        }
+
+        let device = &self.instance.device;
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("normal map copy"),
+        });
+
+        for key in keys {
+            let (width, height) = self.texture_sizes[&key];
+            let size = wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            };
+
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("normal map"),
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                size,
+                dimension: wgpu::TextureDimension::D2,
+                mip_level_count: 1,
+                sample_count: 1,
+                usage: wgpu::TextureUsage::COPY_DST | wgpu::TextureUsage::SAMPLED,
+            });
+
+            // a plain copy between formats of the same size, the bytes aren't decoded
+            encoder.copy_texture_to_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &self.images[&key],
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                size,
+            );
+
+            let view = texture.create_view(&Default::default());
+            self.normal_maps.insert(key, Arc::new(view));
+        }
+
+        self.instance.queue.submit(Some(encoder.finish()));
     }
 
     pub fn load_image(&mut self, path: impl Into<PathBuf>) -> anyhow::Result<()> {
@@ -105,8 +504,21 @@ impl Resources {
 
         log::debug!("loading image: '{:?}'", path);
 
-        let png = image::open(&path)?;
+        let image = Self::decode_image(&path)?;
+
+        let key = self.asset_key(&path);
+        self.upload_image(key.clone(), &image);
+        self.files.insert(key, path);
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn decode_image(path: &Path) -> anyhow::Result<image::RgbaImage> {
+        Ok(image::open(path)?.to_rgba8())
+    }
 
+    fn upload_image(&mut self, key: PathBuf, png: &image::RgbaImage) {
         let texture = self.instance.device.create_texture_with_data(
             &self.instance.queue,
             &wgpu::TextureDescriptor {
@@ -120,9 +532,11 @@ impl Resources {
                 dimension: wgpu::TextureDimension::D2,
                 mip_level_count: 1,
                 sample_count: 1,
-                usage: wgpu::TextureUsage::COPY_DST | wgpu::TextureUsage::SAMPLED,
+                usage: wgpu::TextureUsage::COPY_SRC
+                    | wgpu::TextureUsage::COPY_DST
+                    | wgpu::TextureUsage::SAMPLED,
             },
-            png.to_rgba8().as_bytes(),
+            png.as_bytes(),
         );
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor {
@@ -136,17 +550,229 @@ impl Resources {
             array_layer_count: None,
         });
 
-        self.textures.insert(path, Arc::new(view));
+        // a reloaded normal map is copied again
+        self.normal_maps.remove(&key);
+        self.images.insert(key.clone(), texture);
+        self.textures.insert(key.clone(), Arc::new(view));
+        self.texture_sizes.insert(key, (png.width(), png.height()));
+    }
+
+    // key `path` is stored under in `map`, resolved like `find`
+    fn find_key<T>(&self, map: &HashMap<PathBuf, T>, path: &Path) -> Option<PathBuf> {
+        if map.contains_key(path) {
+            return Some(path.to_path_buf());
+        }
 
-        Ok(())
+        self.roots
+            .iter()
+            .map(|root| root_name(root).join(path))
+            .find(|key| map.contains_key(key))
+    }
+
+    // drops a mesh along with the parts, lods and baked meshes made from it, their buffers are
+    // freed once the gpu is done with them, false if it wasn't loaded
+    pub fn unload_mesh(&mut self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+
+        let key = match self.find_key(&self.meshes, path) {
+            Some(key) => key,
+            None => return false,
+        };
+
+        // derived meshes are keyed by the path they were made from, which may lack the root
+        let prefixes = [
+            format!("{}#", key.to_string_lossy()),
+            format!("{}#", path.to_string_lossy()),
+        ];
+
+        self.meshes.retain(|other, _| {
+            let derived = prefixes
+                .iter()
+                .any(|prefix| other.to_string_lossy().starts_with(prefix.as_str()));
+
+            *other != key && !derived
+        });
+        self.files.remove(&key);
+
+        // a reload may have queued it again
+        self.cancel_queued(&key);
+
+        log::debug!("unloaded mesh: '{:?}'", key);
+
+        true
+    }
+
+    // false if the texture wasn't loaded
+    pub fn unload_texture(&mut self, path: impl AsRef<Path>) -> bool {
+        let key = match self.find_key(&self.textures, path.as_ref()) {
+            Some(key) => key,
+            None => return false,
+        };
+
+        self.textures.remove(&key);
+        self.normal_maps.remove(&key);
+        self.images.remove(&key);
+        self.texture_sizes.remove(&key);
+        self.files.remove(&key);
+
+        self.cancel_queued(&key);
+
+        log::debug!("unloaded texture: '{:?}'", key);
+
+        true
+    }
+
+    // drops an asset still queued on the loader, so it isn't added once it's decoded, false if
+    // it isn't queued
+    pub fn cancel_load(&mut self, path: impl AsRef<Path>) -> bool {
+        let key = match self.find_key(&self.files, path.as_ref()) {
+            Some(key) => key,
+            None => return false,
+        };
+
+        if !self.cancel_queued(&key) {
+            return false;
+        }
+
+        self.files.remove(&key);
+
+        log::debug!("cancelled loading: '{:?}'", key);
+
+        true
+    }
+
+    #[inline]
+    fn cancel_queued(&mut self, key: &Path) -> bool {
+        self.loader
+            .as_mut()
+            .is_some_and(|loader| loader.cancel(key))
+    }
+
+    // false if the world wasn't loaded
+    pub fn unload_world(&mut self, path: impl AsRef<Path>) -> bool {
+        let key = match self.find_key(&self.worlds, path.as_ref()) {
+            Some(key) => key,
+            None => return false,
+        };
+
+        self.worlds.remove(&key);
+        self.files.remove(&key);
+
+        log::debug!("unloaded world: '{:?}'", key);
+
+        true
+    }
+
+    // drops every mesh and texture `world` doesn't use, returns how many were dropped, unused
+    // assets still queued on the loader are cancelled
+    pub fn unload_unused(&mut self, world: &World) -> usize {
+        let mut meshes = Vec::new();
+        let mut textures = vec![world.data.render_settings.environment.as_str()];
+
+        for node in world.nodes.values() {
+            for component in &node.components {
+                match component {
+                    Component::Mesh {
+                        mesh,
+                        albedo,
+                        emission,
+                        normal,
+                        lods,
+                        baked,
+                        ..
+                    } => {
+                        meshes.push(mesh.as_str());
+                        meshes.extend(lods.iter().map(|lod| lod.mesh.as_str()));
+                        meshes.extend(baked.iter().map(|(mesh, _)| mesh.as_str()));
+                        textures.extend([albedo.as_str(), emission.as_str(), normal.as_str()]);
+                    }
+                    Component::Scatter { mesh, albedo, .. } => {
+                        meshes.push(mesh.as_str());
+                        textures.push(albedo.as_str());
+                    }
+                    Component::ParallaxLayer { texture, .. } => textures.push(texture.as_str()),
+                    _ => {}
+                }
+            }
+        }
+
+        // files of used assets, which may still be loading, parts are loaded with their file
+        let used_files = meshes
+            .iter()
+            .map(|mesh| mesh.split('#').next().unwrap())
+            .chain(textures.iter().copied())
+            .filter_map(|asset| self.find_key(&self.files, Path::new(asset)))
+            .collect::<HashSet<_>>();
+
+        let used_meshes = meshes
+            .into_iter()
+            .filter_map(|mesh| self.find_key(&self.meshes, Path::new(mesh)))
+            .collect::<HashSet<_>>();
+        let used_textures = textures
+            .into_iter()
+            .filter_map(|texture| self.find_key(&self.textures, Path::new(texture)))
+            .collect::<HashSet<_>>();
+
+        let before = self.meshes.len() + self.textures.len();
+
+        self.meshes.retain(|key, _| used_meshes.contains(key));
+        self.textures.retain(|key, _| used_textures.contains(key));
+
+        let textures = &self.textures;
+        self.normal_maps.retain(|key, _| textures.contains_key(key));
+        self.images.retain(|key, _| textures.contains_key(key));
+        self.texture_sizes
+            .retain(|key, _| textures.contains_key(key));
+
+        let (meshes, worlds) = (&self.meshes, &self.worlds);
+        let loader = &mut self.loader;
+
+        self.files.retain(|key, _| {
+            let loaded =
+                meshes.contains_key(key) || textures.contains_key(key) || worlds.contains_key(key);
+
+            if loaded || used_files.contains(key) {
+                return true;
+            }
+
+            if let Some(loader) = loader {
+                loader.cancel(key);
+            }
+
+            false
+        });
+
+        before - self.meshes.len() - self.textures.len()
     }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct RenderSettings {
     pub bloom: f32,
     pub ambient_color: glam::Vec3,
     pub ambient_strength: f32,
+    // equirectangular image prefiltered by `Ibl` for ambient light and reflections, falls back
+    // to flat ambient if empty
+    pub environment: String,
+    pub environment_strength: f32,
+    // draws meshes with lighting baked by `World::bake_lighting` where available
+    pub baked_lighting: bool,
+    // contact-hardening shadows, `pcss` off falls back to a fixed pcf kernel, `shadow_softness`
+    // is the filter radius in shadow map texels where occluders touch the receiver and
+    // `light_size` how much its radius grows per unit of distance to the occluder
+    pub pcss: bool,
+    pub shadow_softness: f32,
+    pub light_size: f32,
+    // screen space reflections, reflectivity follows the material's specular
+    pub ssr: bool,
+    pub ssr_steps: u32,
+    pub ssr_max_distance: f32,
+    pub dof: bool,
+    pub focus_distance: f32,
+    pub aperture: f32,
+    pub tonemap: Tonemap,
+    pub exposure: f32,
 }
 
 impl Default for RenderSettings {
@@ -155,16 +781,96 @@ impl Default for RenderSettings {
             bloom: 0.1,
             ambient_color: glam::Vec3::ONE,
             ambient_strength: 0.0,
+            environment: String::new(),
+            environment_strength: 1.0,
+            baked_lighting: false,
+            pcss: true,
+            shadow_softness: 1.0,
+            light_size: 0.5,
+            ssr: false,
+            ssr_steps: 32,
+            ssr_max_distance: 10.0,
+            dof: false,
+            focus_distance: 10.0,
+            aperture: 0.5,
+            tonemap: Tonemap::None,
+            exposure: 1.0,
+        }
+    }
+}
+
+// what happens to nodes leaving `WorldData::bounds`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutOfBounds {
+    // only listed in `World::left_bounds`
+    Report,
+    // listed and despawned along with their children
+    Despawn,
+}
+
+impl Default for OutOfBounds {
+    #[inline]
+    fn default() -> Self {
+        Self::Report
+    }
+}
+
+impl OutOfBounds {
+    pub const ALL: [Self; 2] = [Self::Report, Self::Despawn];
+
+    #[inline]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Report => "Report",
+            Self::Despawn => "Despawn",
         }
     }
 }
 
-#[derive(Clone, Default, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct WorldData {
     pub render_settings: RenderSettings,
     pub player: Option<NodeId>,
+    // lights the scene when it has no directional light of its own
+    #[serde(default = "WorldData::default_sun")]
+    pub sun: Option<DirectionalLight>,
+    // min and max corner of the playable volume, nodes outside are handled by `out_of_bounds`
+    #[serde(default)]
+    pub bounds: Option<(glam::Vec3, glam::Vec3)>,
+    #[serde(default)]
+    pub out_of_bounds: OutOfBounds,
+    // starting state of `World::rng`, so a loaded scene plays out the same every time
+    #[serde(default)]
+    pub seed: u64,
+}
+
+impl Default for WorldData {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            render_settings: RenderSettings::default(),
+            player: None,
+            sun: Self::default_sun(),
+            bounds: None,
+            out_of_bounds: OutOfBounds::default(),
+            seed: 0,
+        }
+    }
+}
+
+impl WorldData {
+    #[inline]
+    fn default_sun() -> Option<DirectionalLight> {
+        Some(DirectionalLight {
+            direction: glam::Vec3::new(-0.3, -1.0, -0.5).normalize(),
+            ..Default::default()
+        })
+    }
 }
 
+pub const DEFAULT_WORLD_KEY: &str = "<default>";
+pub const DEFAULT_WORLD: &str = include_str!("default.world");
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct World {
     pub data: WorldData,
@@ -172,6 +878,31 @@ pub struct World {
     pub next_node_id: NodeId,
     #[serde(skip)]
     pub next_node_validated: bool,
+    #[serde(skip)]
+    pub delta_time: f32,
+    #[serde(skip)]
+    pub time: f32,
+    #[serde(skip)]
+    pub spatial: SpatialHash,
+    // batch keys already warned about mixing meshes
+    #[serde(skip)]
+    batch_warnings: RefCell<HashSet<String>>,
+    // forces the shadow map to be redrawn next frame, for changes node versions don't track,
+    // like edited mesh resources
+    #[serde(skip)]
+    pub refresh_shadows: bool,
+    // `shadow_key` of the last rendered frame
+    #[serde(skip)]
+    last_shadow_key: Option<u64>,
+    // nodes that left `WorldData::bounds` during the last update
+    #[serde(skip)]
+    pub left_bounds: Vec<NodeId>,
+    // nodes outside the bounds as of the last update, so leaving is only reported once
+    #[serde(skip)]
+    outside_bounds: HashSet<NodeId>,
+    // the one source of randomness for updates, seeded from `WorldData::seed`
+    #[serde(skip)]
+    pub rng: Rng,
 }
 
 impl World {
@@ -181,7 +912,41 @@ impl World {
             nodes: HashMap::new(),
             next_node_id: NodeId(0),
             next_node_validated: true,
+            delta_time: 0.0,
+            time: 0.0,
+            spatial: SpatialHash::default(),
+            batch_warnings: RefCell::new(HashSet::new()),
+            refresh_shadows: false,
+            last_shadow_key: None,
+            left_bounds: Vec::new(),
+            outside_bounds: HashSet::new(),
+            rng: Rng::new(0),
+        }
+    }
+
+    // parses a saved world, bringing components saved by older versions up to date
+    pub fn from_ron(ron: &str) -> anyhow::Result<Self> {
+        let mut world: Self = ron::from_str(ron)?;
+        world.migrate();
+
+        Ok(world)
+    }
+
+    // upgrades data saved by older versions to the current format
+    pub fn migrate(&mut self) {
+        for node in self.nodes.values_mut() {
+            for component in &mut node.components {
+                component.migrate();
+            }
         }
+
+        self.reseed();
+    }
+
+    // restarts `rng` from the saved seed
+    #[inline]
+    pub fn reseed(&mut self) {
+        self.rng = Rng::new(self.data.seed);
     }
 
     #[inline]
@@ -193,7 +958,7 @@ impl World {
                 .map(|id| *id)
                 .max()
                 .map(|id| NodeId(id.0 + 1))
-                .unwrap_or(NodeId(0)); 
+                .unwrap_or(NodeId(0));
 
             self.next_node_id = next_id;
             self.next_node_validated = true;
@@ -218,37 +983,1902 @@ impl World {
         id
     }
 
-    #[inline]
-    pub fn despawn(&mut self, id: &NodeId) {
-        self.nodes.remove(id);
-    }
+    // spawns a node for every loaded mesh under `dir`, laid out on a grid sized by their bounds
+    pub fn spawn_meshes(
+        &mut self,
+        resources: &Resources,
+        dir: impl AsRef<Path>,
+        spacing: f32,
+    ) -> Vec<NodeId> {
+        let dir = dir.as_ref();
 
-    #[inline]
-    pub fn node(&self, id: &NodeId) -> Option<&Node> {
+        let mut paths = resources
+            .meshes
+            .keys()
+            .filter(|path| path.starts_with(dir))
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        let columns = (paths.len() as f32).sqrt().ceil() as usize;
+
+        let mut ids = Vec::with_capacity(paths.len());
+
+        let mut x = 0.0;
+        let mut z = 0.0;
+        let mut row_depth = 0.0f32;
+
+        for (i, path) in paths.into_iter().enumerate() {
+            if i > 0 && i % columns == 0 {
+                x = 0.0;
+                z += row_depth + spacing;
+                row_depth = 0.0;
+            }
+
+            let mesh = &resources.meshes[path];
+
+            let (min, size) = match mesh.bounds() {
+                Some(bounds) => (bounds.min, bounds.size()),
+                None => (glam::Vec3::ZERO, glam::Vec3::ZERO),
+            };
+
+            // offset by the minimum so the mesh's bounds start at the cell corner
+            let translation = glam::Vec3::new(x - min.x, -min.y, z - min.z);
+
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| String::from("Mesh"));
+
+            let id = self.spawn(Node {
+                name,
+                transform: Transform::from_translation(translation),
+                parent: None,
+                global: GlobalTransform::default(),
+                components: vec![Component::mesh(path.to_string_lossy().into_owned())],
+                version: 0,
+            });
+
+            ids.push(id);
+
+            x += size.x + spacing;
+            row_depth = row_depth.max(size.z);
+        }
+
+        ids
+    }
+
+    // spawns the node tree of a loaded gltf file's default scene under a root node named after
+    // the file, nodes with a mesh draw their part of the file
+    pub fn spawn_gltf(&mut self, resources: &Resources, key: &str) -> anyhow::Result<NodeId> {
+        let path = resources
+            .file_path(key)
+            .ok_or_else(|| anyhow::anyhow!("'{}' isn't a loaded gltf file", key))?;
+
+        let gltf = Gltf::open(path)?;
+        let scale = resources.mesh_import_scale(path)?;
+
+        self.spawn_gltf_scene(&gltf, key, scale)
+    }
+
+    // spawns the nodes of `gltf`, whose meshes were loaded under `key` with `scale`
+    fn spawn_gltf_scene(&mut self, gltf: &Gltf, key: &str, scale: f32) -> anyhow::Result<NodeId> {
+        let single_mesh = gltf.meshes().len() == 1;
+
+        let scene = gltf
+            .default_scene()
+            .or_else(|| gltf.scenes().next())
+            .ok_or_else(|| anyhow::anyhow!("'{}' has no scene", key))?;
+
+        let root = self.spawn(Node {
+            name: Path::new(key)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| String::from("Mesh")),
+            transform: Transform::IDENTITY,
+            parent: None,
+            global: GlobalTransform::default(),
+            components: Vec::new(),
+            version: 0,
+        });
+
+        let mut stack = scene.nodes().map(|node| (node, root)).collect::<Vec<_>>();
+
+        while let Some((gltf_node, parent)) = stack.pop() {
+            let matrix = glam::Mat4::from_cols_array_2d(&gltf_node.transform().matrix());
+            let mut transform = Transform::from_matrix(matrix);
+
+            // the meshes are scaled on load, scaling offsets as well keeps the parts together
+            transform.translation *= scale;
+
+            let components = gltf_node
+                .mesh()
+                .map(|mesh| {
+                    if single_mesh {
+                        Component::mesh(String::from(key))
+                    } else {
+                        Component::mesh(Resources::gltf_mesh_key(key, mesh.index()))
+                    }
+                })
+                .into_iter()
+                .collect();
+
+            let id = self.spawn(Node {
+                name: gltf_node
+                    .name()
+                    .map(String::from)
+                    .unwrap_or_else(|| format!("Node {}", gltf_node.index())),
+                transform,
+                parent: Some(parent),
+                global: GlobalTransform::default(),
+                components,
+                version: 0,
+            });
+
+            stack.extend(gltf_node.children().map(|child| (child, id)));
+        }
+
+        Ok(root)
+    }
+
+    #[inline]
+    pub fn despawn(&mut self, id: &NodeId) {
+        self.nodes.remove(id);
+
+        // orphaned children were relative to the removed node
+        for node in self.nodes.values_mut() {
+            if node.parent.as_ref() == Some(id) {
+                node.global.dirty = true;
+            }
+        }
+    }
+
+    #[inline]
+    pub fn node(&self, id: &NodeId) -> Option<&Node> {
         self.nodes.get(id)
     }
 
+    // mutable access counts as a change
+    #[inline]
+    pub fn node_mut(&mut self, id: &NodeId) -> Option<&mut Node> {
+        let node = self.nodes.get_mut(id)?;
+        node.changed();
+        Some(node)
+    }
+
+    // nodes `f` accepts
+    #[inline]
+    pub fn query<'a>(
+        &'a self,
+        mut f: impl FnMut(&Node) -> bool + 'a,
+    ) -> impl Iterator<Item = (NodeId, &'a Node)> + 'a {
+        self.nodes
+            .iter()
+            .filter(move |(_, node)| f(node))
+            .map(|(id, node)| (*id, node))
+    }
+
+    // every component named `name`, e.g. `Component::CAMERA`, with the node it's on
+    #[inline]
+    pub fn iter_components<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = (NodeId, &'a Component)> + 'a {
+        self.nodes.iter().flat_map(move |(id, node)| {
+            node.components
+                .iter()
+                .filter(move |component| component.name() == name)
+                .map(move |component| (*id, component))
+        })
+    }
+
+    // nodes with a camera component ordered by id, so the same camera comes first every frame
+    pub fn cameras(&self) -> Vec<NodeId> {
+        let mut cameras = self
+            .iter_components(Component::CAMERA)
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+
+        cameras.sort_unstable();
+        cameras.dedup();
+        cameras
+    }
+
+    // the main camera, the one rendered first and the editor works through
+    #[inline]
+    pub fn camera(&self) -> Option<NodeId> {
+        self.cameras().first().copied()
+    }
+
+    // radians the camera turns per pixel of mouse movement
+    pub const LOOK_SPEED: f32 = 0.003;
+
+    // turns the main camera by `delta` pixels of mouse movement, yawing around the up axis and
+    // pitching short of looking straight up or down
+    pub fn mouse_look(&mut self, delta: glam::Vec2) {
+        if delta == glam::Vec2::ZERO {
+            return;
+        }
+
+        let node = match self.camera().and_then(|id| self.nodes.get_mut(&id)) {
+            Some(node) => node,
+            None => return,
+        };
+
+        let rotation = node.transform.rotation;
+        let pitch = (rotation * -glam::Vec3::Z).y.clamp(-1.0, 1.0).asin();
+
+        let limit = 89f32.to_radians();
+        let pitch_delta = (pitch - delta.y * Self::LOOK_SPEED).clamp(-limit, limit) - pitch;
+
+        let yaw = glam::Quat::from_rotation_y(-delta.x * Self::LOOK_SPEED);
+        let rotation = yaw * rotation * glam::Quat::from_rotation_x(pitch_delta);
+
+        node.transform.rotation = rotation.normalize();
+        node.changed();
+    }
+
+    // the camera component of `id`
     #[inline]
-    pub fn update(&mut self, resources: &Resources) {
+    fn camera_component(&self, id: NodeId) -> Option<&Component> {
+        self.node(&id)?
+            .components
+            .iter()
+            .find(|component| component.name() == Component::CAMERA)
+    }
+
+    // node, projection and viewport of the camera the scene is viewed through
+    fn view_camera(&self) -> Option<(NodeId, Projection, Viewport)> {
+        let id = self.camera()?;
+
+        match self.camera_component(id)? {
+            Component::Camera {
+                projection,
+                viewport,
+                ..
+            } => Some((id, *projection, *viewport)),
+            _ => None,
+        }
+    }
+
+    // world space ray from the camera through `cursor`, in pixels of a `screen_size` window
+    pub fn cursor_ray(
+        &self,
+        cursor: glam::Vec2,
+        screen_size: glam::Vec2,
+    ) -> Option<(glam::Vec3, glam::Vec3)> {
+        let (id, projection, viewport) = self.view_camera()?;
+
+        let [x, y, width, height] = viewport.pixels(screen_size.x as u32, screen_size.y as u32);
+
+        // normalized device coordinates inside the camera's viewport
+        let ndc_x = (cursor.x - x) / width * 2.0 - 1.0;
+        let ndc_y = 1.0 - (cursor.y - y) / height * 2.0;
+
+        // orthographic rays start on the camera plane and all point straight ahead
+        let (origin, direction) = match projection {
+            Projection::Perspective { fov } => {
+                let tan = (fov / 360.0 * std::f32::consts::PI).tan();
+                let direction = glam::Vec3::new(ndc_x * tan * width / height, ndc_y * tan, -1.0);
+
+                (glam::Vec3::ZERO, direction)
+            }
+            Projection::Orthographic {
+                height: view_height,
+            } => {
+                let half = view_height / 2.0;
+                let origin = glam::Vec3::new(ndc_x * half * width / height, ndc_y * half, 0.0);
+
+                (origin, -glam::Vec3::Z)
+            }
+        };
+
+        let matrix = self.node(&id)?.global.matrix;
+
+        Some((
+            matrix.transform_point3(origin),
+            matrix.transform_vector3(direction).normalize(),
+        ))
+    }
+
+    // pixel of a `screen_size` window `point` is seen at, the reverse of `cursor_ray`, `None`
+    // for points behind the camera
+    pub fn world_to_screen(
+        &self,
+        point: glam::Vec3,
+        screen_size: glam::Vec2,
+    ) -> Option<glam::Vec2> {
+        let (id, projection, viewport) = self.view_camera()?;
+
+        let [x, y, width, height] = viewport.pixels(screen_size.x as u32, screen_size.y as u32);
+
+        let local = self
+            .node(&id)?
+            .global
+            .matrix
+            .inverse()
+            .transform_point3(point);
+
+        if local.z >= 0.0 {
+            return None;
+        }
+
+        // half the height seen at the point's depth
+        let half = match projection {
+            Projection::Perspective { fov } => {
+                -local.z * (fov / 360.0 * std::f32::consts::PI).tan()
+            }
+            Projection::Orthographic {
+                height: view_height,
+            } => view_height / 2.0,
+        };
+
+        let ndc_x = local.x / (half * width / height);
+        let ndc_y = local.y / half;
+
+        Some(glam::Vec2::new(
+            x + (ndc_x + 1.0) / 2.0 * width,
+            y + (1.0 - ndc_y) / 2.0 * height,
+        ))
+    }
+
+    // nearest mesh component hit by the ray and the hit in world space, nodes `skip` accepts
+    // are ignored
+    pub fn raycast(
+        &self,
+        resources: &Resources,
+        origin: glam::Vec3,
+        direction: glam::Vec3,
+        skip: impl Fn(NodeId) -> bool,
+    ) -> Option<(NodeId, RayHit)> {
+        let mut nearest: Option<(NodeId, RayHit)> = None;
+
+        for (id, component) in self.iter_components(Component::MESH) {
+            let mesh = match component {
+                Component::Mesh { mesh, .. } => mesh,
+                _ => continue,
+            };
+
+            if skip(id) {
+                continue;
+            }
+
+            let mesh = match resources.get_mesh(mesh) {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+
+            let matrix = self.nodes[&id].global.matrix;
+            let inverse = matrix.inverse();
+
+            // `t` is in multiples of the direction, so it's the same in both spaces
+            let hit = match mesh.raycast(
+                inverse.transform_point3(origin),
+                inverse.transform_vector3(direction),
+            ) {
+                Some(hit) => hit,
+                None => continue,
+            };
+
+            if nearest
+                .as_ref()
+                .is_some_and(|(_, nearest)| nearest.t <= hit.t)
+            {
+                continue;
+            }
+
+            let normal = inverse
+                .transpose()
+                .transform_vector3(hit.normal)
+                .normalize_or_zero();
+
+            nearest = Some((
+                id,
+                RayHit {
+                    position: matrix.transform_point3(hit.position),
+                    normal,
+                    ..hit
+                },
+            ));
+        }
+
+        nearest
+    }
+
+    // the transform of `id` relative to the world, composed from the local transforms up the
+    // parent chain rather than read from `Node::global`, so it's current between updates
+    pub fn global_transform(&self, id: &NodeId) -> Transform {
+        let mut node = match self.node(id) {
+            Some(node) => node,
+            None => return Transform::IDENTITY,
+        };
+
+        let mut transform = node.transform.clone();
+
+        // bounded, so a cycle of parents can't loop forever
+        for _ in 0..self.nodes.len() {
+            match node.parent.as_ref().and_then(|parent| self.node(parent)) {
+                Some(parent) => {
+                    transform = parent.transform.mul_transform(&transform);
+                    node = parent;
+                }
+                None => return transform,
+            }
+        }
+
+        log::warn!("'{}' is part of a cycle of parents", node.name);
+
+        transform
+    }
+
+    // global transform of the parent of `id`, the space its local transform is in
+    #[inline]
+    pub fn parent_transform(&self, id: &NodeId) -> Transform {
+        match self.node(id).and_then(|node| node.parent) {
+            Some(parent) => self.global_transform(&parent),
+            None => Transform::IDENTITY,
+        }
+    }
+
+    // true if `id` is `ancestor` or one of its descendants
+    pub fn descends_from(&self, mut id: NodeId, ancestor: NodeId) -> bool {
+        // bounded, so a cycle of parents can't loop forever
+        for _ in 0..=self.nodes.len() {
+            if id == ancestor {
+                return true;
+            }
+
+            match self.node(&id).and_then(|node| node.parent) {
+                Some(parent) => id = parent,
+                None => return false,
+            }
+        }
+
+        false
+    }
+
+    #[inline]
+    pub fn update(&mut self, resources: &Resources, delta_time: f32) {
+        self.delta_time = delta_time;
+        self.time += delta_time;
+
         let ids = self.nodes.keys().cloned().collect::<Vec<_>>();
 
         for id in ids {
             let mut node = self.nodes.remove(&id).unwrap();
 
-            node.update(id, resources, self);
+            node.update(id, self);
 
             self.nodes.insert(id, node);
         }
+
+        if cfg!(debug_assertions) {
+            self.validate_transforms();
+        }
+
+        self.update_transforms();
+        self.check_bounds();
+        self.rebuild_spatial(resources);
+    }
+
+    // fills `left_bounds` with nodes whose origin moved outside the world bounds since the last
+    // check, and despawns them if the policy says so
+    pub fn check_bounds(&mut self) {
+        self.left_bounds.clear();
+
+        let (min, max) = match self.data.bounds {
+            Some(bounds) => bounds,
+            None => {
+                self.outside_bounds.clear();
+                return;
+            }
+        };
+
+        let mut outside = HashSet::new();
+
+        for (id, node) in &self.nodes {
+            let position = node.global.translation();
+
+            if position.cmplt(min).any() || position.cmpgt(max).any() {
+                outside.insert(*id);
+
+                if !self.outside_bounds.contains(id) {
+                    self.left_bounds.push(*id);
+                }
+            }
+        }
+
+        self.left_bounds.sort();
+
+        if self.data.out_of_bounds == OutOfBounds::Despawn && !self.left_bounds.is_empty() {
+            let despawned = self
+                .nodes
+                .keys()
+                .copied()
+                .filter(|id| {
+                    self.left_bounds
+                        .iter()
+                        .any(|left| self.descends_from(*id, *left))
+                })
+                .collect::<Vec<_>>();
+
+            for id in despawned {
+                outside.remove(&id);
+                self.despawn(&id);
+            }
+        }
+
+        self.outside_bounds = outside;
+    }
+
+    // refills the spatial hash from the global bounds of every node, nodes without a mesh are
+    // inserted as a point
+    pub fn rebuild_spatial(&mut self, resources: &Resources) {
+        self.spatial.clear();
+
+        let mut mesh_bounds = HashMap::new();
+
+        for (id, node) in &self.nodes {
+            let bounds = node
+                .components
+                .iter()
+                .find_map(|component| match component {
+                    Component::Mesh { mesh, .. } => *mesh_bounds
+                        .entry(mesh.as_str())
+                        .or_insert_with(|| resources.get_mesh(mesh).and_then(|mesh| mesh.bounds())),
+                    _ => None,
+                });
+
+            let bounds = match bounds {
+                Some(bounds) => bounds.transform(node.global.matrix),
+                None => {
+                    let point = node.global.translation();
+                    Aabb {
+                        min: point,
+                        max: point,
+                    }
+                }
+            };
+
+            self.spatial.insert(*id, bounds);
+        }
+    }
+
+    // evaluates ambient and directional diffuse lighting per vertex into a copy of every
+    // node's mesh, specular is view dependent and left out
+    pub fn bake_lighting(&mut self, resources: &mut Resources) -> usize {
+        self.update_transforms();
+
+        let settings = &self.data.render_settings;
+        let ambient = settings.ambient_color * settings.ambient_strength;
+
+        let lights = self
+            .iter_components(Component::LIGHT)
+            .filter_map(|(_, component)| match component {
+                Component::Light {
+                    kind: LightKind::Directional { direction, .. },
+                    color,
+                    strength,
+                } => Some((-direction.normalize_or_zero(), *color * *strength)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut baked_count = 0;
+
+        for (id, node) in &mut self.nodes {
+            let normal_matrix = normal_matrix(node.global.matrix);
+
+            for component in &mut node.components {
+                if let Component::Mesh {
+                    mesh,
+                    material,
+                    baked,
+                    ..
+                } = component
+                {
+                    let mut baked_mesh = match resources.get_mesh(mesh.as_str()) {
+                        Some(source) => source.clone(),
+                        None => continue,
+                    };
+
+                    for vertex in &mut baked_mesh.vertices {
+                        let normal = normal_matrix
+                            .transform_vector3(vertex.normal)
+                            .normalize_or_zero();
+
+                        let light = lights.iter().fold(ambient, |light, (direction, color)| {
+                            light + *color * direction.dot(normal).max(0.0)
+                        });
+
+                        vertex.color = (vertex.color.truncate() * light).extend(vertex.color.w);
+                    }
+
+                    baked_mesh.generate_buffers();
+
+                    let key = format!("{}#baked{}", mesh, id.0);
+                    resources.meshes.insert(PathBuf::from(&key), baked_mesh);
+
+                    let mut baked_material = *material;
+                    baked_material.lighting_baked = 1;
+
+                    *baked = Some((key, baked_material));
+                    baked_count += 1;
+                }
+            }
+        }
+
+        baked_count
     }
 
+    // grid resolution and starting distance of each baked lod
+    pub const LOD_LEVELS: [(u32, f32); 2] = [(24, 20.0), (8, 50.0)];
+
+    // generates decimated lods for every mesh node, meshes already baked are reused
+    pub fn bake_lods(&mut self, resources: &mut Resources) -> usize {
+        let mut baked = 0;
+
+        for node in self.nodes.values_mut() {
+            for component in &mut node.components {
+                if let Component::Mesh { mesh, lods, .. } = component {
+                    for (i, (resolution, distance)) in Self::LOD_LEVELS.iter().enumerate() {
+                        let key = format!("{}#lod{}", mesh, i + 1);
+
+                        if resources.get_mesh(&key).is_none() {
+                            let mut lod = match resources.get_mesh(mesh.as_str()) {
+                                Some(source) => source.decimate(*resolution),
+                                None => continue,
+                            };
+
+                            lod.generate_buffers();
+
+                            resources.meshes.insert(PathBuf::from(&key), lod);
+                            baked += 1;
+                        }
+
+                        if !lods.iter().any(|lod| lod.mesh == key) {
+                            lods.push(Lod {
+                                mesh: key,
+                                distance: *distance,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        baked
+    }
+
+    // writes every mesh component to a single .gltf at `path` with node transforms baked in,
+    // returns the number of meshes exported
+    pub fn export_gltf(&self, resources: &Resources, path: &Path) -> anyhow::Result<usize> {
+        let mut ids = self.nodes.keys().copied().collect::<Vec<_>>();
+        ids.sort();
+
+        let mut meshes = Vec::new();
+
+        for id in ids {
+            let node = &self.nodes[&id];
+            let matrix = node.global.matrix;
+            let normal_matrix = normal_matrix(matrix);
+            let mirrored = matrix.determinant() < 0.0;
+
+            for component in &node.components {
+                if let Component::Mesh {
+                    mesh,
+                    albedo,
+                    emission,
+                    normal,
+                    material,
+                    ..
+                } = component
+                {
+                    let source = match resources.get_mesh(mesh.as_str()) {
+                        Some(source) => source,
+                        None => {
+                            log::warn!("mesh '{}' of '{}' isn't loaded", mesh, node.name);
+                            continue;
+                        }
+                    };
+
+                    // gltf doesn't allow empty accessors
+                    if source.vertices.is_empty() || source.indices.is_empty() {
+                        log::warn!("mesh '{}' of '{}' is empty, skipping it", mesh, node.name);
+                        continue;
+                    }
+
+                    let vertices = source
+                        .vertices
+                        .iter()
+                        .map(|vertex| Vertex {
+                            position: matrix.transform_point3(vertex.position),
+                            normal: normal_matrix
+                                .transform_vector3(vertex.normal)
+                                .normalize_or_zero(),
+                            // mirroring flips which way the bitangent points
+                            tangent: matrix
+                                .transform_vector3(vertex.tangent.truncate())
+                                .normalize_or_zero()
+                                .extend(if mirrored {
+                                    -vertex.tangent.w
+                                } else {
+                                    vertex.tangent.w
+                                }),
+                            ..*vertex
+                        })
+                        .collect();
+
+                    // mirroring also turns the triangles inside out, swapping two corners of
+                    // each turns them back
+                    let mut indices = source.indices.clone();
+
+                    if mirrored {
+                        for triangle in indices.chunks_exact_mut(3) {
+                            triangle.swap(1, 2);
+                        }
+                    }
+
+                    let texture = |path: &str| {
+                        if path.is_empty() {
+                            None
+                        } else {
+                            resources.file_path(path).map(PathBuf::as_path)
+                        }
+                    };
+
+                    meshes.push(ExportMesh {
+                        name: &node.name,
+                        vertices,
+                        indices,
+                        material,
+                        albedo: texture(albedo),
+                        emission: texture(emission),
+                        normal: texture(normal),
+                    });
+                }
+            }
+        }
+
+        crate::gltf::export(&meshes, path)?;
+
+        Ok(meshes.len())
+    }
+
+    // nodes within `radius` of `point`, as of the last update
     #[inline]
-    pub fn render<'a>(&'a mut self, resources: &'a Resources, frame: &mut Frame<'a>) {
+    pub fn nearby(&self, point: glam::Vec3, radius: f32) -> Vec<NodeId> {
+        self.spatial.nearby(point, radius)
+    }
+
+    // resets transforms poisoned by nan or inf, which would otherwise spread through the
+    // global matrices and render nothing
+    pub fn validate_transforms(&mut self) {
+        for (id, node) in &mut self.nodes {
+            if !node.transform.is_finite() {
+                log::error!(
+                    "node [{}] '{}' has a non-finite transform, resetting it",
+                    id.0,
+                    node.name
+                );
+
+                node.transform = Transform::IDENTITY;
+            }
+        }
+    }
+
+    // recomputes global matrices, only visiting the subtrees whose local transforms changed
+    pub fn update_transforms(&mut self) {
+        let mut children: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+        let mut roots = Vec::new();
+
+        for (id, node) in &mut self.nodes {
+            node.global.check_local(&node.transform);
+
+            match node.parent {
+                Some(parent) if parent != *id => children.entry(parent).or_default().push(*id),
+                _ => roots.push(*id),
+            }
+        }
+
+        // nodes with a missing parent are treated as roots
+        for (parent, ids) in &children {
+            if !self.nodes.contains_key(parent) {
+                roots.extend(ids);
+            }
+        }
+
+        let mut stack = roots
+            .into_iter()
+            .map(|id| (id, glam::Mat4::IDENTITY, false))
+            .collect::<Vec<_>>();
+
+        while let Some((id, parent_matrix, parent_dirty)) = stack.pop() {
+            let node = self.nodes.get_mut(&id).unwrap();
+
+            let dirty = parent_dirty || node.global.dirty;
+
+            if dirty {
+                node.global.recompute(parent_matrix, &node.transform);
+                node.changed();
+            }
+
+            let matrix = node.global.matrix;
+
+            if let Some(ids) = children.get(&id) {
+                stack.extend(ids.iter().map(|child| (*child, matrix, dirty)));
+            }
+        }
+    }
+
+    // changes whenever a node that casts or lights shadows changes, is added or is removed, the
+    // main camera moves a mesh across a lod or draw distance, or the sun changes
+    pub fn shadow_key(&self) -> u64 {
+        // shadows are rendered with the first view
+        let camera_position = self.camera().map_or(glam::Vec3::ZERO, |camera| {
+            self.nodes[&camera].global.translation()
+        });
+
+        // summed so the key doesn't depend on the map's iteration order
+        let nodes = self
+            .query(|node| {
+                node.components.iter().any(|component| {
+                    matches!(
+                        component,
+                        Component::Mesh { .. }
+                            | Component::Scatter { .. }
+                            | Component::Light { .. }
+                    )
+                })
+            })
+            .fold(0u64, |key, (id, node)| {
+                let mut hasher = DefaultHasher::new();
+                (id.0, node.version).hash(&mut hasher);
+
+                for component in &node.components {
+                    component
+                        .draw_selection(&node.global, camera_position)
+                        .hash(&mut hasher);
+                }
+
+                key.wrapping_add(hasher.finish())
+            });
+
+        let mut hasher = DefaultHasher::new();
+        nodes.hash(&mut hasher);
+        self.data
+            .sun
+            .as_ref()
+            .map(|sun| {
+                (
+                    bytes_of(&sun.direction),
+                    bytes_of(&sun.color),
+                    sun.strength.to_bits(),
+                    sun.casts_shadow,
+                )
+            })
+            .hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // renders one frame per camera in `cameras` order, each starting out as `new_frame()`, so
+    // the main camera comes first, without cameras a single frame is rendered from the origin
+    pub fn render_cameras<'a>(
+        &'a mut self,
+        resources: &'a Resources,
+        mut new_frame: impl FnMut() -> Frame<'a>,
+    ) -> Vec<Frame<'a>> {
+        let mut shadows_changed = self.prepare_render();
+
+        let world = &*self;
+        let mut cameras = world.cameras().into_iter().map(Some).collect::<Vec<_>>();
+
+        if cameras.is_empty() {
+            cameras.push(None);
+        }
+
+        cameras
+            .into_iter()
+            .map(|camera| {
+                let mut frame = new_frame();
+                world.render_camera(resources, camera, shadows_changed, &mut frame);
+
+                // every view shares the shadow maps, the first one renders them
+                shadows_changed = false;
+
+                frame
+            })
+            .collect()
+    }
+
+    // brings transforms up to date and returns whether shadows have to be rendered again
+    fn prepare_render(&mut self) -> bool {
+        if cfg!(debug_assertions) {
+            self.validate_transforms();
+        }
+
+        self.update_transforms();
+
+        let shadow_key = self.shadow_key();
+        let shadows_changed = self.refresh_shadows || self.last_shadow_key != Some(shadow_key);
+        self.refresh_shadows = false;
+        self.last_shadow_key = Some(shadow_key);
+
+        shadows_changed
+    }
+
+    fn render_camera<'a>(
+        &'a self,
+        resources: &'a Resources,
+        camera: Option<NodeId>,
+        shadows_changed: bool,
+        frame: &mut Frame<'a>,
+    ) {
         frame.bloom = self.data.render_settings.bloom;
         frame.ambient_color = self.data.render_settings.ambient_color;
         frame.ambient_strength = self.data.render_settings.ambient_strength;
+        frame.environment = resources.get_texture(&self.data.render_settings.environment);
+        frame.environment_strength = self.data.render_settings.environment_strength;
+        frame.baked_lighting = self.data.render_settings.baked_lighting;
+        frame.scene_bounds = self.spatial.bounds();
+        frame.ssr = self.data.render_settings.ssr;
+        frame.ssr_steps = self.data.render_settings.ssr_steps;
+        frame.ssr_max_distance = self.data.render_settings.ssr_max_distance;
+        frame.dof = self.data.render_settings.dof;
+        frame.focus_distance = self.data.render_settings.focus_distance;
+        frame.aperture = self.data.render_settings.aperture;
+        frame.tonemap = self.data.render_settings.tonemap;
+        frame.exposure = self.data.render_settings.exposure;
+        frame.pcss = self.data.render_settings.pcss;
+        frame.shadow_softness = self.data.render_settings.shadow_softness;
+        frame.light_size = self.data.render_settings.light_size;
+        frame.shadows_changed = shadows_changed;
 
-        for (id, node) in &mut self.nodes {
+        // bound before anything renders, meshes pick their lod by the camera's position
+        if let Some(camera) = camera {
+            if let Some(component) = self.camera_component(camera) {
+                component.bind_camera(&self.nodes[&camera].global, frame);
+            }
+        }
+
+        let mut batches: HashMap<&str, Vec<(NodeId, &GlobalTransform, &Component)>> =
+            HashMap::new();
+
+        for (id, node) in &self.nodes {
+            frame.node_id = id.pick_id();
             node.render(*id, resources, frame);
+
+            for component in &node.components {
+                if let Component::Mesh {
+                    batch: Some(batch), ..
+                } = component
+                {
+                    if component.beyond_draw_distance(&node.global, frame) {
+                        continue;
+                    }
+
+                    batches
+                        .entry(batch)
+                        .or_default()
+                        .push((*id, &node.global, component));
+                }
+            }
+        }
+
+        if frame.directional_lights().is_empty() {
+            if let Some(sun) = self.data.sun {
+                frame.add_directional_light(sun);
+            }
+        }
+
+        for (batch, members) in batches {
+            let mut meshes = members
+                .iter()
+                .filter_map(|(_, _, component)| match component {
+                    Component::Mesh { mesh, .. } => Some(mesh),
+                    _ => None,
+                });
+
+            let first = meshes.next();
+
+            if meshes.all(|mesh| Some(mesh) == first) {
+                let transforms = members.iter().map(|(_, global, _)| global.matrix).collect();
+                let ids = members.iter().map(|(id, _, _)| id.pick_id()).collect();
+
+                let (_, _, component) = members[0];
+                component.render_instanced(resources, transforms, ids, frame);
+
+                for (id, global, component) in &members {
+                    if let Component::Mesh {
+                        mesh,
+                        wireframe: true,
+                        ..
+                    } = component
+                    {
+                        if let Some(mesh) = resources.get_mesh(mesh) {
+                            frame.node_id = id.pick_id();
+                            frame.render_wireframe(mesh, global.matrix);
+                        }
+                    }
+                }
+            } else {
+                if self.batch_warnings.borrow_mut().insert(batch.to_string()) {
+                    log::warn!(
+                        "batch '{}' mixes different meshes, drawing them individually",
+                        batch
+                    );
+                }
+
+                for (id, global, component) in members {
+                    frame.node_id = id.pick_id();
+                    component.render_mesh(resources, global, frame);
+                }
+            }
+        }
+
+        // whatever is drawn after the world isn't a node
+        frame.node_id = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::{Grid, GridPlane, Renderer};
+
+    #[test]
+    fn parses_inline_world() {
+        let world = World::from_ron(
+            r#"(
+                data: (
+                    render_settings: (bloom: 0.3),
+                    player: None,
+                    seed: 7,
+                ),
+                nodes: {
+                    (1): (
+                        name: "Camera",
+                        transform: (
+                            translation: (0, 2, 5),
+                            rotation: (0, 0, 0, 1),
+                            scale: (1, 1, 1),
+                        ),
+                        components: [Camera(fov: 60, near: 0.1, far: 100)],
+                    ),
+                },
+                next_node_id: (2),
+            )"#,
+        )
+        .unwrap();
+
+        assert_eq!(world.data.render_settings.bloom, 0.3);
+        assert_eq!(world.data.seed, 7);
+        assert!(world.next_node_id == NodeId(2));
+
+        let camera = &world.nodes[&NodeId(1)];
+        assert_eq!(camera.transform.translation, glam::Vec3::new(0.0, 2.0, 5.0));
+
+        // the legacy fov was migrated into the projection
+        match camera.components[..] {
+            [Component::Camera {
+                projection: Projection::Perspective { fov },
+                fov: None,
+                ..
+            }] => assert_eq!(fov, 60.0),
+            _ => panic!("expected a migrated perspective camera"),
+        }
+
+        // the embedded default world parses too
+        assert!(World::from_ron(DEFAULT_WORLD).unwrap().nodes.is_empty());
+        assert!(World::from_ron("(nodes: [])").is_err());
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn import_scale_shrinks_mesh_bounds() {
+        let instance = futures::executor::block_on(Instance::headless()).expect("no GPU adapter");
+
+        // the same triangle twice, one authored in centimeters
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/triangle.glb");
+        let dir = std::env::temp_dir().join("phil-noire-import-scale");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::copy(&fixture, dir.join("meters.glb")).unwrap();
+        std::fs::copy(&fixture, dir.join("centimeters.glb")).unwrap();
+        std::fs::write(dir.join("centimeters.glb.import"), "(scale: 0.01)").unwrap();
+
+        let mut resources = Resources::new(&instance);
+        resources.load_assets(&dir).unwrap();
+
+        let bounds = |name: &str| resources.get_mesh(name).unwrap().bounds().unwrap();
+        let (meters, centimeters) = (bounds("meters.glb"), bounds("centimeters.glb"));
+
+        assert!((centimeters.size() * 100.0).abs_diff_eq(meters.size(), 1e-5));
+        assert!((centimeters.min * 100.0).abs_diff_eq(meters.min, 1e-5));
+
+        // normals aren't scaled
+        let normal = |name: &str| resources.get_mesh(name).unwrap().vertices[0].normal;
+        assert_eq!(normal("centimeters.glb"), normal("meters.glb"));
+    }
+
+    #[test]
+    fn import_sidecar_sets_scale() {
+        let dir = std::env::temp_dir().join("phil-noire-import-sidecar");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mesh = dir.join("desk.gltf");
+        std::fs::write(dir.join("desk.gltf.import"), "(scale: 0.01)").unwrap();
+
+        let settings = ImportSettings::load(&mesh).unwrap().unwrap();
+        assert_eq!(settings.scale, 0.01);
+
+        // meshes without one use the global default
+        assert!(ImportSettings::load(&dir.join("chair.gltf"))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn gltf_parts_spawn_as_child_nodes() {
+        // a car body with a wheel under it, both drawing the same triangle
+        let gltf = Gltf::from_slice(
+            br#"{
+                "asset": { "version": "2.0" },
+                "scene": 0,
+                "scenes": [{ "nodes": [0] }],
+                "nodes": [
+                    { "name": "Body", "mesh": 0, "translation": [0, 2, 0], "children": [1] },
+                    { "name": "Wheel", "mesh": 1, "translation": [1, -1, 0] }
+                ],
+                "meshes": [
+                    { "primitives": [{ "attributes": { "POSITION": 0 } }] },
+                    { "primitives": [{ "attributes": { "POSITION": 0 } }] }
+                ],
+                "buffers": [{ "byteLength": 36, "uri": "car.bin" }],
+                "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+                "accessors": [{
+                    "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                    "min": [0, 0, 0], "max": [1, 1, 0]
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        let mut world = World::new();
+        let root = world.spawn_gltf_scene(&gltf, "car.gltf", 0.5).unwrap();
+        world.update_transforms();
+
+        let children = |parent: NodeId| {
+            world
+                .nodes
+                .iter()
+                .filter(|(_, node)| node.parent == Some(parent))
+                .map(|(id, _)| *id)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(world.nodes[&root].name, "car");
+
+        let body = children(root);
+        assert_eq!(body.len(), 1);
+        let wheel = children(body[0]);
+        assert_eq!(wheel.len(), 1);
+
+        let (body, wheel) = (&world.nodes[&body[0]], &world.nodes[&wheel[0]]);
+        assert_eq!(wheel.name, "Wheel");
+
+        // offsets are scaled along with the meshes, so the wheel stays under the body
+        assert_eq!(body.transform.translation, glam::Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(wheel.transform.translation, glam::Vec3::new(0.5, -0.5, 0.0));
+        assert_eq!(wheel.global.translation(), glam::Vec3::new(0.5, 0.5, 0.0));
+
+        match (&body.components[..], &wheel.components[..]) {
+            ([Component::Mesh { mesh: a, .. }], [Component::Mesh { mesh: b, .. }]) => {
+                assert_eq!(a, "car.gltf#mesh0");
+                assert_eq!(b, "car.gltf#mesh1");
+            }
+            _ => panic!("expected a mesh on each part"),
+        }
+    }
+
+    #[test]
+    fn worlds_with_the_same_seed_play_out_the_same() {
+        let saved = |seed: u64| {
+            format!("(data: (render_settings: (), player: None, seed: {}), nodes: {{}}, next_node_id: (0))", seed)
+        };
+        let rolls = |world: &mut World| (0..16).map(|_| world.rng.next_u64()).collect::<Vec<_>>();
+
+        let mut a = World::from_ron(&saved(1234)).unwrap();
+        let mut b = World::from_ron(&saved(1234)).unwrap();
+        let mut c = World::from_ron(&saved(1235)).unwrap();
+
+        let first = rolls(&mut a);
+        assert_eq!(first, rolls(&mut b));
+        assert_ne!(first, rolls(&mut c));
+
+        // restarting replays the same rolls rather than continuing
+        a.reseed();
+        assert_eq!(rolls(&mut a), first);
+    }
+
+    #[test]
+    fn later_roots_fill_in_missing_assets() {
+        let base = PathBuf::from("game/base");
+        let mods = PathBuf::from("mods/extra");
+        let roots = [base.clone(), mods.clone()];
+
+        let mut files = HashMap::new();
+        for path in [
+            base.join("level.world"),
+            mods.join("level.world"),
+            mods.join("bonus.world"),
+        ] {
+            files.entry(asset_key(&roots, &path)).or_insert(path);
+        }
+
+        // a file only the second root has resolves there
+        let bonus = find_asset(&roots, &files, Path::new("bonus.world"));
+        assert_eq!(bonus, Some(&mods.join("bonus.world")));
+        assert_eq!(
+            asset_key(&roots, &mods.join("bonus.world")),
+            Path::new("extra/bonus.world")
+        );
+
+        // a file both roots have resolves to the first
+        let level = find_asset(&roots, &files, Path::new("level.world"));
+        assert_eq!(level, Some(&base.join("level.world")));
+
+        assert_eq!(find_asset(&roots, &files, Path::new("missing.world")), None);
+    }
+
+    #[test]
+    fn query_finds_lights_in_mixed_scene() {
+        let mut world = World::new();
+
+        let light = Component::Light {
+            kind: LightKind::Point { radius: 10.0 },
+            color: glam::Vec3::ONE,
+            strength: 1.0,
+        };
+
+        let mut spawn = |components: Vec<Component>| {
+            world.spawn(Node {
+                name: String::from("Node"),
+                transform: Transform::IDENTITY,
+                parent: None,
+                global: GlobalTransform::default(),
+                components,
+                version: 0,
+            })
+        };
+
+        let lamp = spawn(vec![Component::mesh("lamp.gltf".into()), light.clone()]);
+        spawn(vec![Component::mesh("desk.gltf".into())]);
+        let bulb = spawn(vec![light]);
+        spawn(Vec::new());
+
+        let mut lights = world
+            .query(|node| {
+                node.components
+                    .iter()
+                    .any(|component| component.name() == Component::LIGHT)
+            })
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        lights.sort();
+
+        assert!(lights == [lamp, bulb]);
+
+        let mut components = world
+            .iter_components(Component::LIGHT)
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        components.sort();
+
+        assert!(components == lights);
+    }
+
+    #[test]
+    fn global_transform_walks_parents() {
+        let mut world = World::new();
+
+        let root = world.spawn(Node {
+            name: String::from("Root"),
+            transform: Transform::from_xyz(1.0, 0.0, 0.0),
+            parent: None,
+            global: GlobalTransform::default(),
+            components: Vec::new(),
+            version: 0,
+        });
+        let child = world.spawn(Node {
+            name: String::from("Child"),
+            transform: Transform::from_xyz(0.0, 2.0, 0.0),
+            parent: Some(root),
+            global: GlobalTransform::default(),
+            components: Vec::new(),
+            version: 0,
+        });
+
+        let translation = world.global_transform(&child).translation;
+        assert!(translation.abs_diff_eq(glam::Vec3::new(1.0, 2.0, 0.0), 1e-6));
+        assert_eq!(world.parent_transform(&child).translation, glam::Vec3::X);
+
+        // a cycle of parents still returns
+        world.nodes.get_mut(&root).unwrap().parent = Some(child);
+        world.global_transform(&child);
+    }
+
+    #[test]
+    fn moving_parent_recomputes_only_its_subtree() {
+        let mut world = World::new();
+
+        let mut spawn = |transform: Transform, parent: Option<NodeId>| {
+            world.spawn(Node {
+                name: String::from("Node"),
+                transform,
+                parent,
+                global: GlobalTransform::default(),
+                components: Vec::new(),
+                version: 0,
+            })
+        };
+
+        let root = spawn(Transform::IDENTITY, None);
+        let child = spawn(Transform::from_xyz(0.0, 1.0, 0.0), Some(root));
+        let grandchild = spawn(Transform::from_xyz(0.0, 0.0, 1.0), Some(child));
+        let other = spawn(Transform::from_xyz(5.0, 0.0, 0.0), None);
+
+        world.update_transforms();
+
+        let versions = |world: &World| {
+            [root, child, grandchild, other]
+                .iter()
+                .map(|id| world.nodes[id].version)
+                .collect::<Vec<_>>()
+        };
+
+        // nothing moved, nothing is recomputed
+        let before = versions(&world);
+        world.update_transforms();
+        assert_eq!(versions(&world), before);
+
+        world.nodes.get_mut(&root).unwrap().transform.translation = glam::Vec3::X;
+        world.update_transforms();
+
+        let after = versions(&world);
+        assert!(before[..3].iter().zip(&after[..3]).all(|(b, a)| a != b));
+        assert_eq!(before[3], after[3]);
+
+        for id in &[root, child, grandchild, other] {
+            assert!(!world.nodes[id].global.dirty);
         }
+
+        let translation = world.nodes[&grandchild].global.translation();
+        assert!(translation.abs_diff_eq(glam::Vec3::new(1.0, 1.0, 1.0), 1e-6));
+    }
+
+    #[test]
+    fn mutable_access_bumps_version() {
+        let mut world = World::new();
+        let id = world.spawn(Node {
+            name: String::from("Node"),
+            transform: Transform::IDENTITY,
+            parent: None,
+            global: GlobalTransform::default(),
+            components: Vec::new(),
+            version: 0,
+        });
+
+        let version = world.nodes[&id].version;
+        world.node_mut(&id).unwrap().transform.translation = glam::Vec3::X;
+
+        assert_ne!(world.nodes[&id].version, version);
+    }
+
+    #[test]
+    fn leaving_the_bounds_fires_the_policy() {
+        let mut world = World::new();
+        world.data.bounds = Some((glam::Vec3::splat(-10.0), glam::Vec3::splat(10.0)));
+
+        let mut spawn = |name: &str, parent: Option<NodeId>| {
+            world.spawn(Node {
+                name: String::from(name),
+                transform: Transform::IDENTITY,
+                parent,
+                global: GlobalTransform::default(),
+                components: Vec::new(),
+                version: 0,
+            })
+        };
+
+        let player = spawn("Player", None);
+        let bullet = spawn("Bullet", None);
+        let trail = spawn("Trail", Some(bullet));
+
+        let step = |world: &mut World, id: NodeId, x: f32| {
+            world.node_mut(&id).unwrap().transform.translation.x = x;
+            world.update_transforms();
+            world.check_bounds();
+        };
+
+        // reported once when it leaves, again only after coming back
+        step(&mut world, player, 9.0);
+        assert!(world.left_bounds.is_empty());
+        step(&mut world, player, 10.5);
+        assert!(world.left_bounds == [player]);
+        step(&mut world, player, 11.0);
+        assert!(world.left_bounds.is_empty());
+        step(&mut world, player, 0.0);
+        step(&mut world, player, -12.0);
+        assert!(world.left_bounds == [player]);
+        assert!(world.nodes.contains_key(&player));
+
+        // despawned along with its children, which left with it
+        world.data.out_of_bounds = OutOfBounds::Despawn;
+        step(&mut world, bullet, 20.0);
+        assert!(world.left_bounds.len() == 2 && world.left_bounds.contains(&bullet));
+        assert!(!world.nodes.contains_key(&bullet) && !world.nodes.contains_key(&trail));
+    }
+
+    #[test]
+    fn camera_crossing_lod_or_draw_distance_changes_shadows() {
+        let mut world = World::new();
+
+        let camera = world.spawn(Node {
+            name: String::from("Camera"),
+            transform: Transform::from_xyz(0.0, 0.0, 5.0),
+            parent: None,
+            global: GlobalTransform::default(),
+            components: vec![Component::Camera {
+                projection: Projection::default(),
+                fov: None,
+                near: 0.1,
+                far: 100.0,
+                viewport: Viewport::default(),
+                auto_clip: false,
+            }],
+            version: 0,
+        });
+
+        let mut lod = Component::mesh("rock.gltf".into());
+        if let Component::Mesh { lods, .. } = &mut lod {
+            lods.push(Lod {
+                mesh: "rock_far.gltf".into(),
+                distance: 10.0,
+            });
+        }
+
+        let mut prop = Component::mesh("pebble.gltf".into());
+        if let Component::Mesh {
+            max_draw_distance, ..
+        } = &mut prop
+        {
+            *max_draw_distance = Some(20.0);
+        }
+
+        world.spawn(Node {
+            name: String::from("Props"),
+            transform: Transform::IDENTITY,
+            parent: None,
+            global: GlobalTransform::default(),
+            components: vec![lod, prop],
+            version: 0,
+        });
+
+        let move_camera = |world: &mut World, z: f32| {
+            world.node_mut(&camera).unwrap().transform.translation.z = z;
+            world.prepare_render()
+        };
+
+        assert!(world.prepare_render());
+        assert!(!world.prepare_render());
+
+        // moving without crossing anything keeps the cached shadows
+        assert!(!move_camera(&mut world, 8.0));
+        assert!(move_camera(&mut world, 12.0), "crossed the lod distance");
+        assert!(!move_camera(&mut world, 15.0));
+        assert!(move_camera(&mut world, 25.0), "crossed the draw distance");
+        assert!(move_camera(&mut world, 19.0), "came back into draw range");
+    }
+
+    #[test]
+    fn mouse_look_turns_main_camera() {
+        let mut world = World::new();
+        let id = world.spawn(Node {
+            name: String::from("Camera"),
+            transform: Transform::from_xyz(0.0, 0.0, 1.0),
+            parent: None,
+            global: GlobalTransform::default(),
+            components: vec![Component::Camera {
+                projection: Projection::default(),
+                fov: None,
+                near: 0.1,
+                far: 100.0,
+                viewport: Viewport::default(),
+                auto_clip: false,
+            }],
+            version: 0,
+        });
+
+        let forward = |world: &World| world.nodes[&id].transform.rotation * -glam::Vec3::Z;
+
+        // moving right turns right, towards +x from looking down -z
+        world.mouse_look(glam::Vec2::new(100.0, 0.0));
+        assert!(forward(&world).x > 0.0);
+        assert!(forward(&world).y.abs() < 1e-4);
+
+        // pitch stops short of straight down however far the mouse moves
+        world.mouse_look(glam::Vec2::new(0.0, 10000.0));
+        let pitch = forward(&world).y.asin();
+        assert!((pitch + 89f32.to_radians()).abs() < 1e-3);
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn unload_mesh_keeps_others() {
+        let instance = futures::executor::block_on(Instance::headless()).expect("no GPU adapter");
+
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/triangle.glb");
+        let dir = std::env::temp_dir().join("phil-noire-unload");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::copy(&fixture, dir.join("a.glb")).unwrap();
+        std::fs::copy(&fixture, dir.join("b.glb")).unwrap();
+
+        let mut resources = Resources::new(&instance);
+        resources.load_assets(&dir).unwrap();
+
+        assert!(resources.unload_mesh("phil-noire-unload/a.glb"));
+
+        assert!(resources.get_mesh("phil-noire-unload/a.glb").is_none());
+        assert!(resources.get_mesh("phil-noire-unload/b.glb").is_some());
+        assert!(resources.file_path("phil-noire-unload/a.glb").is_none());
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn unload_unused_cancels_queued_meshes() {
+        let instance = futures::executor::block_on(Instance::headless()).expect("no GPU adapter");
+
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/triangle.glb");
+        let dir = std::env::temp_dir().join("phil-noire-unload-queued");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::copy(&fixture, dir.join("a.glb")).unwrap();
+        std::fs::copy(&fixture, dir.join("b.glb")).unwrap();
+
+        let mut resources = Resources::new(&instance);
+        resources.set_loader_threads(1);
+        resources.load_assets(&dir).unwrap();
+
+        let (a, b) = (
+            "phil-noire-unload-queued/a.glb",
+            "phil-noire-unload-queued/b.glb",
+        );
+
+        let mut world = World::new();
+        world.spawn(Node {
+            name: String::from("B"),
+            transform: Transform::IDENTITY,
+            parent: None,
+            global: GlobalTransform::default(),
+            components: vec![Component::mesh(b.into())],
+            version: 0,
+        });
+
+        resources.unload_unused(&world);
+
+        while resources.loading_progress().is_some() {
+            resources.poll_loader();
+            std::thread::yield_now();
+        }
+
+        // a result sent before the cancel must not be added either
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        resources.poll_loader();
+
+        assert!(resources.get_mesh(a).is_none());
+        assert!(resources.get_mesh(b).is_some());
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn split_screen_matches_single_views() {
+        let instance = futures::executor::block_on(Instance::headless()).expect("no GPU adapter");
+
+        // the grid is the only thing drawn, it differs between views
+        fn grid_frame<'a>() -> Frame<'a> {
+            let mut frame = Frame::new();
+
+            frame.aspect = 2.0;
+            frame.grid = Some(Grid {
+                plane: GridPlane::XZ,
+                origin: glam::Vec3::ZERO,
+                spacing: 1.0,
+            });
+
+            frame
+        }
+
+        let resources = Resources::new(&instance);
+        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let (width, height) = (64, 32);
+        let mut renderer = Renderer::new(&instance, format, width, height);
+
+        let mut world = World::new();
+
+        // side by side, looking at the grid from different places
+        for (i, position) in [
+            glam::Vec3::new(4.0, 3.0, 4.0),
+            glam::Vec3::new(-2.0, 6.0, 1.0),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let mut transform = Transform::from_translation(*position);
+            transform.look_at(glam::Vec3::ZERO, glam::Vec3::Y);
+
+            world.spawn(Node {
+                name: format!("Camera {}", i),
+                transform,
+                parent: None,
+                global: GlobalTransform::default(),
+                components: vec![Component::Camera {
+                    projection: Projection::default(),
+                    fov: None,
+                    near: 0.1,
+                    far: 100.0,
+                    viewport: Viewport {
+                        x: i as f32 * 0.5,
+                        width: 0.5,
+                        ..Default::default()
+                    },
+                    auto_clip: false,
+                }],
+                version: 0,
+            });
+        }
+
+        let frames = world.render_cameras(&resources, grid_frame);
+        let split = renderer.capture_frames(&instance, frames).unwrap();
+
+        for view in 0..2 {
+            let mut frames = world.render_cameras(&resources, grid_frame);
+            let single = renderer
+                .capture_frames(&instance, vec![frames.remove(view)])
+                .unwrap();
+
+            let x_range = view as u32 * width / 2..(view as u32 + 1) * width / 2;
+
+            for x in x_range {
+                for y in 0..height {
+                    assert_eq!(split.get_pixel(x, y), single.get_pixel(x, y));
+                }
+            }
+        }
+    }
+
+    // prints the average frame time of a scene of a few thousand draws with one and with three
+    // frames in flight, run with `cargo test frame_time -- --ignored --nocapture`
+    #[test]
+    #[ignore = "needs a GPU adapter, measures rather than checks"]
+    fn frames_in_flight_frame_time() {
+        const FRAMES: u32 = 300;
+
+        let instance = futures::executor::block_on(Instance::headless()).expect("no GPU adapter");
+
+        let mut resources = Resources::new(&instance);
+        let mut quad = Mesh::quad(&instance, glam::Vec2::splat(0.5), 0);
+        quad.generate_buffers();
+        resources.meshes.insert("quad".into(), quad);
+
+        let mut world = World::new();
+
+        let mut transform = Transform::from_xyz(0.0, 40.0, 40.0);
+        transform.look_at(glam::Vec3::ZERO, glam::Vec3::Y);
+
+        world.spawn(Node {
+            name: String::from("Camera"),
+            transform,
+            parent: None,
+            global: GlobalTransform::default(),
+            components: vec![Component::Camera {
+                projection: Projection::default(),
+                fov: None,
+                near: 0.1,
+                far: 200.0,
+                viewport: Viewport::default(),
+                auto_clip: false,
+            }],
+            version: 0,
+        });
+
+        for i in 0..4096 {
+            world.spawn(Node {
+                name: format!("Quad {}", i),
+                transform: Transform::from_xyz((i % 64) as f32 - 32.0, 0.0, (i / 64) as f32 - 32.0),
+                parent: None,
+                global: GlobalTransform::default(),
+                components: vec![Component::mesh("quad".into())],
+                version: 0,
+            });
+        }
+
+        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let (width, height) = (1280, 720);
+        let mut renderer = Renderer::new(&instance, format, width, height);
+
+        let texture = instance.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("frame time target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+        });
+        let target = texture.create_view(&Default::default());
+
+        for &frames_in_flight in &[1, 3] {
+            renderer.set_frames_in_flight(&instance, frames_in_flight);
+
+            let start = std::time::Instant::now();
+
+            for _ in 0..FRAMES {
+                let views = world
+                    .render_cameras(&resources, Frame::new)
+                    .into_iter()
+                    .map(|frame| (&target, frame))
+                    .collect::<Vec<_>>();
+
+                renderer.render_frames(&instance, &views);
+            }
+
+            instance.device.poll(wgpu::Maintain::Wait);
+
+            let frame_time = start.elapsed().as_secs_f64() * 1000.0 / FRAMES as f64;
+            eprintln!(
+                "{} frames in flight: {:.3} ms per frame",
+                frames_in_flight, frame_time
+            );
+        }
+    }
+
+    // prints the bind groups created by each of the first frames of a textured scene, steady
+    // state frames should create none, run alone with
+    // `cargo test bind_groups_per_frame -- --ignored --nocapture` as the counter is global
+    #[test]
+    #[ignore = "needs a GPU adapter, measures rather than checks"]
+    fn bind_groups_per_frame() {
+        let instance = futures::executor::block_on(Instance::headless()).expect("no GPU adapter");
+
+        let mut resources = Resources::new(&instance);
+        let mut quad = Mesh::quad(&instance, glam::Vec2::ONE, 0);
+        quad.generate_buffers();
+        resources.meshes.insert("quad".into(), quad);
+
+        let mut world = World::new();
+
+        let mut transform = Transform::from_xyz(0.0, 4.0, 4.0);
+        transform.look_at(glam::Vec3::ZERO, glam::Vec3::Y);
+
+        world.spawn(Node {
+            name: String::from("Camera"),
+            transform,
+            parent: None,
+            global: GlobalTransform::default(),
+            components: vec![Component::Camera {
+                projection: Projection::default(),
+                fov: None,
+                near: 0.1,
+                far: 100.0,
+                viewport: Viewport::default(),
+                auto_clip: false,
+            }],
+            version: 0,
+        });
+
+        for i in 0..16 {
+            world.spawn(Node {
+                name: format!("Quad {}", i),
+                transform: Transform::from_xyz((i % 4) as f32 - 2.0, 0.0, (i / 4) as f32 - 2.0),
+                parent: None,
+                global: GlobalTransform::default(),
+                components: vec![Component::mesh("quad".into())],
+                version: 0,
+            });
+        }
+
+        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let (width, height) = (320, 180);
+        let mut renderer = Renderer::new(&instance, format, width, height);
+
+        let texture = instance.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("bind group target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+        });
+        let target = texture.create_view(&Default::default());
+
+        let created = (0..8)
+            .map(|_| {
+                let views = world
+                    .render_cameras(&resources, Frame::new)
+                    .into_iter()
+                    .map(|frame| (&target, frame))
+                    .collect::<Vec<_>>();
+
+                renderer.render_frames(&instance, &views).bind_groups
+            })
+            .collect::<Vec<_>>();
+
+        instance.device.poll(wgpu::Maintain::Wait);
+
+        eprintln!("bind groups created per frame: {:?}", created);
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn id_buffer_holds_node_under_pixel() {
+        let instance = futures::executor::block_on(Instance::headless()).expect("no GPU adapter");
+
+        fn square_frame<'a>() -> Frame<'a> {
+            let mut frame = Frame::new();
+            frame.aspect = 1.0;
+            frame
+        }
+
+        let mut resources = Resources::new(&instance);
+        let mut quad = Mesh::quad(&instance, glam::Vec2::splat(2.0), 0);
+        quad.generate_buffers();
+        resources.meshes.insert("quad".into(), quad);
+
+        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let mut renderer = Renderer::new(&instance, format, 32, 32);
+
+        let mut world = World::new();
+
+        let mut transform = Transform::from_xyz(0.0, 3.0, 1.0);
+        transform.look_at(glam::Vec3::ZERO, glam::Vec3::Y);
+
+        world.spawn(Node {
+            name: String::from("Camera"),
+            transform,
+            parent: None,
+            global: GlobalTransform::default(),
+            components: vec![Component::Camera {
+                projection: Projection::default(),
+                fov: None,
+                near: 0.1,
+                far: 100.0,
+                viewport: Viewport::default(),
+                auto_clip: false,
+            }],
+            version: 0,
+        });
+        let quad = world.spawn(Node {
+            name: String::from("Quad"),
+            transform: Transform::IDENTITY,
+            parent: None,
+            global: GlobalTransform::default(),
+            components: vec![Component::mesh("quad".into())],
+            version: 0,
+        });
+
+        let frames = world.render_cameras(&resources, square_frame);
+        renderer.capture_frames(&instance, frames).unwrap();
+
+        // the quad fills the middle of the view, the corners look past it
+        let center = renderer.read_id(&instance, glam::UVec2::new(16, 16));
+        assert!(center.and_then(NodeId::from_pick_id) == Some(quad));
+        assert_eq!(renderer.read_id(&instance, glam::UVec2::ZERO), Some(0));
     }
 }